@@ -2,30 +2,75 @@
 
 struct CameraUniform {
     view_proj: mat4x4<f32>,
+    position: vec4<f32>,
 };
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
 
+// Two words packed by `Vertex::new`
 struct VertexInput {
-    @location(0) position: vec3<f32>,
-    @location(1) tex_coords: vec2<f32>,
-    @location(2) light: f32,
+    @location(0) packed: u32,
+    @location(1) light: u32,
+};
+
+struct InstanceInput {
+    @location(2) origin: vec3<f32>,
+    // Distant chunks are meshed in cells of several blocks
+    @location(3) scale: f32,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
     @location(1) light: f32,
+    @location(2) block_light: vec3<f32>,
+    @location(3) world_position: vec3<f32>,
 };
 
+// Fixed shading per face, indexed by the face's `Direction` id
+fn face_shade(face: u32) -> f32 {
+    switch (face) {
+        case 0u: {
+            return 1.0;
+        }
+        case 2u, 3u: {
+            return 0.5;
+        }
+        case 4u: {
+            return 0.75;
+        }
+        default: {
+            return 0.25;
+        }
+    }
+}
+
 @vertex
 fn vs_main(
     model: VertexInput,
+    instance: InstanceInput,
 ) -> VertexOutput {
+    let position = vec3<f32>(
+        f32(model.packed & 31u),
+        f32((model.packed >> 5u) & 31u),
+        f32((model.packed >> 10u) & 31u),
+    );
+    let face = (model.packed >> 15u) & 7u;
+    let u = (model.packed >> 18u) & 1u;
+    let v = (model.packed >> 19u) & 1u;
+    let texture = (model.packed >> 20u) & 255u;
+    let ambient_occlusion = f32((model.light >> 12u) & 3u);
+
     var out: VertexOutput;
-    out.tex_coords = model.tex_coords;
-    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
-    out.light = model.light;
+    out.tex_coords = vec2<f32>(f32(texture + u) / 256.0, f32(v));
+    out.world_position = instance.origin + position * instance.scale;
+    out.clip_position = camera.view_proj * vec4<f32>(out.world_position, 1.0);
+    out.light = face_shade(face) * (1.0 - ambient_occlusion * 0.2);
+    out.block_light = vec3<f32>(
+        f32(model.light & 15u),
+        f32((model.light >> 4u) & 15u),
+        f32((model.light >> 8u) & 15u),
+    ) / 15.0;
     return out;
 }
 
@@ -36,8 +81,57 @@ var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 
+struct SkyUniform {
+    sun_direction: vec4<f32>,
+    zenith_color: vec4<f32>,
+    horizon_color: vec4<f32>,
+    fog_color: vec4<f32>,
+    sky_light: f32,
+    fog_start: f32,
+    fog_end: f32,
+    fog_density: f32,
+    fog_mode: u32,
+    sky_fog: f32,
+};
+@group(2) @binding(0)
+var<uniform> sky: SkyUniform;
+
+// How much of a fragment at the given distance from the camera is hidden by fog
+fn fog_factor(distance: f32) -> f32 {
+    switch (sky.fog_mode) {
+        case 1u: {
+            return 1.0 - clamp(exp(-sky.fog_density * distance), 0.0, 1.0);
+        }
+        case 2u: {
+            let amount = sky.fog_density * distance;
+            return 1.0 - clamp(exp(-amount * amount), 0.0, 1.0);
+        }
+        default: {
+            return clamp((distance - sky.fog_start) / (sky.fog_end - sky.fog_start), 0.0, 1.0);
+        }
+    }
+}
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    // Coloured block light is added on top of the sky light so emitters tint their surroundings
+    let light : vec3<f32> = in.light * (vec3<f32>(sky.sky_light) + in.block_light);
+    let lit : vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords) * vec4<f32>(light, 1.0);
+    let fog = fog_factor(distance(in.world_position, camera.position.xyz));
+    return vec4<f32>(mix(lit.rgb, sky.fog_color.rgb, fog), lit.a);
+}
+
+// Used by both the opaque and the alpha blended translucent pipelines
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let lit : vec4<f32> = textureSample(t_diffuse, s_diffuse, in.tex_coords) * vec4<f32>(in.light, in.light, in.light, 1.0);
-    return lit;
-}
\ No newline at end of file
+    return shade(in);
+}
+
+// Alpha tested geometry such as leaves, where pixels are either fully opaque or not drawn at all
+@fragment
+fn fs_cutout(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = shade(in);
+    if (color.a < 0.5) {
+        discard;
+    }
+    return vec4<f32>(color.rgb, 1.0);
+}
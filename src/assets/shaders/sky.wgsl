@@ -0,0 +1,67 @@
+// Vertex shader
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct SkyUniform {
+    sun_direction: vec4<f32>,
+    zenith_color: vec4<f32>,
+    horizon_color: vec4<f32>,
+    fog_color: vec4<f32>,
+    sky_light: f32,
+    fog_start: f32,
+    fog_end: f32,
+    fog_density: f32,
+    fog_mode: u32,
+    sky_fog: f32,
+};
+@group(1) @binding(0)
+var<uniform> sky: SkyUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) direction: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    // The cube follows the camera so it never gets any closer
+    out.clip_position = camera.view_proj * vec4<f32>(camera.position.xyz + position, 1.0);
+    out.direction = position;
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = normalize(in.direction);
+    let sun = sky.sun_direction.xyz;
+
+    let height = clamp(direction.y, 0.0, 1.0);
+    var color = mix(sky.horizon_color.rgb, sky.zenith_color.rgb, sqrt(height));
+
+    // Sun and moon are opposite each other, drawn as discs with a soft glow around the sun
+    let sun_dot = dot(direction, sun);
+    let moon_dot = dot(direction, -sun);
+    color = color + vec3<f32>(1.0, 0.8, 0.5) * pow(max(sun_dot, 0.0), 64.0) * 0.5;
+    if (sun_dot > 0.9985) {
+        color = vec3<f32>(1.0, 0.95, 0.8);
+    }
+    if (moon_dot > 0.999) {
+        color = vec3<f32>(0.8, 0.85, 0.9);
+    }
+
+    // Blend the horizon into the fog, or hide the sky completely when inside a fluid
+    let horizon_fog = 1.0 - smoothstep(0.0, 0.2, abs(direction.y));
+    color = mix(color, sky.fog_color.rgb, max(horizon_fog, sky.sky_fog));
+
+    return vec4<f32>(color, 1.0);
+}
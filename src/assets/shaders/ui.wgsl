@@ -0,0 +1,43 @@
+// Vertex shader
+
+struct ScreenUniform {
+    // Width and height of the window in UI pixels
+    size: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> screen: ScreenUniform;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    // UI pixels start at the top left corner, clip space at the bottom left
+    let position = model.position / screen.size.xy * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(position.x, -position.y, 0.0, 1.0);
+    out.uv = model.uv;
+    out.color = model.color;
+    return out;
+}
+
+// Fragment shader
+
+@group(0) @binding(0)
+var t_atlas: texture_2d<f32>;
+@group(0) @binding(1)
+var s_atlas: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_atlas, s_atlas, in.uv) * in.color;
+}
@@ -3,7 +3,7 @@ use toml::value::{Table, Array};
 use once_cell::sync::OnceCell;
 use std::fs;
 
-use crate::direction::Direction;
+use crate::{direction::Direction, light::Light};
 
 pub static BLOCK_TYPES: OnceCell<Vec<Option<Type>>> = OnceCell::new();
 
@@ -12,16 +12,79 @@ pub struct Type {
     pub name: String,
     pub id: u32,
     textures: Table,
+    #[serde(default)]
+    emission: Light,
+    #[serde(default)]
+    fluid: Option<Fluid>,
+    #[serde(default)]
+    render: RenderType,
     pub states: Array
 }
 
+/// How a block's faces are drawn. Anything other than `Opaque` lets light and visibility through.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderType {
+    #[default]
+    Opaque,
+    /// Pixels are either fully opaque or fully transparent, like leaves.
+    Cutout,
+    /// Alpha blended, like water or stained glass.
+    Translucent,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Fluid {
+    pub fog_color: [f32; 3],
+    pub fog_distance: f32,
+}
+
 impl Type {
     pub fn get_texture(&self, direction: Direction) -> u32 {
         self.textures.get(&direction.get_string()).unwrap().as_integer().unwrap() as u32
     }
+
+    pub fn get_emission(&self) -> Light {
+        self.emission
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission != [0; 3]
+    }
+
+    pub fn get_fluid(&self) -> Option<&Fluid> {
+        self.fluid.as_ref()
+    }
+
+    pub fn get_render_type(&self) -> RenderType {
+        self.render
+    }
+
+    /// Whether the block stops light from passing through it and hides whatever is behind it.
+    pub fn is_opaque(&self) -> bool {
+        self.id != 0 && self.render == RenderType::Opaque
+    }
+
+    /// Whether the player collides with the block, everything but air and fluids.
+    pub fn is_solid(&self) -> bool {
+        self.id != 0 && self.fluid.is_none()
+    }
 }
 
 pub fn init() {
+    match BLOCK_TYPES.set(load()) {
+        Ok(_) => {}
+        Err(types) => panic!("Failed to initialize block types: {:?}", types)
+    }
+}
+
+/// Tests share one process, so unlike `init` this may be called any number of times.
+#[cfg(test)]
+pub fn init_for_tests() {
+    BLOCK_TYPES.get_or_init(load);
+}
+
+fn load() -> Vec<Option<Type>> {
     let mut blocks: Vec<Option<Type>> = vec![None; 1000];
     let block_paths = fs::read_dir("src/assets/blocks/").unwrap();
 
@@ -32,11 +95,12 @@ pub fn init() {
         
         blocks[id as usize] = Some(block);
     }
+    blocks
+}
 
-    match BLOCK_TYPES.set(blocks) {
-        Ok(_) => {}
-        Err(types) => panic!("Failed to initialize block types: {:?}", types)
-    }
+/// Whether there is a block type with this id, for ids that come from outside of the game like saves.
+pub fn exists(id: u32) -> bool {
+    BLOCK_TYPES.get().and_then(|types| types.get(id as usize)).is_some_and(Option::is_some)
 }
 
 pub fn get(id: u32) -> &'static Type {
@@ -1,6 +1,10 @@
-use cgmath::{SquareMatrix, InnerSpace, Vector3, Zero};
+use std::collections::HashSet;
+
+use cgmath::{SquareMatrix, InnerSpace, Vector3};
 use winit::{event::*, dpi::PhysicalSize};
 
+use crate::{input::Action, player::PlayerInput};
+
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
@@ -22,8 +26,13 @@ pub(crate) struct Camera {
 }
 
 impl Camera {
-    fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
-        let view = cgmath::Matrix4::look_at_rh(self.pos, self.pos + Vector3::new(self.yaw.cos(), self.pitch.tan(), self.yaw.sin()), self.up);
+    /// The unit vector the camera is looking along.
+    pub fn get_direction(&self) -> Vector3<f32> {
+        Vector3::new(self.yaw.cos(), self.pitch.tan(), self.yaw.sin()).normalize()
+    }
+
+    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        let view = cgmath::Matrix4::look_at_rh(self.pos, self.pos + self.get_direction(), self.up);
         let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
         proj * view
     }
@@ -33,127 +42,102 @@ impl Camera {
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub(crate) struct CameraUniform {
     view_proj: [[f32; 4]; 4],
+    position: [f32; 4],
 }
 
 impl CameraUniform {
     pub fn new() -> Self {
         Self {
             view_proj: cgmath::Matrix4::identity().into(),
+            position: [0.0; 4],
         }
     }
 
     pub fn update_view_proj(&mut self, camera: &Camera) {
         self.view_proj = (OPENGL_TO_WGPU_MATRIX * camera.build_view_projection_matrix()).into();
+        self.position = camera.pos.to_homogeneous().into();
     }
 }
 
+/// How fast a fully pushed stick turns the camera, in radians per second.
+pub const STICK_LOOK_SPEED: f32 = 3.0;
+/// Radians the camera turns per unit of mouse movement at a sensitivity of 1.
+pub const MOUSE_LOOK_SPEED: f64 = 0.01;
+
 pub(crate) struct CameraController {
-    speed: f32,
-    is_up_pressed: bool,
-    is_down_pressed: bool,
-    is_forward_pressed: bool,
-    is_backward_pressed: bool,
-    is_left_pressed: bool,
-    is_right_pressed: bool,
+    held: HashSet<Action>,
     mouse_delta: (f64, f64),
+    /// Gamepad sticks, right then forward for moving and right then up for looking.
+    analog_movement: (f32, f32),
+    analog_look: (f32, f32),
+    /// Multiplies `MOUSE_LOOK_SPEED`, from the settings.
+    pub sensitivity: f32,
 }
 
 impl CameraController {
-    pub fn new(speed: f32) -> Self {
+    pub fn new() -> Self {
         Self {
-            speed,
-            is_up_pressed: false,
-            is_down_pressed: false,
-            is_forward_pressed: false,
-            is_backward_pressed: false,
-            is_left_pressed: false,
-            is_right_pressed: false,
+            held: HashSet::new(),
             mouse_delta: (0.0, 0.0),
+            analog_movement: (0.0, 0.0),
+            analog_look: (0.0, 0.0),
+            sensitivity: 1.0,
         }
     }
 
-    pub fn process_events(&mut self, device_event: Option<&DeviceEvent>, window_event: Option<&WindowEvent>) -> bool {
-        device_event.and_then(|event| Some({
-            match event {
-                DeviceEvent::Key(KeyboardInput {
-                    virtual_keycode: Some(key),
-                    state,
-                    ..
-                }) => {
-                    let is_pressed = *state == ElementState::Pressed;
-                    match key {
-                        VirtualKeyCode::Space => {
-                            self.is_up_pressed = is_pressed;
-                            true
-                        }
-                        VirtualKeyCode::LShift => {
-                            self.is_down_pressed = is_pressed;
-                            true
-                        }
-                        VirtualKeyCode::W => {
-                            self.is_forward_pressed = is_pressed;
-                            true
-                        }
-                        VirtualKeyCode::A => {
-                            self.is_left_pressed = is_pressed;
-                            true
-                        }
-                        VirtualKeyCode::S => {
-                            self.is_backward_pressed = is_pressed;
-                            true
-                        }
-                        VirtualKeyCode::D => {
-                            self.is_right_pressed = is_pressed;
-                            true
-                        }
-                        _ => false,
-                    }
-                }
-                DeviceEvent::MouseMotion { delta } => {
-                    self.mouse_delta = *delta;
-                    true
-                }
-                _ => false
-            };
-        }));
-        window_event.and_then(|event| Some({ 
-            match event {
-                _ => false
+    pub fn process_events(&mut self, device_event: Option<&DeviceEvent>) -> bool {
+        match device_event {
+            Some(DeviceEvent::MouseMotion { delta }) => {
+                // Several motion events can arrive between frames
+                self.mouse_delta.0 += delta.0;
+                self.mouse_delta.1 += delta.1;
+                true
             }
-        }));
-        false
+            _ => false,
+        }
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera) {
-        let forward = Vector3::new(camera.yaw.cos(), camera.pitch, camera.yaw.sin());
-        let mut movement = Vector3::zero();
-
-        // Forward backward movement
-        if self.is_forward_pressed { movement += forward; }
-        if self.is_backward_pressed { movement -= forward; }
+    /// Records an action's key being pressed or released. Returns true only when it was just
+    /// pressed, so held keys repeating don't trigger the action again.
+    pub fn process_action(&mut self, action: Action, pressed: bool) -> bool {
+        if pressed {
+            self.held.insert(action)
+        } else {
+            self.held.remove(&action);
+            false
+        }
+    }
 
-        // Left right movement
-        let right = forward.cross(camera.up);
-        if self.is_left_pressed { movement -= right; }
-        if self.is_right_pressed { movement += right; }
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
 
-        // Up down movement
-        if self.is_up_pressed { movement.y += 1.0; }
-        if self.is_down_pressed { movement.y -= 1.0; }
+    pub fn set_analog(&mut self, movement: (f32, f32), look: (f32, f32)) {
+        self.analog_movement = movement;
+        self.analog_look = look;
+    }
 
-        // Normalize is NAN if zero
-        if movement.x + movement.y + movement.z != 0.0 {
-            camera.pos += movement.normalize() * self.speed;
-        } else {
-            camera.pos += movement * self.speed;
+    /// The movement actions currently held, the player moves itself with them.
+    pub fn get_input(&self) -> PlayerInput {
+        PlayerInput {
+            forward: self.is_held(Action::MoveForward),
+            backward: self.is_held(Action::MoveBackward),
+            left: self.is_held(Action::MoveLeft),
+            right: self.is_held(Action::MoveRight),
+            up: self.is_held(Action::Jump),
+            down: self.is_held(Action::Sneak),
+            analog: self.analog_movement,
         }
+    }
 
-        // Mouse movement
-        camera.yaw += (self.mouse_delta.0 / 100.0) as f32;
-        let pitch_delta = (self.mouse_delta.1 / 100.0) as f32;
-        if (pitch_delta < 0.0 && camera.pitch < 1.5) || (pitch_delta > 0.0 && camera.pitch > -1.5) {
-            camera.pitch -= pitch_delta;
-        }
+    /// Turns the camera by the mouse movement since the last frame and by the look stick for as long
+    /// as the frame took. Looking around happens every frame rather than every tick, so it stays
+    /// responsive at any frame rate.
+    pub fn update_camera(&mut self, camera: &mut Camera, frame_time: f32) {
+        let mouse_speed = MOUSE_LOOK_SPEED * self.sensitivity as f64;
+        camera.yaw += (self.mouse_delta.0 * mouse_speed) as f32 + self.analog_look.0 * STICK_LOOK_SPEED * frame_time;
+        let pitch_delta = -(self.mouse_delta.1 * mouse_speed) as f32 + self.analog_look.1 * STICK_LOOK_SPEED * frame_time;
+        camera.pitch = (camera.pitch + pitch_delta).clamp(-1.5, 1.5);
         self.mouse_delta = (0.0, 0.0)
     }
-}
\ No newline at end of file
+}
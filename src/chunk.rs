@@ -1,89 +1,98 @@
 use cgmath::{Point3};
 use noise::NoiseFn;
 
-use crate::PERLIN;
+use crate::{light::LightMap, palette::PalettedBlocks, PERLIN};
 
 pub const SIZE: u8 = 16;
+const VOLUME: usize = SIZE as usize * SIZE as usize * SIZE as usize;
 
 #[derive(PartialEq)]
 pub struct Chunk {
     pub position: Point3<i32>,
-    pub blocks: [[[u32; SIZE as usize]; SIZE as usize]; SIZE as usize],
+    /// Blocks in x, y, z order, see `get_index`.
+    pub blocks: PalettedBlocks,
+    pub light: LightMap,
+    /// Whether the chunk changed since it was last meshed. New chunks start out dirty, since they
+    /// have never been meshed.
+    dirty: bool,
 }
 
 impl Chunk {
     pub fn new_empty(pos: Point3<i32>) -> Chunk {
-        Chunk {
-            position: pos * SIZE as i32,
-            blocks: [[[0; SIZE as usize]; SIZE as usize]; SIZE as usize],
-        }
+        Chunk::new_filled(pos, 0)
     }
     pub fn new_filled(pos: Point3<i32>, block: u32) -> Chunk {
         Chunk {
             position: pos * SIZE as i32,
-            blocks: [[[block; SIZE as usize]; SIZE as usize]; SIZE as usize],
+            blocks: PalettedBlocks::new(VOLUME, block),
+            light: LightMap::new(),
+            dirty: true,
         }
     }
     pub fn new_layered(pos: Point3<i32>, surface: u32, shallow: u32, deep: u32) -> Chunk {
-        let mut blocks: [[[u32; SIZE as usize]; SIZE as usize]; SIZE as usize] =
-            [[[0; SIZE as usize]; SIZE as usize]; SIZE as usize];
-        for x in 0..blocks.len() {
-            for y in 0..blocks[x].len() {
-                for z in 0..blocks[x][y].len() {
-                    if y as u8 >= SIZE - 1 {
-                        blocks[x][y][z] = surface;
-                    } else if (y as u8) < SIZE - 1 && (y as f32) >= SIZE as f32 / 1.5 {
-                        blocks[x][y][z] = shallow;
-                    } else {
-                        blocks[x][y][z] = deep;
+        let mut chunk = Chunk::new_filled(pos, deep);
+        for x in 0..SIZE {
+            for y in 0..SIZE {
+                for z in 0..SIZE {
+                    if y >= SIZE - 1 {
+                        chunk.set_block(Point3::new(x, y, z), surface);
+                    } else if (y as f32) >= SIZE as f32 / 1.5 {
+                        chunk.set_block(Point3::new(x, y, z), shallow);
                     }
                 }
             }
         }
-        Chunk {
-            position: pos * SIZE as i32,
-            blocks,
-        }
+        chunk
     }
     pub fn new_perlin(pos: Point3<i32>, block: u32) -> Chunk {
-        let mut blocks: [[[u32; SIZE as usize]; SIZE as usize]; SIZE as usize] =
-            [[[0; SIZE as usize]; SIZE as usize]; SIZE as usize];
-        for x in 0..blocks.len() {
-            for y in 0..blocks[x].len() {
-                for z in 0..blocks[x][y].len() {
-                    blocks[x][y][z] = if PERLIN.get().unwrap().get([
+        let mut chunk = Chunk::new_empty(pos);
+        for x in 0..SIZE {
+            for y in 0..SIZE {
+                for z in 0..SIZE {
+                    if PERLIN.get().unwrap().get([
                         ((x as f64 + 0.4) + (pos.x * SIZE as i32) as f64) / 10.0,
                         ((y as f64 + 0.7) + (pos.y * SIZE as i32) as f64) / 10.0,
                         ((z as f64 + 0.8) + (pos.z * SIZE as i32) as f64) / 10.0]
                     ) > 0.15 {
-                        block
-                    } else {
-                        0
-                    };
+                        chunk.set_block(Point3::new(x, y, z), block);
+                    }
                 }
             }
         }
-        Chunk {
-            position: pos * SIZE as i32,
-            blocks,
-        }
+        chunk
+    }
+
+    /// Position of the chunk in chunk coordinates, rather than block coordinates like `position`.
+    pub fn get_chunk_position(&self) -> Point3<i32> {
+        self.position / SIZE as i32
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+    /// Marks the chunk for remeshing, for changes outside of its blocks like its light or a neighbour's border.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+    pub fn clear_dirty(&mut self) {
+        self.dirty = false;
     }
 
     pub fn get_block(&self, pos: Point3<u8>) -> u32 {
         if pos.x >= SIZE || pos.y >= SIZE || pos.z >= SIZE {
             return 0;
         }
-        self.blocks[pos.x as usize][pos.y as usize][pos.z as usize]
+        self.blocks.get(get_index(pos.x as usize, pos.y as usize, pos.z as usize))
     }
     pub fn get_block_s(&self, pos: Point3<i16>) -> u32 {
         if pos.x >= SIZE as i16 || pos.y >= SIZE as i16 || pos.z >= SIZE as i16 || pos.x < 0 || pos.y < 0 || pos.z < 0 {
             return 0;
         }
-        self.blocks[pos.x as usize][pos.y as usize][pos.z as usize]
+        self.blocks.get(get_index(pos.x as usize, pos.y as usize, pos.z as usize))
     }
 
     pub fn set_block(&mut self, pos: Point3<u8>, block: u32) {
-        self.blocks[pos.x as usize][pos.y as usize][pos.z as usize] = block;
+        self.set_index(get_index(pos.x as usize, pos.y as usize, pos.z as usize), block);
     }
     pub fn set_block_global(&mut self, pos: Point3<i32>, block: u32) {
         let x = (pos.x - self.position.x) + SIZE as i32 / 2;
@@ -92,7 +101,7 @@ impl Chunk {
         if x >= SIZE as i32 || y >= SIZE as i32 || z >= SIZE as i32 || x < 0 || y < 0 || z < 0 {
             return;
         };
-        self.blocks[(x as i32) as usize][(y as i32) as usize][(z as i32) as usize] = block;
+        self.set_index(get_index(x as usize, y as usize, z as usize), block);
     }
     pub fn get_block_global(&self, pos: Point3<i32>) -> u32 {
         let x = (pos.x - self.position.x) + SIZE as i32 / 2;
@@ -101,6 +110,19 @@ impl Chunk {
         if x >= SIZE as i32 || y >= SIZE as i32 || z >= SIZE as i32 || x < 0 || y < 0 || z < 0 {
             return 0;
         };
-        self.blocks[(x as i32) as usize][(y as i32) as usize][(z as i32) as usize]
+        self.blocks.get(get_index(x as usize, y as usize, z as usize))
     }
-}
\ No newline at end of file
+
+    /// Sets a block, leaving the chunk clean if it was already that block.
+    fn set_index(&mut self, index: usize, block: u32) {
+        if self.blocks.get(index) != block {
+            self.blocks.set(index, block);
+            self.dirty = true;
+        }
+    }
+}
+
+/// Index of a block in `Chunk::blocks` from its position in the chunk.
+pub fn get_index(x: usize, y: usize, z: usize) -> usize {
+    (x * SIZE as usize + y) * SIZE as usize + z
+}
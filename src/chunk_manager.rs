@@ -1,44 +1,262 @@
-use cgmath::{Vector3, Point3};
-use rand::{Rng, prelude::ThreadRng};
+use std::collections::{HashMap, HashSet};
 
-use crate::{chunk::{Chunk, self}, math, render::chunk_buffers::ChunkBuffers, RENDER_DISTANCE};
+use cgmath::{EuclideanSpace, Vector3, Point3};
+
+use log::warn;
+
+use crate::{block_types, chunk::{self, Chunk}, light::{self, Light, LightMap}, math, region::WorldStorage, settings, render::{chunk_buffers::ChunkBuffers, lod}};
+
+/// Generating a chunk relights and possibly remeshes its neighbours, so only a few are loaded per update.
+const CHUNKS_PER_UPDATE: usize = 8;
+/// Chunks are unloaded one chunk further out than they are loaded, so walking back and forth over
+/// a chunk border doesn't keep unloading and reloading the same chunks.
+const UNLOAD_MARGIN: i32 = 1;
 
 pub struct ChunkManager {
     pub chunks: Vec<Chunk>,
-    random: ThreadRng
+    /// Index into `chunks` of every loaded chunk by its chunk position.
+    indices: HashMap<Point3<i32>, usize>,
+    /// Chunk positions of chunks that contain a light emitting block, so chunks with none nearby can skip the light flood.
+    emitters: HashSet<Point3<i32>>,
+    /// The chunk the player was in at the last update, which levels of detail are measured from.
+    center: Point3<i32>,
+    /// The level's seed, which decides what never saved chunks are generated out of.
+    seed: u32,
+    /// Where chunks are saved to and loaded from, chunks are only kept in memory without one.
+    storage: Option<WorldStorage>,
+    /// Chunk positions of dirty chunks waiting to be remeshed, each at most once. Positions rather
+    /// than indices, since unloading a chunk moves the ones after it.
+    remesh_queue: Vec<Point3<i32>>,
+    /// Chunks within the view distance that were still missing after the last update.
+    pending_loads: usize,
+    /// Chunks up to this many chunks away are drawn at full detail, see `lod`.
+    render_distance: i32,
+    /// Rings past the render distance drawn at lower levels of detail, see `lod::get_level`.
+    lod_distances: Vec<i32>,
+    /// Set when the render distance or rings change, so the next update unloads and remeshes chunks like
+    /// the player had moved.
+    distance_changed: bool,
 }
 
 impl ChunkManager {
     pub fn new() -> ChunkManager {
         ChunkManager {
             chunks: Vec::new(),
-            random: rand::thread_rng()
+            indices: HashMap::new(),
+            emitters: HashSet::new(),
+            center: Point3::new(0, 0, 0),
+            seed: 0,
+            storage: None,
+            remesh_queue: Vec::new(),
+            pending_loads: 0,
+            render_distance: settings::DEFAULT_RENDER_DISTANCE,
+            lod_distances: lod::DEFAULT_DISTANCES.to_vec(),
+            distance_changed: false,
         }
     }
 
-    pub fn update(&mut self, player_pos: &Point3<f32>, chunk_buffers: &mut ChunkBuffers, device: &wgpu::Device) {
+    pub fn with_storage(storage: WorldStorage, seed: u32) -> ChunkManager {
+        ChunkManager {
+            storage: Some(storage),
+            seed,
+            ..ChunkManager::new()
+        }
+    }
+
+    /// Loads the closest missing chunks within the view distance, saves and unloads chunks that are
+    /// too far away, and marks chunks whose level of detail changed since the player moved into
+    /// another chunk dirty. Nothing is meshed until `remesh`.
+    ///
+    /// `chunk_buffers` must hold the mesh of every loaded chunk at the chunk's index, which `remesh`
+    /// leaves it holding. Unloaded chunks' meshes are removed here to keep the two lined up, and the
+    /// level of detail of each chunk is read back from its mesh.
+    pub fn update(&mut self, player_pos: &Point3<f32>, chunk_buffers: &mut ChunkBuffers) {
+        debug_assert_eq!(self.chunks.len(), chunk_buffers.get_buffer_count(), "update without a remesh since the last one");
         let player_pos = Point3::new(player_pos.x.round() as i32, player_pos.y.round() as i32, player_pos.z.round() as i32);
-        for posx in (-RENDER_DISTANCE + math::get_chunk_position(player_pos).x)..(RENDER_DISTANCE + math::get_chunk_position(player_pos).x) {
-            for posy in (-RENDER_DISTANCE + math::get_chunk_position(player_pos).y)..(RENDER_DISTANCE + math::get_chunk_position(player_pos).y) {
-                for posz in (-RENDER_DISTANCE + math::get_chunk_position(player_pos).z)..(RENDER_DISTANCE + math::get_chunk_position(player_pos).z) {
-                    if self.get_pos_chunk(player_pos).is_none() {
-                        let chunk = Chunk::new_perlin(Point3::new(posx, posy, posz), self.random.gen_range(1..4));
-                        let index = self.add_chunk(chunk);
-                        chunk_buffers.update_chunk(device, index, self.chunks.get(index).unwrap());
+        let center = math::get_chunk_position(player_pos);
+        if center != self.center || self.distance_changed {
+            self.center = center;
+            self.distance_changed = false;
+            // Backwards, so removing a chunk doesn't move the ones still to be checked
+            for index in (0..self.chunks.len()).rev() {
+                if lod::get_distance(center, self.chunks[index].get_chunk_position()) > lod::get_view_distance(self.render_distance, &self.lod_distances) + UNLOAD_MARGIN {
+                    self.save_chunk(index);
+                    self.remove_chunk(index);
+                    chunk_buffers.remove_chunk(index);
+                }
+            }
+            for index in 0..self.chunks.len() {
+                let lod = self.get_lod(index);
+                if chunk_buffers.get_buffer(index).lod != lod {
+                    self.mark_dirty(index);
+                }
+            }
+        }
+
+        let view_distance = lod::get_view_distance(self.render_distance, &self.lod_distances);
+        let mut missing = Vec::new();
+        for x in -view_distance..=view_distance {
+            for y in -view_distance..=view_distance {
+                for z in -view_distance..=view_distance {
+                    let position = center + Vector3::new(x, y, z);
+                    if !self.indices.contains_key(&position) {
+                        missing.push(position);
+                    }
+                }
+            }
+        }
+        missing.sort_by_key(|position| {
+            let offset = position - center;
+            (lod::get_distance(center, *position), offset.x * offset.x + offset.y * offset.y + offset.z * offset.z)
+        });
+
+        self.pending_loads = missing.len().saturating_sub(CHUNKS_PER_UPDATE);
+        for position in missing.into_iter().take(CHUNKS_PER_UPDATE) {
+            let index = self.load_chunk(position, get_fill_block(self.seed, position));
+            self.update_light(index);
+
+            // Light from the new chunk can spill into the chunks around it
+            for neighbour in self.get_neighbour_indices(index) {
+                self.update_light(neighbour);
+            }
+        }
+    }
+
+    /// Sets a block and relights the chunks around it, leaving every chunk that needs remeshing
+    /// because of it dirty. Returns `false` when the chunk isn't loaded.
+    pub fn edit_block(&mut self, pos: Point3<i32>, block: u32) -> bool {
+        let index = match self.get_pos_index(pos.to_vec()) {
+            Some(index) => index,
+            None => return false,
+        };
+        self.set_block(pos, block);
+        self.update_light(index);
+        for neighbour in self.get_neighbour_indices(index) {
+            self.update_light(neighbour);
+        }
+        true
+    }
+
+    /// Takes effect at the next update, which loads or unloads chunks for the new distances.
+    pub fn set_render_distance(&mut self, render_distance: i32, lod_distances: &[i32]) {
+        if render_distance != self.render_distance || lod_distances != self.lod_distances {
+            self.render_distance = render_distance;
+            self.lod_distances = lod_distances.to_vec();
+            self.distance_changed = true;
+        }
+    }
+
+    /// Marks the chunk at `index` dirty and queues it for remeshing.
+    pub fn mark_dirty(&mut self, index: usize) {
+        let chunk = &mut self.chunks[index];
+        chunk.mark_dirty();
+        if !self.remesh_queue.contains(&chunk.get_chunk_position()) {
+            self.remesh_queue.push(chunk.get_chunk_position());
+        }
+    }
+
+    /// Empties the remesh queue, returning the indices of the queued chunks that are still loaded
+    /// and dirty in ascending order, and marking them clean.
+    fn take_dirty(&mut self) -> Vec<usize> {
+        let mut dirty: Vec<usize> = std::mem::take(&mut self.remesh_queue).into_iter()
+            .filter_map(|position| self.indices.get(&position).copied())
+            .filter(|index| self.chunks[*index].is_dirty())
+            .collect();
+        // New chunks get their meshes appended, so they have to be built in order
+        dirty.sort_unstable();
+        for index in &dirty {
+            self.chunks[*index].clear_dirty();
+        }
+        dirty
+    }
+
+    /// Rebuilds the mesh of every dirty chunk once, however many times it changed since the last
+    /// remesh. Returns how many chunks were remeshed.
+    pub fn remesh(&mut self, chunk_buffers: &mut ChunkBuffers, device: &wgpu::Device, queue: &wgpu::Queue) -> usize {
+        let dirty = self.take_dirty();
+        for &index in &dirty {
+            chunk_buffers.update_chunk(device, queue, index, &self.chunks[index], self.get_lod(index));
+        }
+        dirty.len()
+    }
+
+    /// Indices of the loaded chunk containing the block, and of every chunk sharing a face, edge or
+    /// corner with the block when it is on the chunk's border.
+    pub fn get_touching_indices(&self, pos: Point3<i32>) -> Vec<usize> {
+        let mut indices = Vec::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let index = self.indices.get(&math::get_chunk_position(pos + Vector3::new(x, y, z)));
+                    if let Some(index) = index {
+                        if !indices.contains(index) {
+                            indices.push(*index);
+                        }
                     }
                 }
             }
         }
+        indices
+    }
+
+    /// Level of detail the chunk at `index` should be meshed at, from its distance to the player's chunk.
+    pub fn get_lod(&self, index: usize) -> u8 {
+        lod::get_level(lod::get_distance(self.center, self.chunks[index].get_chunk_position()), self.render_distance, &self.lod_distances)
+    }
+
+    /// Loads the chunk at a chunk position from the world's save, or generates it out of `block` if
+    /// it was never saved. Returns the index it was added at.
+    pub fn load_chunk(&mut self, position: Point3<i32>, block: u32) -> usize {
+        let saved = match &mut self.storage {
+            Some(storage) => storage.load_chunk(position).unwrap_or_else(|error| {
+                warn!("Failed to load chunk {:?}, generating it again: {}", position, error);
+                None
+            }),
+            None => None,
+        };
+        self.add_chunk(saved.unwrap_or_else(|| Chunk::new_perlin(position, block)))
+    }
+
+    fn save_chunk(&mut self, index: usize) {
+        if let Some(storage) = &mut self.storage {
+            let chunk = &self.chunks[index];
+            if let Err(error) = storage.save_chunk(chunk) {
+                warn!("Failed to save chunk {:?}: {}", chunk.get_chunk_position(), error);
+            }
+        }
     }
-    
+
+    /// Saves every loaded chunk, for when the game closes.
+    pub fn save_all(&mut self) {
+        for index in 0..self.chunks.len() {
+            self.save_chunk(index);
+        }
+        if let Some(storage) = &mut self.storage {
+            if let Err(error) = storage.flush() {
+                warn!("Failed to save the world: {}", error);
+            }
+        }
+    }
+
     pub fn add_chunk(&mut self, chunk: Chunk) -> usize {
         let index = self.chunks.len();
+        // The palette can hold blocks that were replaced since, at worst that costs a light flood
+        if chunk.blocks.get_palette().iter().any(|block| *block != 0 && block_types::get(*block).is_emissive()) {
+            self.emitters.insert(chunk.get_chunk_position());
+        }
+        self.indices.insert(chunk.get_chunk_position(), index);
         self.chunks.push(chunk);
+        if self.chunks[index].is_dirty() {
+            self.mark_dirty(index);
+        }
         index
     }
 
     pub fn remove_chunk(&mut self, index: usize) {
-        self.chunks.remove(index);
+        let chunk = self.chunks.remove(index);
+        self.emitters.remove(&chunk.get_chunk_position());
+        // Every chunk after the removed one moved down by one
+        self.indices = self.chunks.iter().enumerate().map(|(index, chunk)| (chunk.get_chunk_position(), index)).collect();
     }
 
     pub fn get_chunk(&self, index: usize) -> &Chunk {
@@ -49,37 +267,17 @@ impl ChunkManager {
     }
 
     pub fn get_pos_chunk(&self, pos: Point3<i32>) -> Option<&Chunk> {
-        for chunk in &self.chunks {
-            if pos.x < chunk.position.x + (chunk::SIZE / 2) as i32 && pos.x >= chunk.position.x - (chunk::SIZE / 2) as i32 &&
-               pos.y < chunk.position.y + (chunk::SIZE / 2) as i32 && pos.y >= chunk.position.y - (chunk::SIZE / 2) as i32 &&
-               pos.z < chunk.position.z + (chunk::SIZE / 2) as i32 && pos.z >= chunk.position.z - (chunk::SIZE / 2) as i32 {
-                return Some(&chunk);
-            }
-        }
-        None
+        let index = *self.indices.get(&math::get_chunk_position(pos))?;
+        Some(&self.chunks[index])
     }
 
     pub fn get_pos_chunk_mut(&mut self, pos: Point3<i32>) -> Option<&mut Chunk> {
-        for chunk in &mut self.chunks {
-            if pos.x < chunk.position.x + (chunk::SIZE / 2) as i32 && pos.x >= chunk.position.x - (chunk::SIZE / 2) as i32 &&
-               pos.y < chunk.position.y + (chunk::SIZE / 2) as i32 && pos.y >= chunk.position.y - (chunk::SIZE / 2) as i32 &&
-               pos.z < chunk.position.z + (chunk::SIZE / 2) as i32 && pos.z >= chunk.position.z - (chunk::SIZE / 2) as i32 {
-                return Some(chunk);
-            }
-        }
-        None
+        let index = *self.indices.get(&math::get_chunk_position(pos))?;
+        Some(&mut self.chunks[index])
     }
 
-    pub fn get_pos_index(&mut self, pos: Vector3<i32>) -> Option<usize> {
-        for index in 0..self.chunks.len() {
-            let chunk = &self.chunks[index];
-            if pos.x < chunk.position.x + (chunk::SIZE / 2) as i32 && pos.x >= chunk.position.x - (chunk::SIZE / 2) as i32 &&
-               pos.y < chunk.position.y + (chunk::SIZE / 2) as i32 && pos.y >= chunk.position.y - (chunk::SIZE / 2) as i32 &&
-               pos.z < chunk.position.z + (chunk::SIZE / 2) as i32 && pos.z >= chunk.position.z - (chunk::SIZE / 2) as i32 {
-                return Some(index);
-            }
-        }
-        None
+    pub fn get_pos_index(&self, pos: Vector3<i32>) -> Option<usize> {
+        self.indices.get(&math::get_chunk_position(Point3::from_vec(pos))).copied()
     }
 
     /*pub fn get_index_pos(&mut self, index: usize) -> Option<Vector3<i32>> {
@@ -94,17 +292,75 @@ impl ChunkManager {
         None
     }*/
 
+    /// Returns the indices of every loaded chunk touching the chunk at `index`, including diagonals.
+    pub fn get_neighbour_indices(&self, index: usize) -> Vec<usize> {
+        let position = self.chunks[index].position;
+        let size = chunk::SIZE as i32;
+        let mut neighbours = Vec::new();
+        for (other_index, other) in self.chunks.iter().enumerate() {
+            let offset = other.position - position;
+            if other_index != index && offset.x.abs() <= size && offset.y.abs() <= size && offset.z.abs() <= size {
+                neighbours.push(other_index);
+            }
+        }
+        neighbours
+    }
+
+    /// Recalculates the block light of the chunk at `index` from the emitters around it, marking it
+    /// dirty and returning `true` when it changed.
+    pub fn update_light(&mut self, index: usize) -> bool {
+        // Light travels less than a chunk, so only emitters in this chunk or the ones touching it matter
+        let position = self.chunks[index].get_chunk_position();
+        let lit = self.emitters.iter().any(|emitter| lod::get_distance(*emitter, position) <= 1);
+        let light = if lit { light::flood(self, &self.chunks[index]) } else { LightMap::new() };
+        let changed = self.chunks[index].light != light;
+        self.chunks[index].light = light;
+        if changed {
+            self.mark_dirty(index);
+        }
+        changed
+    }
+
     pub fn get_chunk_count(&self) -> usize {
         self.chunks.len()
     }
 
-    pub fn set_block(&mut self, pos: Point3<i32>, block: u32) {
-        let chunk = self.get_pos_chunk_mut(pos);
-        match chunk {
-            Some(_) => {
-                chunk.unwrap().set_block_global(pos, block);
+    /// Chunks waiting to be loaded or generated by a later update.
+    pub fn get_pending_load_count(&self) -> usize {
+        self.pending_loads
+    }
+
+    /// Chunks waiting to be remeshed.
+    pub fn get_pending_remesh_count(&self) -> usize {
+        self.remesh_queue.len()
+    }
+
+    /// Block light at a block, which is dark outside of the loaded chunks.
+    pub fn get_light(&self, pos: Point3<i32>) -> Light {
+        match self.get_pos_chunk(pos) {
+            Some(chunk) => {
+                let local = (pos - chunk.position).map(|value| (value + chunk::SIZE as i32 / 2) as i16);
+                chunk.light.get(Point3::from_vec(local))
             }
-            None => {}
+            None => [0; 3],
+        }
+    }
+
+    /// Sets a block, marking its chunk dirty along with any chunk whose mesh the block borders.
+    pub fn set_block(&mut self, pos: Point3<i32>, block: u32) {
+        if block != 0 && block_types::get(block).is_emissive() {
+            self.emitters.insert(math::get_chunk_position(pos));
+        }
+        let chunk = match self.get_pos_chunk_mut(pos) {
+            Some(chunk) => chunk,
+            None => return,
+        };
+        if chunk.get_block_global(pos) == block {
+            return;
+        }
+        chunk.set_block_global(pos, block);
+        for index in self.get_touching_indices(pos) {
+            self.mark_dirty(index);
         }
     }
 
@@ -117,4 +373,174 @@ impl ChunkManager {
             None => 0
         }
     }
-}
\ No newline at end of file
+}
+
+/// The block a never saved chunk is generated out of, the same every time for the same seed and
+/// chunk position.
+fn get_fill_block(seed: u32, position: Point3<i32>) -> u32 {
+    let mut hash = seed;
+    for coordinate in [position.x, position.y, position.z] {
+        hash = (hash ^ coordinate as u32).wrapping_mul(0x9e37_79b1).rotate_left(15);
+    }
+    hash ^= hash >> 16;
+    1 + hash % 3
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TestDirectory;
+
+    use super::*;
+
+    fn manager() -> ChunkManager {
+        block_types::init_for_tests();
+        let mut manager = ChunkManager::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    manager.add_chunk(Chunk::new_empty(Point3::new(x, y, z)));
+                }
+            }
+        }
+        manager
+    }
+
+    fn touching_positions(manager: &ChunkManager, pos: Point3<i32>) -> Vec<Point3<i32>> {
+        let mut positions: Vec<Point3<i32>> = manager.get_touching_indices(pos).iter()
+            .map(|index| manager.get_chunk(*index).get_chunk_position())
+            .collect();
+        positions.sort_by_key(|position| (position.x, position.y, position.z));
+        positions
+    }
+
+    #[test]
+    fn inner_block_only_touches_its_chunk() {
+        let manager = manager();
+        assert_eq!(touching_positions(&manager, Point3::new(0, 0, 0)), vec![Point3::new(0, 0, 0)]);
+        assert_eq!(touching_positions(&manager, Point3::new(-7, 6, 3)), vec![Point3::new(0, 0, 0)]);
+    }
+
+    #[test]
+    fn border_block_touches_the_chunk_across_the_face() {
+        let manager = manager();
+        assert_eq!(touching_positions(&manager, Point3::new(7, 0, 0)), vec![Point3::new(0, 0, 0), Point3::new(1, 0, 0)]);
+        assert_eq!(touching_positions(&manager, Point3::new(0, -8, 0)), vec![Point3::new(0, -1, 0), Point3::new(0, 0, 0)]);
+    }
+
+    #[test]
+    fn corner_block_touches_every_chunk_around_the_corner() {
+        let manager = manager();
+        assert_eq!(touching_positions(&manager, Point3::new(7, 7, 7)).len(), 8);
+        // Edges touch four
+        assert_eq!(touching_positions(&manager, Point3::new(-8, 0, 7)).len(), 4);
+    }
+
+    #[test]
+    fn saved_chunks_are_loaded_instead_of_generated() {
+        block_types::init_for_tests();
+        let directory = TestDirectory::new("manager_saved");
+
+        let mut manager = ChunkManager::with_storage(WorldStorage::new(directory.path()), 0);
+        let mut chunk = Chunk::new_filled(Point3::new(4, -2, 0), 2);
+        chunk.set_block(Point3::new(1, 2, 3), 0);
+        manager.add_chunk(chunk);
+        manager.save_all();
+
+        let mut manager = ChunkManager::with_storage(WorldStorage::new(directory.path()), 0);
+        let index = manager.load_chunk(Point3::new(4, -2, 0), 1);
+        let loaded = manager.get_chunk(index);
+        assert_eq!(loaded.get_block(Point3::new(0, 0, 0)), 2);
+        assert_eq!(loaded.get_block(Point3::new(1, 2, 3)), 0);
+        assert_eq!(manager.get_pos_index(Vector3::new(64, -32, 0)), Some(index));
+    }
+
+    #[test]
+    fn light_is_read_from_the_chunk_it_is_in() {
+        let mut manager = manager();
+        // A lamp
+        manager.edit_block(Point3::new(7, 0, 0), 5);
+        assert_eq!(manager.get_light(Point3::new(7, 0, 0)), [15, 13, 9]);
+        // Across the border, in the next chunk
+        assert_eq!(manager.get_light(Point3::new(9, 0, 0)), [13, 11, 7]);
+        assert_eq!(manager.get_light(Point3::new(100, 0, 0)), [0; 3]);
+    }
+
+    fn dirty_positions(manager: &mut ChunkManager) -> Vec<Point3<i32>> {
+        manager.take_dirty().iter().map(|index| manager.get_chunk(*index).get_chunk_position()).collect()
+    }
+
+    #[test]
+    fn new_chunks_are_dirty_until_taken() {
+        let mut manager = manager();
+        assert_eq!(manager.take_dirty(), (0..27).collect::<Vec<usize>>());
+        assert!(manager.chunks.iter().all(|chunk| !chunk.is_dirty()));
+        assert!(manager.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn inner_edits_only_dirty_their_chunk() {
+        let mut manager = manager();
+        manager.take_dirty();
+        manager.set_block(Point3::new(0, 0, 0), 1);
+        assert_eq!(dirty_positions(&mut manager), vec![Point3::new(0, 0, 0)]);
+
+        // Setting a block to what it already is changes nothing
+        manager.set_block(Point3::new(0, 0, 0), 1);
+        assert!(manager.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn border_edits_dirty_the_neighbours() {
+        let mut manager = manager();
+        manager.take_dirty();
+        manager.set_block(Point3::new(7, 0, 0), 1);
+        let mut dirty = dirty_positions(&mut manager);
+        dirty.sort_by_key(|position| position.x);
+        assert_eq!(dirty, vec![Point3::new(0, 0, 0), Point3::new(1, 0, 0)]);
+    }
+
+    #[test]
+    fn chunks_are_queued_once_however_often_they_change() {
+        let mut manager = manager();
+        manager.take_dirty();
+        for x in -8..8 {
+            manager.set_block(Point3::new(x, 0, 0), 1);
+            manager.set_block(Point3::new(x, 0, 0), 0);
+        }
+        assert_eq!(manager.remesh_queue.len(), 3);
+        assert_eq!(manager.take_dirty().len(), 3);
+    }
+
+    #[test]
+    fn unloaded_chunks_leave_the_queue() {
+        let mut manager = manager();
+        manager.take_dirty();
+        manager.set_block(Point3::new(0, 0, 0), 1);
+        let index = manager.get_pos_index(Vector3::new(0, 0, 0)).unwrap();
+        manager.remove_chunk(index);
+        assert!(manager.take_dirty().is_empty());
+    }
+
+    #[test]
+    fn unloaded_chunks_are_left_out() {
+        let manager = manager();
+        // On the outer border of the loaded chunks
+        assert_eq!(touching_positions(&manager, Point3::new(23, 0, 0)), vec![Point3::new(1, 0, 0)]);
+    }
+
+    #[test]
+    fn generated_blocks_follow_the_seed() {
+        let position = Point3::new(3, -1, 7);
+        assert_eq!(get_fill_block(42, position), get_fill_block(42, position));
+        let mut blocks = HashSet::new();
+        for x in -4..4 {
+            for seed in [1, 42] {
+                let block = get_fill_block(seed, Point3::new(x, 0, 0));
+                assert!((1..4).contains(&block));
+                blocks.insert(block);
+            }
+        }
+        assert_eq!(blocks.len(), 3);
+        assert!((0..16).any(|x| get_fill_block(1, Point3::new(x, 0, 0)) != get_fill_block(2, Point3::new(x, 0, 0))));
+    }
+}
@@ -0,0 +1,127 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use log::LevelFilter;
+
+use crate::settings::{Settings, SETTINGS_PATH};
+
+/// Options from the command line. Settings given here override the ones from the settings file for
+/// this run only, see `SettingsFile`.
+#[derive(Parser, Debug, PartialEq)]
+#[command(version, about = "A block building game")]
+pub struct Arguments {
+    /// Play this world without the start menu, creating it if there is none by that name
+    #[arg(long)]
+    pub world: Option<String>,
+    /// Seed of the world created by --world. An existing world keeps its own seed, and giving a
+    /// different one for it is an error
+    #[arg(long, requires = "world")]
+    pub seed: Option<u32>,
+    /// Settings file to read, and to save changes from the settings screen to
+    #[arg(long, default_value = SETTINGS_PATH)]
+    pub settings: PathBuf,
+    /// Chunks drawn at full detail around the camera
+    #[arg(long)]
+    pub render_distance: Option<i32>,
+    /// Vertical field of view in degrees
+    #[arg(long)]
+    pub fov: Option<f32>,
+    /// Multiplies how far the camera turns for the same mouse movement
+    #[arg(long)]
+    pub mouse_sensitivity: Option<f32>,
+    /// Waits for the display between frames
+    #[arg(long)]
+    pub vsync: Option<bool>,
+    /// Width of the window in pixels
+    #[arg(long)]
+    pub width: Option<u32>,
+    /// Height of the window in pixels
+    #[arg(long)]
+    pub height: Option<u32>,
+    /// Borderless fullscreen on the current monitor
+    #[arg(long)]
+    pub fullscreen: Option<bool>,
+    /// Screen pixels per UI pixel
+    #[arg(long)]
+    pub ui_scale: Option<f32>,
+    /// One of off, error, warn, info, debug or trace
+    #[arg(long)]
+    pub log_level: Option<LevelFilter>,
+}
+
+impl Arguments {
+    /// Replaces the settings that were given on the command line. They still have to be validated.
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(render_distance) = self.render_distance {
+            settings.render_distance = render_distance;
+        }
+        if let Some(fov) = self.fov {
+            settings.fov = fov;
+        }
+        if let Some(mouse_sensitivity) = self.mouse_sensitivity {
+            settings.mouse_sensitivity = mouse_sensitivity;
+        }
+        if let Some(vsync) = self.vsync {
+            settings.vsync = vsync;
+        }
+        if let Some(width) = self.width {
+            settings.window_width = width;
+        }
+        if let Some(height) = self.height {
+            settings.window_height = height;
+        }
+        if let Some(fullscreen) = self.fullscreen {
+            settings.fullscreen = fullscreen;
+        }
+        if let Some(ui_scale) = self.ui_scale {
+            settings.ui_scale = ui_scale;
+        }
+        if let Some(log_level) = self.log_level {
+            settings.log_level = log_level;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Arguments, clap::Error> {
+        Arguments::try_parse_from(["minecraft_clone"].iter().chain(args))
+    }
+
+    #[test]
+    fn worlds_and_seeds() {
+        let arguments = parse(&[]).unwrap();
+        assert_eq!((arguments.world, arguments.seed), (None, None));
+        assert_eq!(arguments.settings, PathBuf::from(SETTINGS_PATH));
+        let arguments = parse(&["--world", "My World", "--seed", "5"]).unwrap();
+        assert_eq!((arguments.world.as_deref(), arguments.seed), (Some("My World"), Some(5)));
+        assert!(parse(&["--world"]).is_err());
+        assert!(parse(&["--world", "a", "--seed", "-1"]).is_err());
+        assert!(parse(&["--seed", "1"]).is_err());
+        assert!(parse(&["--fly"]).is_err());
+    }
+
+    #[test]
+    fn overrides_replace_only_what_was_given() {
+        let arguments = parse(&["--fov", "90", "--vsync", "false", "--width", "800", "--log-level", "debug"]).unwrap();
+        let mut settings = Settings::default();
+        arguments.apply(&mut settings);
+        assert_eq!(settings, Settings {
+            fov: 90.0,
+            vsync: false,
+            window_width: 800,
+            log_level: LevelFilter::Debug,
+            ..Settings::default()
+        });
+        assert!(parse(&["--log-level", "loud"]).is_err());
+    }
+
+    #[test]
+    fn overrides_are_validated_like_the_file() {
+        let mut settings = Settings::default();
+        parse(&["--render-distance", "50"]).unwrap().apply(&mut settings);
+        assert_eq!(settings.validate().unwrap_err(), "render_distance must be from 1 to 12, not 50");
+    }
+}
@@ -0,0 +1,187 @@
+use std::{collections::VecDeque, time::Duration};
+
+use cgmath::Point3;
+
+use crate::{
+    direction::Direction,
+    light::Light,
+    math,
+    render::{chunk_buffers::MeshStats, font, ui::{self, Color, Rect, Ui}},
+};
+
+/// Frames kept for the average and the graph, one UI pixel wide each.
+const FRAME_HISTORY: usize = 120;
+/// Frame time of 60 frames per second, drawn as a line across the graph.
+const TARGET_FRAME_MS: f32 = 1000.0 / 60.0;
+/// Frames slower than this get cut off at the top of the graph.
+const GRAPH_MAX_MS: f32 = TARGET_FRAME_MS * 3.0;
+const GRAPH_HEIGHT: f32 = 50.0;
+const MARGIN: f32 = 4.0;
+const LINE_HEIGHT: f32 = font::GLYPH_SIZE as f32 + 2.0;
+const BACKGROUND: Color = [0.0, 0.0, 0.0, 0.5];
+
+/// How long the last frames took, oldest first.
+pub struct FrameTimes {
+    frames: VecDeque<Duration>,
+}
+
+impl FrameTimes {
+    pub fn new() -> FrameTimes {
+        FrameTimes { frames: VecDeque::with_capacity(FRAME_HISTORY) }
+    }
+
+    pub fn push(&mut self, frame_time: Duration) {
+        if self.frames.len() == FRAME_HISTORY {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame_time);
+    }
+
+    /// Average over the kept frames, so the number doesn't flicker every frame.
+    pub fn get_average(&self) -> Duration {
+        if self.frames.is_empty() {
+            return Duration::ZERO;
+        }
+        self.frames.iter().sum::<Duration>() / self.frames.len() as u32
+    }
+
+    pub fn get_max(&self) -> Duration {
+        self.frames.iter().copied().max().unwrap_or_default()
+    }
+
+    pub fn get_fps(&self) -> f32 {
+        let average = self.get_average().as_secs_f32();
+        if average > 0.0 { 1.0 / average } else { 0.0 }
+    }
+}
+
+/// Everything the overlay shows besides frame times, gathered by the game each frame it is open.
+pub struct DebugInfo {
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub facing: Direction,
+    pub loaded_chunks: usize,
+    pub pending_loads: usize,
+    pub pending_remeshes: usize,
+    pub meshes: MeshStats,
+    /// Name and position of the block being looked at.
+    pub target: Option<(String, Point3<i32>)>,
+    /// Block light at the camera.
+    pub light: Light,
+    pub generator: String,
+    pub seed: u32,
+}
+
+impl DebugInfo {
+    pub fn get_lines(&self, frame_times: &FrameTimes) -> Vec<String> {
+        let block = self.position.map(|value| value.round() as i32);
+        let chunk = math::get_chunk_position(block);
+        let mut lines = vec![
+            format!(
+                "{:.0} fps ({:.1} ms, max {:.1} ms)",
+                frame_times.get_fps(), as_millis(frame_times.get_average()), as_millis(frame_times.get_max())
+            ),
+            format!("XYZ {:.2} / {:.2} / {:.2}", self.position.x, self.position.y, self.position.z),
+            format!("Block {} {} {}", block.x, block.y, block.z),
+            format!("Chunk {} {} {}", chunk.x, chunk.y, chunk.z),
+            format!("Facing {} ({:.1} / {:.1})", self.facing.get_string(), self.yaw.to_degrees(), self.pitch.to_degrees()),
+            format!("Chunks {} loaded, {} to load, {} to mesh", self.loaded_chunks, self.pending_loads, self.pending_remeshes),
+            format!("Meshes {}, {} vertices, {} indices", self.meshes.meshes, self.meshes.vertices, self.meshes.indices),
+        ];
+        lines.push(match &self.target {
+            Some((name, position)) => format!("Looking at {} at {} {} {}", name, position.x, position.y, position.z),
+            None => "Looking at nothing".to_string(),
+        });
+        lines.push(format!("Light {} {} {}", self.light[0], self.light[1], self.light[2]));
+        // There are no biomes, the generator is all there is to say about the terrain
+        lines.push(format!("Generator {}, seed {}", self.generator, self.seed));
+        lines
+    }
+}
+
+/// Lays out the text in the top left corner and the frame time graph in the bottom left.
+pub fn draw(ui: &mut Ui, info: &DebugInfo, frame_times: &FrameTimes) {
+    for (line, text) in info.get_lines(frame_times).iter().enumerate() {
+        let y = MARGIN + line as f32 * LINE_HEIGHT;
+        ui.rect(Rect::new(MARGIN - 1.0, y - 1.0, ui::get_text_width(text) + 2.0, LINE_HEIGHT), BACKGROUND);
+        ui.text(MARGIN, y, text, ui::WHITE);
+    }
+
+    let (_, height) = ui.get_size();
+    let bottom = height - MARGIN;
+    ui.rect(Rect::new(MARGIN, bottom - GRAPH_HEIGHT, FRAME_HISTORY as f32, GRAPH_HEIGHT), BACKGROUND);
+    for (x, frame_time) in frame_times.frames.iter().enumerate() {
+        let millis = as_millis(*frame_time);
+        let bar = get_graph_height(millis);
+        ui.rect(Rect::new(MARGIN + x as f32, bottom - bar, 1.0, bar), get_frame_color(millis));
+    }
+    for target in [TARGET_FRAME_MS, TARGET_FRAME_MS * 2.0] {
+        let y = bottom - get_graph_height(target);
+        ui.rect(Rect::new(MARGIN, y, FRAME_HISTORY as f32, 1.0), [1.0, 1.0, 1.0, 0.5]);
+    }
+}
+
+fn as_millis(duration: Duration) -> f32 {
+    duration.as_secs_f32() * 1000.0
+}
+
+fn get_graph_height(millis: f32) -> f32 {
+    (millis / GRAPH_MAX_MS).min(1.0) * GRAPH_HEIGHT
+}
+
+/// Green for frames that make 60 frames per second, yellow for 30 and red for anything slower.
+fn get_frame_color(millis: f32) -> Color {
+    if millis <= TARGET_FRAME_MS {
+        [0.2, 0.9, 0.2, 0.9]
+    } else if millis <= TARGET_FRAME_MS * 2.0 {
+        [0.9, 0.9, 0.2, 0.9]
+    } else {
+        [0.9, 0.2, 0.2, 0.9]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_times_keep_the_latest_frames() {
+        let mut frame_times = FrameTimes::new();
+        assert_eq!(frame_times.get_fps(), 0.0);
+        frame_times.push(Duration::from_millis(100));
+        for _ in 0..FRAME_HISTORY {
+            frame_times.push(Duration::from_millis(20));
+        }
+        // The slow first frame has been dropped
+        assert_eq!(frame_times.get_max(), Duration::from_millis(20));
+        assert_eq!(frame_times.get_average(), Duration::from_millis(20));
+        assert!((frame_times.get_fps() - 50.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn lines_show_the_chunk_and_target() {
+        let info = DebugInfo {
+            position: Point3::new(8.4, -0.6, 23.0),
+            yaw: 0.0,
+            pitch: 0.0,
+            facing: Direction::NORTH,
+            loaded_chunks: 27,
+            pending_loads: 3,
+            pending_remeshes: 1,
+            meshes: MeshStats { meshes: 27, vertices: 400, indices: 600 },
+            target: Some(("stone".to_string(), Point3::new(1, 2, 3))),
+            light: [15, 0, 7],
+            generator: "perlin".to_string(),
+            seed: 42,
+        };
+        let lines = info.get_lines(&FrameTimes::new());
+        assert!(lines.contains(&"Block 8 -1 23".to_string()));
+        assert!(lines.contains(&"Chunk 1 0 1".to_string()));
+        assert!(lines.contains(&"Facing north (0.0 / 0.0)".to_string()));
+        assert!(lines.contains(&"Chunks 27 loaded, 3 to load, 1 to mesh".to_string()));
+        assert!(lines.contains(&"Meshes 27, 400 vertices, 600 indices".to_string()));
+        assert!(lines.contains(&"Looking at stone at 1 2 3".to_string()));
+        assert!(lines.contains(&"Light 15 0 7".to_string()));
+    }
+}
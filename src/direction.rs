@@ -11,6 +11,15 @@ pub enum Direction {
 }
 
 impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::UP,
+        Direction::DOWN,
+        Direction::NORTH,
+        Direction::SOUTH,
+        Direction::WEST,
+        Direction::EAST,
+    ];
+
     pub fn get(id: u8) -> Direction {
         match id {
             0 => Direction::UP,
@@ -22,6 +31,16 @@ impl Direction {
             _ => panic!("Invalid ID!"),
         }
     }
+    /// The horizontal direction closest to a vector, for which way the camera is facing.
+    pub fn get_facing(vec: Vector3<f32>) -> Direction {
+        if vec.x.abs() > vec.z.abs() {
+            if vec.x > 0.0 { Direction::WEST } else { Direction::EAST }
+        } else if vec.z > 0.0 {
+            Direction::SOUTH
+        } else {
+            Direction::NORTH
+        }
+    }
     pub fn get_vec(&self) -> Vector3<i8> {
         match self {
             Direction::UP => Vector3::new(0, 1, 0),
@@ -42,6 +61,16 @@ impl Direction {
             Direction::EAST => 5,
         }
     }
+    pub fn get_opposite(&self) -> Direction {
+        match self {
+            Direction::UP => Direction::DOWN,
+            Direction::DOWN => Direction::UP,
+            Direction::NORTH => Direction::SOUTH,
+            Direction::SOUTH => Direction::NORTH,
+            Direction::WEST => Direction::EAST,
+            Direction::EAST => Direction::WEST,
+        }
+    }
     pub fn get_string(&self) -> String {
         match self {
             Direction::UP => String::from("up"),
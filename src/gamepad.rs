@@ -0,0 +1,341 @@
+#[cfg(test)]
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use serde_derive::Deserialize;
+
+/// Stick movement smaller than this counts as the stick resting in the middle.
+pub const DEAD_ZONE: f32 = 0.15;
+/// Sticks past the dead zone are raised to this power, so small movements are finer than large ones.
+pub const RESPONSE_CURVE: f32 = 2.0;
+/// How far a trigger has to be pulled to count as pressed.
+pub const TRIGGER_THRESHOLD: f32 = 0.5;
+
+/// Gamepad buttons, named after their position like in gilrs. They are bound to actions as
+/// `Gamepad` followed by the name, e.g. `GamepadSouth`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum Button {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    /// Pressed once the trigger is pulled past `TRIGGER_THRESHOLD`.
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+// Only gilrs and tests create events, so without the `gamepad` feature they're unused outside of tests
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Axis {
+    /// Sticks go from -1 to 1, with right and up being positive.
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    /// Triggers go from 0 when released to 1 when fully pulled.
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GamepadEvent {
+    Button(Button, bool),
+    Axis(Axis, f32),
+}
+
+/// Where gamepad events come from, a real controller or a `VirtualGamepad`.
+pub trait GamepadSource {
+    fn next_event(&mut self) -> Option<GamepadEvent>;
+}
+
+/// A gamepad driven from code, clones share the same queue of events.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct VirtualGamepad {
+    events: Rc<RefCell<VecDeque<GamepadEvent>>>,
+}
+
+#[cfg(test)]
+impl VirtualGamepad {
+    pub fn push(&self, event: GamepadEvent) {
+        self.events.borrow_mut().push_back(event);
+    }
+}
+
+#[cfg(test)]
+impl GamepadSource for VirtualGamepad {
+    fn next_event(&mut self) -> Option<GamepadEvent> {
+        self.events.borrow_mut().pop_front()
+    }
+}
+
+/// Controllers connected to the computer through gilrs.
+#[cfg(feature = "gamepad")]
+struct GilrsGamepad {
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(feature = "gamepad")]
+impl GamepadSource for GilrsGamepad {
+    fn next_event(&mut self) -> Option<GamepadEvent> {
+        use gilrs::{Axis as GAxis, Button as GButton, EventType};
+
+        let to_button = |button: GButton| match button {
+            GButton::South => Some(Button::South),
+            GButton::East => Some(Button::East),
+            GButton::North => Some(Button::North),
+            GButton::West => Some(Button::West),
+            GButton::LeftTrigger => Some(Button::LeftBumper),
+            GButton::RightTrigger => Some(Button::RightBumper),
+            GButton::Select => Some(Button::Select),
+            GButton::Start => Some(Button::Start),
+            GButton::LeftThumb => Some(Button::LeftStick),
+            GButton::RightThumb => Some(Button::RightStick),
+            GButton::DPadUp => Some(Button::DPadUp),
+            GButton::DPadDown => Some(Button::DPadDown),
+            GButton::DPadLeft => Some(Button::DPadLeft),
+            GButton::DPadRight => Some(Button::DPadRight),
+            _ => None,
+        };
+        // Skip events we have no use for instead of ending the frame's events early
+        while let Some(event) = self.gilrs.next_event() {
+            let event = match event.event {
+                EventType::ButtonPressed(button, _) => to_button(button).map(|button| GamepadEvent::Button(button, true)),
+                EventType::ButtonReleased(button, _) => to_button(button).map(|button| GamepadEvent::Button(button, false)),
+                // Triggers are analog, `Gamepad` turns them into presses itself
+                EventType::ButtonChanged(GButton::LeftTrigger2, value, _) => Some(GamepadEvent::Axis(Axis::LeftTrigger, value)),
+                EventType::ButtonChanged(GButton::RightTrigger2, value, _) => Some(GamepadEvent::Axis(Axis::RightTrigger, value)),
+                EventType::AxisChanged(axis, value, _) => match axis {
+                    GAxis::LeftStickX => Some(Axis::LeftStickX),
+                    GAxis::LeftStickY => Some(Axis::LeftStickY),
+                    GAxis::RightStickX => Some(Axis::RightStickX),
+                    GAxis::RightStickY => Some(Axis::RightStickY),
+                    _ => None,
+                }.map(|axis| GamepadEvent::Axis(axis, value)),
+                _ => None,
+            };
+            if event.is_some() {
+                return event;
+            }
+        }
+        None
+    }
+}
+
+/// The state of the sticks and triggers, built up from a source's events.
+pub struct Gamepad {
+    source: Option<Box<dyn GamepadSource>>,
+    left_stick: (f32, f32),
+    right_stick: (f32, f32),
+    triggers: [f32; 2],
+}
+
+impl Gamepad {
+    #[cfg_attr(not(feature = "gamepad"), allow(dead_code))]
+    pub fn new(source: Box<dyn GamepadSource>) -> Gamepad {
+        Gamepad {
+            source: Some(source),
+            left_stick: (0.0, 0.0),
+            right_stick: (0.0, 0.0),
+            triggers: [0.0; 2],
+        }
+    }
+
+    /// A gamepad that never does anything, for when there is no way to read real ones.
+    pub fn disconnected() -> Gamepad {
+        Gamepad {
+            source: None,
+            left_stick: (0.0, 0.0),
+            right_stick: (0.0, 0.0),
+            triggers: [0.0; 2],
+        }
+    }
+
+    /// Reads controllers through gilrs when the game is built with the `gamepad` feature.
+    pub fn open() -> Gamepad {
+        #[cfg(feature = "gamepad")]
+        match gilrs::Gilrs::new() {
+            Ok(gilrs) => return Gamepad::new(Box::new(GilrsGamepad { gilrs })),
+            Err(error) => log::warn!("Gamepads are unavailable: {}", error),
+        }
+        Gamepad::disconnected()
+    }
+
+    /// Takes every event since the last poll and returns the buttons that were pressed or released,
+    /// in order. Triggers are reported as buttons when they cross `TRIGGER_THRESHOLD`.
+    pub fn poll(&mut self) -> Vec<(Button, bool)> {
+        let mut buttons = Vec::new();
+        while let Some(event) = self.source.as_mut().and_then(|source| source.next_event()) {
+            match event {
+                GamepadEvent::Button(button, pressed) => buttons.push((button, pressed)),
+                GamepadEvent::Axis(Axis::LeftStickX, value) => self.left_stick.0 = value,
+                GamepadEvent::Axis(Axis::LeftStickY, value) => self.left_stick.1 = value,
+                GamepadEvent::Axis(Axis::RightStickX, value) => self.right_stick.0 = value,
+                GamepadEvent::Axis(Axis::RightStickY, value) => self.right_stick.1 = value,
+                GamepadEvent::Axis(axis @ (Axis::LeftTrigger | Axis::RightTrigger), value) => {
+                    let (index, button) = if axis == Axis::LeftTrigger { (0, Button::LeftTrigger) } else { (1, Button::RightTrigger) };
+                    let was_pressed = self.triggers[index] >= TRIGGER_THRESHOLD;
+                    let is_pressed = value >= TRIGGER_THRESHOLD;
+                    if was_pressed != is_pressed {
+                        buttons.push((button, is_pressed));
+                    }
+                    self.triggers[index] = value;
+                }
+            }
+        }
+        buttons
+    }
+
+    /// Where the left stick is pushed, right then forward, after the dead zone and response curve.
+    pub fn get_movement(&self) -> (f32, f32) {
+        shape_stick(self.left_stick)
+    }
+
+    /// Where the right stick is pushed, right then up, after the dead zone and response curve.
+    pub fn get_look(&self) -> (f32, f32) {
+        shape_stick(self.right_stick)
+    }
+}
+
+/// Zeroes a stick inside the dead zone and scales the rest back up to the full range before
+/// applying the response curve, keeping the direction it is pushed in.
+fn shape_stick((x, y): (f32, f32)) -> (f32, f32) {
+    let magnitude = (x * x + y * y).sqrt();
+    if magnitude <= DEAD_ZONE {
+        return (0.0, 0.0);
+    }
+    let scaled = ((magnitude.min(1.0) - DEAD_ZONE) / (1.0 - DEAD_ZONE)).powf(RESPONSE_CURVE);
+    (x / magnitude * scaled, y / magnitude * scaled)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Point3;
+
+    use crate::{camera::CameraController, player::{Player, WALK_SPEED}};
+
+    use super::*;
+
+    fn virtual_gamepad() -> (VirtualGamepad, Gamepad) {
+        let device = VirtualGamepad::default();
+        let gamepad = Gamepad::new(Box::new(device.clone()));
+        (device, gamepad)
+    }
+
+    fn assert_near(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 0.001, "{} is not close to {}", value, expected);
+    }
+
+    #[test]
+    fn dead_zone_ignores_small_movements() {
+        assert_eq!(shape_stick((0.1, 0.05)), (0.0, 0.0));
+        assert_eq!(shape_stick((0.0, -DEAD_ZONE)), (0.0, 0.0));
+        let (x, y) = shape_stick((0.0, DEAD_ZONE + 0.01));
+        assert_eq!(x, 0.0);
+        assert!(y > 0.0 && y < 0.01);
+    }
+
+    #[test]
+    fn full_stick_is_full_speed() {
+        assert_near(shape_stick((1.0, 0.0)).0, 1.0);
+        assert_near(shape_stick((0.0, -1.0)).1, -1.0);
+        // Corners of square stick ranges don't go past the full range
+        let (x, y) = shape_stick((1.0, 1.0));
+        assert_near((x * x + y * y).sqrt(), 1.0);
+    }
+
+    #[test]
+    fn response_curve_makes_half_stick_slower() {
+        let half = DEAD_ZONE + (1.0 - DEAD_ZONE) / 2.0;
+        assert_near(shape_stick((half, 0.0)).0, 0.5f32.powf(RESPONSE_CURVE));
+    }
+
+    #[test]
+    fn buttons_pass_through_in_order() {
+        let (device, mut gamepad) = virtual_gamepad();
+        device.push(GamepadEvent::Button(Button::South, true));
+        device.push(GamepadEvent::Button(Button::Start, true));
+        device.push(GamepadEvent::Button(Button::South, false));
+        assert_eq!(gamepad.poll(), vec![(Button::South, true), (Button::Start, true), (Button::South, false)]);
+        assert!(gamepad.poll().is_empty());
+    }
+
+    #[test]
+    fn triggers_press_once_past_the_threshold() {
+        let (device, mut gamepad) = virtual_gamepad();
+        device.push(GamepadEvent::Axis(Axis::RightTrigger, 0.3));
+        assert!(gamepad.poll().is_empty());
+        device.push(GamepadEvent::Axis(Axis::RightTrigger, 0.6));
+        device.push(GamepadEvent::Axis(Axis::RightTrigger, 0.9));
+        assert_eq!(gamepad.poll(), vec![(Button::RightTrigger, true)]);
+        device.push(GamepadEvent::Axis(Axis::RightTrigger, 0.1));
+        device.push(GamepadEvent::Axis(Axis::LeftTrigger, 1.0));
+        assert_eq!(gamepad.poll(), vec![(Button::RightTrigger, false), (Button::LeftTrigger, true)]);
+    }
+
+    #[test]
+    fn sticks_keep_their_last_position() {
+        let (device, mut gamepad) = virtual_gamepad();
+        device.push(GamepadEvent::Axis(Axis::LeftStickY, 1.0));
+        device.push(GamepadEvent::Axis(Axis::RightStickX, -1.0));
+        gamepad.poll();
+        gamepad.poll();
+        assert_near(gamepad.get_movement().1, 1.0);
+        assert_near(gamepad.get_look().0, -1.0);
+        device.push(GamepadEvent::Axis(Axis::LeftStickY, 0.0));
+        gamepad.poll();
+        assert_eq!(gamepad.get_movement(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn disconnected_gamepad_does_nothing() {
+        let mut gamepad = Gamepad::disconnected();
+        assert!(gamepad.poll().is_empty());
+        assert_eq!(gamepad.get_movement(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn stick_walks_the_player() {
+        let (device, mut gamepad) = virtual_gamepad();
+        let mut controller = CameraController::new();
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        let floor = |block: Point3<i32>| block.y == 0;
+
+        // Full forward walks as fast as the keyboard
+        device.push(GamepadEvent::Axis(Axis::LeftStickY, 1.0));
+        gamepad.poll();
+        controller.set_analog(gamepad.get_movement(), gamepad.get_look());
+        for _ in 0..10 {
+            player.tick(&controller.get_input(), 0.0, 0.0, floor);
+        }
+        assert_near(player.position.x, 10.0 * WALK_SPEED);
+        assert_near(player.position.z, 0.0);
+
+        // Pushing a little to the side inside the dead zone changes nothing
+        device.push(GamepadEvent::Axis(Axis::LeftStickY, 0.0));
+        device.push(GamepadEvent::Axis(Axis::LeftStickX, 0.1));
+        gamepad.poll();
+        controller.set_analog(gamepad.get_movement(), gamepad.get_look());
+        player.tick(&controller.get_input(), 0.0, 0.0, floor);
+        assert_near(player.position.x, 10.0 * WALK_SPEED);
+        assert_near(player.position.z, 0.0);
+
+        // Half way past the dead zone walks slower than full speed
+        device.push(GamepadEvent::Axis(Axis::LeftStickX, DEAD_ZONE + (1.0 - DEAD_ZONE) / 2.0));
+        gamepad.poll();
+        controller.set_analog(gamepad.get_movement(), gamepad.get_look());
+        player.tick(&controller.get_input(), 0.0, 0.0, floor);
+        assert_near(player.position.z, WALK_SPEED * 0.5f32.powf(RESPONSE_CURVE));
+    }
+}
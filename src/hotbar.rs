@@ -0,0 +1,97 @@
+use crate::block_types;
+
+pub const SLOTS: usize = 9;
+
+/// The blocks the player can place, one of which is in their hand.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hotbar {
+    /// Block id in each slot.
+    slots: [u32; SLOTS],
+    selected: usize,
+}
+
+impl Default for Hotbar {
+    /// The first nine blocks after air.
+    fn default() -> Self {
+        Hotbar {
+            slots: [1, 2, 3, 4, 5, 6, 7, 8, 9],
+            selected: 0,
+        }
+    }
+}
+
+impl Hotbar {
+    /// A hotbar from saved slots, falling back to the default for anything that isn't a block that
+    /// exists, like slots saved before a block was removed.
+    pub fn load(slots: &[u32], selected: usize) -> Hotbar {
+        let mut hotbar = Hotbar::default();
+        if slots.len() == SLOTS && slots.iter().all(|block| *block != 0 && block_types::exists(*block)) {
+            hotbar.slots.copy_from_slice(slots);
+        }
+        hotbar.selected = if selected < SLOTS { selected } else { 0 };
+        hotbar
+    }
+
+    pub fn get_slots(&self) -> &[u32; SLOTS] {
+        &self.slots
+    }
+
+    pub fn get_selected_slot(&self) -> usize {
+        self.selected
+    }
+
+    /// Id of the block in the selected slot, which is what gets placed.
+    pub fn get_selected_block(&self) -> u32 {
+        self.slots[self.selected]
+    }
+
+    pub fn select(&mut self, slot: usize) {
+        self.selected = slot.min(SLOTS - 1);
+    }
+
+    /// Moves the selection by `offset` slots, wrapping around either end.
+    pub fn scroll(&mut self, offset: i32) {
+        self.selected = (self.selected as i32 + offset).rem_euclid(SLOTS as i32) as usize;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolling_wraps_around() {
+        let mut hotbar = Hotbar::default();
+        hotbar.scroll(-1);
+        assert_eq!(hotbar.get_selected_slot(), 8);
+        assert_eq!(hotbar.get_selected_block(), 9);
+        hotbar.scroll(1);
+        assert_eq!(hotbar.get_selected_slot(), 0);
+        hotbar.scroll(20);
+        assert_eq!(hotbar.get_selected_slot(), 2);
+    }
+
+    #[test]
+    fn selecting_clamps_to_the_last_slot() {
+        let mut hotbar = Hotbar::default();
+        hotbar.select(4);
+        assert_eq!(hotbar.get_selected_block(), 5);
+        hotbar.select(100);
+        assert_eq!(hotbar.get_selected_slot(), 8);
+    }
+
+    #[test]
+    fn loading_keeps_valid_slots_only() {
+        block_types::init_for_tests();
+        let slots = [3, 3, 2, 1, 1, 1, 8, 8, 4];
+        let hotbar = Hotbar::load(&slots, 6);
+        assert_eq!(hotbar.get_slots(), &slots);
+        assert_eq!(hotbar.get_selected_block(), 8);
+
+        // Air, missing blocks and the wrong number of slots all fall back to the default
+        for slots in [&[0, 1, 2, 3, 4, 5, 6, 7, 8][..], &[1, 2, 3, 4, 5, 6, 7, 8, 999], &[1, 2, 3]] {
+            assert_eq!(Hotbar::load(slots, 0), Hotbar::default());
+        }
+        assert_eq!(Hotbar::load(&slots, 9).get_selected_slot(), 0);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    block_types,
+    direction::Direction,
+    hotbar::{self, Hotbar},
+    render::{font, ui::{self, Color, Rect, Ui, UiTexture}},
+};
+
+/// Textures in a row of the block atlas, which `shader.wgsl` divides by too.
+const ATLAS_TEXTURES: f32 = 256.0;
+
+const CROSSHAIR_SIZE: f32 = 9.0;
+const CROSSHAIR_COLOR: Color = [1.0, 1.0, 1.0, 0.8];
+const SLOT_SIZE: f32 = 20.0;
+const ICON_SIZE: f32 = 16.0;
+/// Between the hotbar and the bottom of the window.
+const MARGIN: f32 = 4.0;
+const BACKGROUND: Color = [0.0, 0.0, 0.0, 0.5];
+
+/// Lays out the crosshair, the hotbar and the name of the selected block.
+pub fn draw(ui: &mut Ui, hotbar: &Hotbar) {
+    let (width, height) = ui.get_size();
+    draw_crosshair(ui, (width / 2.0).floor(), (height / 2.0).floor());
+
+    let bar_width = SLOT_SIZE * hotbar::SLOTS as f32;
+    let left = ((width - bar_width) / 2.0).floor();
+    let top = (height - MARGIN - SLOT_SIZE).floor();
+    ui.rect(Rect::new(left, top, bar_width, SLOT_SIZE), BACKGROUND);
+    for (slot, block) in hotbar.get_slots().iter().enumerate() {
+        let x = left + slot as f32 * SLOT_SIZE;
+        let inset = (SLOT_SIZE - ICON_SIZE) / 2.0;
+        ui.image(Rect::new(x + inset, top + inset, ICON_SIZE, ICON_SIZE), UiTexture::Blocks, get_icon_uv(*block), ui::WHITE);
+    }
+    let selected_x = left + hotbar.get_selected_slot() as f32 * SLOT_SIZE;
+    draw_outline(ui, Rect::new(selected_x, top, SLOT_SIZE, SLOT_SIZE), ui::WHITE);
+
+    let name = &block_types::get(hotbar.get_selected_block()).name;
+    let x = ((width - ui::get_text_width(name)) / 2.0).floor();
+    ui.text(x, top - MARGIN - font::GLYPH_SIZE as f32, name, ui::WHITE);
+}
+
+/// Part of the block atlas showing a block's side, which is how it's shown in the hotbar.
+fn get_icon_uv(block: u32) -> Rect {
+    let texture = block_types::get(block).get_texture(Direction::NORTH);
+    Rect::new(texture as f32 / ATLAS_TEXTURES, 0.0, 1.0 / ATLAS_TEXTURES, 1.0)
+}
+
+fn draw_crosshair(ui: &mut Ui, x: f32, y: f32) {
+    let half = (CROSSHAIR_SIZE / 2.0).floor();
+    ui.rect(Rect::new(x - half, y, CROSSHAIR_SIZE, 1.0), CROSSHAIR_COLOR);
+    // The vertical line leaves out the middle pixel so the two don't blend twice
+    ui.rect(Rect::new(x, y - half, 1.0, half), CROSSHAIR_COLOR);
+    ui.rect(Rect::new(x, y + 1.0, 1.0, half), CROSSHAIR_COLOR);
+}
+
+/// A one pixel border just inside of `rect`.
+fn draw_outline(ui: &mut Ui, rect: Rect, color: Color) {
+    ui.rect(Rect::new(rect.x, rect.y, rect.width, 1.0), color);
+    ui.rect(Rect::new(rect.x, rect.y + rect.height - 1.0, rect.width, 1.0), color);
+    ui.rect(Rect::new(rect.x, rect.y + 1.0, 1.0, rect.height - 2.0), color);
+    ui.rect(Rect::new(rect.x + rect.width - 1.0, rect.y + 1.0, 1.0, rect.height - 2.0), color);
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::dpi::PhysicalSize;
+
+    use super::*;
+
+    #[test]
+    fn icons_show_a_side_of_each_block() {
+        block_types::init_for_tests();
+        let mut ui = Ui::new(PhysicalSize::new(640, 480), 2.0);
+        let mut hotbar = Hotbar::default();
+        hotbar.select(2);
+        draw(&mut ui, &hotbar);
+
+        let batches = ui.get_batches();
+        let icons = batches.iter().find(|batch| batch.texture == UiTexture::Blocks).unwrap();
+        assert_eq!(icons.quads.len(), hotbar::SLOTS);
+        for (slot, block) in hotbar.get_slots().iter().enumerate() {
+            let vertex = ui.get_vertices()[(icons.quads.start as usize + slot) * 4];
+            let texture = block_types::get(*block).get_texture(Direction::NORTH);
+            assert_eq!(vertex.uv, [texture as f32 / ATLAS_TEXTURES, 0.0]);
+        }
+    }
+
+    #[test]
+    fn hotbar_is_centred_at_the_bottom() {
+        block_types::init_for_tests();
+        let mut ui = Ui::new(PhysicalSize::new(640, 480), 2.0);
+        draw(&mut ui, &Hotbar::default());
+
+        // The background comes right after the three crosshair quads
+        let background = ui.get_vertices()[3 * 4];
+        let left = (320.0 - SLOT_SIZE * hotbar::SLOTS as f32) / 2.0;
+        assert_eq!(background.position, [left, 240.0 - MARGIN - SLOT_SIZE]);
+        assert_eq!(background.color, BACKGROUND);
+    }
+}
@@ -0,0 +1,342 @@
+use std::{collections::{HashMap, HashSet}, fmt};
+
+use serde_derive::{Deserialize, Serialize};
+use winit::event::{MouseButton, VirtualKeyCode};
+
+use crate::gamepad::Button;
+
+/// Something the player can do, bound to keys and mouse buttons in the `[controls]` table of the
+/// settings file, e.g. `jump = "Space"` or `place = ["MouseRight", "E"]`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Sneak,
+    Break,
+    Place,
+    TogglePause,
+    ToggleFly,
+    ToggleNoclip,
+    ToggleDebug,
+    Hotbar1,
+    Hotbar2,
+    Hotbar3,
+    Hotbar4,
+    Hotbar5,
+    Hotbar6,
+    Hotbar7,
+    Hotbar8,
+    Hotbar9,
+    HotbarNext,
+    HotbarPrevious,
+}
+
+impl Action {
+    /// Every action, in the order the controls screen lists them.
+    pub const ALL: [Action; 23] = [
+        Action::MoveForward, Action::MoveBackward, Action::MoveLeft, Action::MoveRight,
+        Action::Jump, Action::Sneak, Action::Break, Action::Place,
+        Action::TogglePause, Action::ToggleFly, Action::ToggleNoclip, Action::ToggleDebug,
+        Action::Hotbar1, Action::Hotbar2, Action::Hotbar3,
+        Action::Hotbar4, Action::Hotbar5, Action::Hotbar6,
+        Action::Hotbar7, Action::Hotbar8, Action::Hotbar9,
+        Action::HotbarNext, Action::HotbarPrevious,
+    ];
+
+    pub const HOTBAR: [Action; 9] = [
+        Action::Hotbar1, Action::Hotbar2, Action::Hotbar3,
+        Action::Hotbar4, Action::Hotbar5, Action::Hotbar6,
+        Action::Hotbar7, Action::Hotbar8, Action::Hotbar9,
+    ];
+
+    /// Index of the hotbar slot the action selects, if it selects one.
+    pub fn get_hotbar_slot(&self) -> Option<usize> {
+        Action::HOTBAR.iter().position(|action| action == self)
+    }
+
+    /// The name used for the action in the settings file, e.g. `move_forward`.
+    pub fn get_name(&self) -> String {
+        match toml::Value::try_from(self) {
+            Ok(toml::Value::String(name)) => name,
+            _ => format!("{:?}", self),
+        }
+    }
+}
+
+/// A key, mouse button, scroll wheel direction or gamepad button. Keys are named like winit's
+/// `VirtualKeyCode`s, e.g. `W`, `Space` or `LShift`, mouse buttons are `MouseLeft`, `MouseRight`,
+/// `MouseMiddle` or `Mouse4` and up, the wheel is `ScrollUp` or `ScrollDown`, and gamepad buttons
+/// are `Gamepad` followed by the button, e.g. `GamepadSouth`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+    /// Pressed and released at once for every step the wheel turns.
+    Scroll(ScrollDirection),
+    Gamepad(Button),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ScrollDirection {
+    Up,
+    Down,
+}
+
+impl Binding {
+    pub fn parse(name: &str) -> Result<Binding, String> {
+        let mouse = match name {
+            "MouseLeft" => Some(MouseButton::Left),
+            "MouseRight" => Some(MouseButton::Right),
+            "MouseMiddle" => Some(MouseButton::Middle),
+            _ => name.strip_prefix("Mouse").and_then(|number| number.parse().ok()).map(MouseButton::Other),
+        };
+        if let Some(button) = mouse {
+            return Ok(Binding::Mouse(button));
+        }
+        match name {
+            "ScrollUp" => return Ok(Binding::Scroll(ScrollDirection::Up)),
+            "ScrollDown" => return Ok(Binding::Scroll(ScrollDirection::Down)),
+            _ => {}
+        }
+        if let Some(button) = name.strip_prefix("Gamepad") {
+            return toml::Value::String(button.to_string()).try_into()
+                .map(Binding::Gamepad)
+                .map_err(|_| format!("Unknown gamepad button: {}", name));
+        }
+        toml::Value::String(name.to_string()).try_into()
+            .map(Binding::Key)
+            .map_err(|_| format!("Unknown key: {}", name))
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key(key) => write!(f, "{:?}", key),
+            Binding::Mouse(MouseButton::Left) => write!(f, "MouseLeft"),
+            Binding::Mouse(MouseButton::Right) => write!(f, "MouseRight"),
+            Binding::Mouse(MouseButton::Middle) => write!(f, "MouseMiddle"),
+            Binding::Mouse(MouseButton::Other(number)) => write!(f, "Mouse{}", number),
+            Binding::Scroll(direction) => write!(f, "Scroll{:?}", direction),
+            Binding::Gamepad(button) => write!(f, "Gamepad{:?}", button),
+        }
+    }
+}
+
+/// Which actions each key and mouse button triggers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyBindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let key = Binding::Key;
+        let gamepad = Binding::Gamepad;
+        // Moving and looking around with a gamepad uses the sticks instead
+        let mut bindings = HashMap::from([
+            (Action::MoveForward, vec![key(VirtualKeyCode::W)]),
+            (Action::MoveBackward, vec![key(VirtualKeyCode::S)]),
+            (Action::MoveLeft, vec![key(VirtualKeyCode::A)]),
+            (Action::MoveRight, vec![key(VirtualKeyCode::D)]),
+            (Action::Jump, vec![key(VirtualKeyCode::Space), gamepad(Button::South)]),
+            (Action::Sneak, vec![key(VirtualKeyCode::LShift), gamepad(Button::East)]),
+            (Action::Break, vec![Binding::Mouse(MouseButton::Left), gamepad(Button::RightTrigger)]),
+            (Action::Place, vec![Binding::Mouse(MouseButton::Right), gamepad(Button::LeftTrigger)]),
+            (Action::TogglePause, vec![key(VirtualKeyCode::Escape), gamepad(Button::Start)]),
+            (Action::ToggleFly, vec![key(VirtualKeyCode::F), gamepad(Button::DPadUp)]),
+            (Action::ToggleNoclip, vec![key(VirtualKeyCode::N), gamepad(Button::DPadDown)]),
+            (Action::ToggleDebug, vec![key(VirtualKeyCode::F3), gamepad(Button::Select)]),
+            (Action::HotbarNext, vec![Binding::Scroll(ScrollDirection::Down), gamepad(Button::RightBumper)]),
+            (Action::HotbarPrevious, vec![Binding::Scroll(ScrollDirection::Up), gamepad(Button::LeftBumper)]),
+        ]);
+        let number_keys = [
+            VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3,
+            VirtualKeyCode::Key4, VirtualKeyCode::Key5, VirtualKeyCode::Key6,
+            VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9,
+        ];
+        for (action, number_key) in Action::HOTBAR.into_iter().zip(number_keys) {
+            bindings.insert(action, vec![key(number_key)]);
+        }
+        KeyBindings { bindings }
+    }
+}
+
+impl KeyBindings {
+    /// Actions missing from the `[controls]` table keep their default bindings.
+    pub fn from_controls(controls: Option<&toml::Value>) -> Result<KeyBindings, String> {
+        let mut key_bindings = KeyBindings::default();
+        let controls = match controls {
+            Some(toml::Value::Table(controls)) => controls,
+            Some(_) => return Err("controls must be a table".to_string()),
+            None => return Ok(key_bindings),
+        };
+        for (name, value) in controls {
+            let action: Action = toml::Value::String(name.clone()).try_into()
+                .map_err(|_| format!("Unknown action: {}", name))?;
+            let names = match value {
+                toml::Value::String(name) => vec![name.as_str()],
+                toml::Value::Array(names) => names.iter()
+                    .map(|binding| binding.as_str().ok_or(format!("Bindings for {} must be strings", name)))
+                    .collect::<Result<_, _>>()?,
+                _ => return Err(format!("Bindings for {} must be a string or a list of strings", name)),
+            };
+            let bindings = names.into_iter().map(Binding::parse).collect::<Result<_, _>>()?;
+            key_bindings.bindings.insert(action, bindings);
+        }
+        Ok(key_bindings)
+    }
+
+    pub fn get_bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Binds an action to a key, mouse button or wheel direction instead of its current ones, or to
+    /// a gamepad button instead of its current gamepad buttons. The other kind is left alone, so
+    /// rebinding the keyboard doesn't unbind the gamepad.
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        let is_gamepad = |binding: &Binding| matches!(binding, Binding::Gamepad(_));
+        let bindings = self.bindings.entry(action).or_default();
+        bindings.retain(|other| is_gamepad(other) != is_gamepad(&binding));
+        bindings.push(binding);
+    }
+
+    /// The `[controls]` table of the settings file, with every action listed.
+    pub fn to_toml(&self) -> toml::value::Table {
+        Action::ALL.iter()
+            .map(|action| {
+                let names = self.get_bindings(*action).iter().map(|binding| toml::Value::String(binding.to_string())).collect();
+                (action.get_name(), toml::Value::Array(names))
+            })
+            .collect()
+    }
+
+    /// Every action the binding triggers.
+    pub fn get_actions(&self, binding: Binding) -> Vec<Action> {
+        let mut actions: Vec<Action> = self.bindings.iter()
+            .filter(|(_, bindings)| bindings.contains(&binding))
+            .map(|(action, _)| *action)
+            .collect();
+        actions.sort();
+        actions
+    }
+
+    /// Keys and mouse buttons bound to more than one action, each with the actions it triggers.
+    pub fn get_conflicts(&self) -> Vec<(Binding, Vec<Action>)> {
+        let bindings: HashSet<Binding> = self.bindings.values().flatten().copied().collect();
+        let mut conflicts: Vec<(Binding, Vec<Action>)> = bindings.into_iter()
+            .map(|binding| (binding, self.get_actions(binding)))
+            .filter(|(_, actions)| actions.len() > 1)
+            .collect();
+        conflicts.sort_by_key(|(_, actions)| actions.clone());
+        conflicts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads the `[controls]` table of a whole settings file, like `Settings` does.
+    fn from_toml(text: &str) -> Result<KeyBindings, String> {
+        let settings: toml::Value = toml::from_str(text).map_err(|error| error.to_string())?;
+        KeyBindings::from_controls(settings.get("controls"))
+    }
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        let bindings = KeyBindings::default();
+        assert!(bindings.get_conflicts().is_empty());
+        assert_eq!(bindings.get_actions(Binding::Key(VirtualKeyCode::W)), vec![Action::MoveForward]);
+        assert_eq!(bindings.get_actions(Binding::Mouse(MouseButton::Left)), vec![Action::Break]);
+        assert_eq!(bindings.get_actions(Binding::Key(VirtualKeyCode::Key3)), vec![Action::Hotbar3]);
+        assert_eq!(bindings.get_actions(Binding::Gamepad(Button::RightTrigger)), vec![Action::Break]);
+        assert!(bindings.get_actions(Binding::Key(VirtualKeyCode::Q)).is_empty());
+    }
+
+    #[test]
+    fn parses_keys_and_mouse_buttons() {
+        assert_eq!(Binding::parse("Space"), Ok(Binding::Key(VirtualKeyCode::Space)));
+        assert_eq!(Binding::parse("LShift"), Ok(Binding::Key(VirtualKeyCode::LShift)));
+        assert_eq!(Binding::parse("MouseMiddle"), Ok(Binding::Mouse(MouseButton::Middle)));
+        assert_eq!(Binding::parse("Mouse4"), Ok(Binding::Mouse(MouseButton::Other(4))));
+        assert_eq!(Binding::parse("ScrollDown"), Ok(Binding::Scroll(ScrollDirection::Down)));
+        assert_eq!(Binding::parse("GamepadRightTrigger"), Ok(Binding::Gamepad(Button::RightTrigger)));
+        assert!(Binding::parse("Banana").is_err());
+        assert!(Binding::parse("Mouse").is_err());
+        assert!(Binding::parse("GamepadBanana").is_err());
+    }
+
+    #[test]
+    fn bindings_print_the_way_they_are_parsed() {
+        for name in ["W", "Key1", "Escape", "MouseLeft", "MouseRight", "MouseMiddle", "Mouse5", "ScrollUp", "ScrollDown", "GamepadSouth", "GamepadDPadUp"] {
+            assert_eq!(Binding::parse(name).unwrap().to_string(), name);
+        }
+    }
+
+    #[test]
+    fn file_overrides_only_the_listed_actions() {
+        let bindings = from_toml("[controls]\njump = \"J\"\nplace = [\"MouseRight\", \"E\"]\n").unwrap();
+        assert_eq!(bindings.get_actions(Binding::Key(VirtualKeyCode::J)), vec![Action::Jump]);
+        assert!(bindings.get_actions(Binding::Key(VirtualKeyCode::Space)).is_empty());
+        assert_eq!(bindings.get_actions(Binding::Key(VirtualKeyCode::E)), vec![Action::Place]);
+        assert_eq!(bindings.get_actions(Binding::Mouse(MouseButton::Right)), vec![Action::Place]);
+        assert_eq!(bindings.get_actions(Binding::Key(VirtualKeyCode::W)), vec![Action::MoveForward]);
+    }
+
+    #[test]
+    fn missing_controls_table_uses_defaults() {
+        let bindings = from_toml("").unwrap();
+        assert_eq!(bindings.get_actions(Binding::Key(VirtualKeyCode::Space)), vec![Action::Jump]);
+    }
+
+    #[test]
+    fn reports_conflicts() {
+        let bindings = from_toml("[controls]\njump = \"W\"\nsneak = [\"W\", \"C\"]\n").unwrap();
+        let conflicts = bindings.get_conflicts();
+        assert_eq!(conflicts, vec![(Binding::Key(VirtualKeyCode::W), vec![Action::MoveForward, Action::Jump, Action::Sneak])]);
+    }
+
+    #[test]
+    fn rejects_bad_files() {
+        assert!(from_toml("[controls]\nfly_to_the_moon = \"M\"\n").is_err());
+        assert!(from_toml("[controls]\njump = \"NotAKey\"\n").is_err());
+        assert!(from_toml("[controls]\njump = 5\n").is_err());
+        assert!(from_toml("[controls]\njump = [\"Space\", 5]\n").is_err());
+        assert!(from_toml("controls = 1\n").is_err());
+        assert!(from_toml("[controls\n").is_err());
+    }
+
+    #[test]
+    fn rebinding_keeps_the_other_kind() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(Action::Jump, Binding::Key(VirtualKeyCode::J));
+        assert_eq!(bindings.get_bindings(Action::Jump), &[Binding::Gamepad(Button::South), Binding::Key(VirtualKeyCode::J)]);
+        bindings.rebind(Action::Jump, Binding::Gamepad(Button::North));
+        assert_eq!(bindings.get_bindings(Action::Jump), &[Binding::Key(VirtualKeyCode::J), Binding::Gamepad(Button::North)]);
+    }
+
+    #[test]
+    fn controls_table_round_trips() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(Action::Place, Binding::Scroll(ScrollDirection::Up));
+        let mut settings = toml::value::Table::new();
+        settings.insert("controls".to_string(), toml::Value::Table(bindings.to_toml()));
+        let loaded = from_toml(&toml::to_string(&settings).unwrap()).unwrap();
+        for action in Action::ALL {
+            assert_eq!(loaded.get_bindings(action), bindings.get_bindings(action));
+        }
+        assert_eq!(Action::MoveForward.get_name(), "move_forward");
+    }
+
+    #[test]
+    fn hotbar_slots() {
+        assert_eq!(Action::Hotbar1.get_hotbar_slot(), Some(0));
+        assert_eq!(Action::Hotbar9.get_hotbar_slot(), Some(8));
+        assert_eq!(Action::Jump.get_hotbar_slot(), None);
+    }
+}
@@ -0,0 +1,201 @@
+use std::{fs, io, path::Path};
+
+use cgmath::Point3;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{hotbar::Hotbar, player::{MoveMode, Player}};
+
+/// Version of the world format written by this build. Worlds saved by a newer one aren't loaded,
+/// since this build may not understand them.
+pub const FORMAT_VERSION: u32 = 1;
+/// The only terrain generator there is so far.
+pub const GENERATOR: &str = "perlin";
+pub const LEVEL_FILE: &str = "level.toml";
+pub const PLAYER_FILE: &str = "player.toml";
+
+/// Everything about a world that isn't a chunk, saved to the `level.toml` in its directory.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+    pub version: u32,
+    pub seed: u32,
+    pub generator: String,
+    /// The world time in ticks, see `WorldTime`.
+    pub time: u64,
+    /// Where players without a save of their own start, at their feet.
+    pub spawn: [f32; 3],
+}
+
+impl Level {
+    pub fn new(seed: u32, time: u64, spawn: Point3<f32>) -> Level {
+        Level {
+            version: FORMAT_VERSION,
+            seed,
+            generator: GENERATOR.to_string(),
+            time,
+            spawn: spawn.into(),
+        }
+    }
+
+    /// Returns `None` when the world has no level file yet.
+    pub fn load(directory: &Path) -> io::Result<Option<Level>> {
+        let level: Level = match load_toml(&directory.join(LEVEL_FILE))? {
+            Some(level) => level,
+            None => return Ok(None),
+        };
+        if level.version > FORMAT_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                "World format {} is newer than the supported {}", level.version, FORMAT_VERSION
+            )));
+        }
+        Ok(Some(level))
+    }
+
+    pub fn save(&self, directory: &Path) -> io::Result<()> {
+        save_toml(&directory.join(LEVEL_FILE), self)
+    }
+
+    pub fn get_spawn(&self) -> Point3<f32> {
+        self.spawn.into()
+    }
+}
+
+/// Where the player was and what they had, saved to the `player.toml` in the world's directory.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerData {
+    /// At the player's feet.
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub mode: MoveMode,
+    /// Block ids in the hotbar, the whole inventory for now.
+    #[serde(default)]
+    pub hotbar: Vec<u32>,
+    #[serde(default)]
+    pub selected_slot: usize,
+}
+
+impl PlayerData {
+    pub fn new(player: &Player, yaw: f32, pitch: f32, hotbar: &Hotbar) -> PlayerData {
+        PlayerData {
+            position: player.position.into(),
+            yaw,
+            pitch,
+            mode: player.mode,
+            hotbar: hotbar.get_slots().to_vec(),
+            selected_slot: hotbar.get_selected_slot(),
+        }
+    }
+
+    /// Returns `None` when the player hasn't been in the world before.
+    pub fn load(directory: &Path) -> io::Result<Option<PlayerData>> {
+        load_toml(&directory.join(PLAYER_FILE))
+    }
+
+    pub fn save(&self, directory: &Path) -> io::Result<()> {
+        save_toml(&directory.join(PLAYER_FILE), self)
+    }
+
+    /// The saved hotbar, or the default one for players saved without one.
+    pub fn get_hotbar(&self) -> Hotbar {
+        Hotbar::load(&self.hotbar, self.selected_slot)
+    }
+
+    /// A player standing where this one was saved.
+    pub fn get_player(&self) -> Player {
+        let mut player = Player::new(self.position.into());
+        player.mode = self.mode;
+        player
+    }
+}
+
+fn load_toml<T: serde::de::DeserializeOwned>(path: &Path) -> io::Result<Option<T>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    toml::from_str(&text)
+        .map(Some)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid {}: {}", path.display(), error)))
+}
+
+/// Writes to a temporary file first and moves it over the old one, so a crash while saving can't
+/// leave a half written file behind.
+fn save_toml<T: serde::Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let text = toml::to_string(value).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temporary = path.with_extension("toml.tmp");
+    fs::write(&temporary, text)?;
+    fs::rename(&temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{block_types, test_util::TestDirectory};
+
+    use super::*;
+
+    #[test]
+    fn missing_files_are_none() {
+        let directory = TestDirectory::new("level_missing");
+        assert_eq!(Level::load(directory.path()).unwrap(), None);
+        assert_eq!(PlayerData::load(directory.path()).unwrap(), None);
+        // Loading doesn't create anything
+        assert!(!directory.path().exists());
+    }
+
+    #[test]
+    fn level_round_trips() {
+        let directory = TestDirectory::new("level_round_trip");
+        let level = Level::new(1234, 18000, Point3::new(0.5, 64.0, -3.25));
+        level.save(directory.path()).unwrap();
+        assert_eq!(Level::load(directory.path()).unwrap(), Some(level.clone()));
+
+        // Saving again replaces the old file
+        let later = Level { time: 20, ..level };
+        later.save(directory.path()).unwrap();
+        assert_eq!(Level::load(directory.path()).unwrap(), Some(later));
+        assert!(!directory.path().join("level.toml.tmp").exists());
+    }
+
+    #[test]
+    fn player_round_trips() {
+        let directory = TestDirectory::new("level_player");
+        let mut player = Player::new(Point3::new(3.0, -7.5, 12.0));
+        player.mode = MoveMode::Fly;
+        let mut hotbar = Hotbar::default();
+        hotbar.select(6);
+        let data = PlayerData::new(&player, 1.25, -0.5, &hotbar);
+        data.save(directory.path()).unwrap();
+
+        let loaded = PlayerData::load(directory.path()).unwrap().unwrap();
+        assert_eq!(loaded, data);
+        let player = loaded.get_player();
+        assert_eq!(player.position, Point3::new(3.0, -7.5, 12.0));
+        assert_eq!(player.mode, MoveMode::Fly);
+        block_types::init_for_tests();
+        assert_eq!(loaded.get_hotbar(), hotbar);
+    }
+
+    #[test]
+    fn players_saved_without_a_hotbar_get_the_default() {
+        let directory = TestDirectory::new("level_old_player");
+        fs::create_dir_all(directory.path()).unwrap();
+        fs::write(directory.path().join(PLAYER_FILE), "position = [0.0, 1.0, 2.0]\nyaw = 0.0\npitch = 0.0\nmode = \"walk\"\nselected_block = 3\n").unwrap();
+        let loaded = PlayerData::load(directory.path()).unwrap().unwrap();
+        assert_eq!(loaded.get_hotbar(), Hotbar::default());
+    }
+
+    #[test]
+    fn newer_and_broken_levels_are_rejected() {
+        let directory = TestDirectory::new("level_rejected");
+        let newer = Level { version: FORMAT_VERSION + 1, ..Level::new(0, 0, Point3::new(0.0, 0.0, 0.0)) };
+        newer.save(directory.path()).unwrap();
+        assert_eq!(Level::load(directory.path()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        fs::write(directory.path().join(LEVEL_FILE), "seed = \"not a number\"").unwrap();
+        assert_eq!(Level::load(directory.path()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
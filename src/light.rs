@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+
+use cgmath::Point3;
+
+use crate::{block_types, chunk::{self, Chunk}, chunk_manager::ChunkManager};
+
+pub const MAX_LIGHT: u8 = 15;
+
+/// Red, green and blue block light levels, each between 0 and `MAX_LIGHT`.
+pub type Light = [u8; 3];
+
+// The light map covers the chunk plus a one block border, so faces on the
+// edge of a chunk can be lit by light coming from the neighbouring chunk.
+const MAP_SIZE: usize = chunk::SIZE as usize + 2;
+
+// Light travels at most MAX_LIGHT blocks, so only emitters this close to the
+// chunk can reach it.
+const MARGIN: i32 = MAX_LIGHT as i32;
+const REGION_SIZE: usize = chunk::SIZE as usize + 2 * MARGIN as usize;
+
+#[derive(PartialEq, Clone)]
+pub struct LightMap {
+    values: Vec<Light>,
+}
+
+impl LightMap {
+    pub fn new() -> LightMap {
+        LightMap {
+            values: vec![[0; 3]; MAP_SIZE * MAP_SIZE * MAP_SIZE],
+        }
+    }
+
+    /// Returns the light at a local chunk position, which may be one block outside of the chunk.
+    pub fn get(&self, pos: Point3<i16>) -> Light {
+        match map_index(pos) {
+            Some(index) => self.values[index],
+            None => [0; 3],
+        }
+    }
+}
+
+fn map_index(pos: Point3<i16>) -> Option<usize> {
+    let (x, y, z) = (pos.x + 1, pos.y + 1, pos.z + 1);
+    if x < 0 || y < 0 || z < 0 || x >= MAP_SIZE as i16 || y >= MAP_SIZE as i16 || z >= MAP_SIZE as i16 {
+        return None;
+    }
+    Some((x as usize * MAP_SIZE + y as usize) * MAP_SIZE + z as usize)
+}
+
+fn region_index(x: usize, y: usize, z: usize) -> usize {
+    (x * REGION_SIZE + y) * REGION_SIZE + z
+}
+
+/// Flood fills the block light of every emitter near the chunk, one colour channel per component.
+pub fn flood(manager: &ChunkManager, chunk: &Chunk) -> LightMap {
+    let half = (chunk::SIZE / 2) as i32;
+    let region_min = chunk.position.map(|value| value - half - MARGIN);
+    let region_max = region_min.map(|value| value + REGION_SIZE as i32);
+
+    // Copy every block around the chunk into a flat region so the fill doesn't have to look chunks up
+    let mut blocks = vec![0; REGION_SIZE * REGION_SIZE * REGION_SIZE];
+    let mut light: Vec<Light> = vec![[0; 3]; blocks.len()];
+    let mut queue = VecDeque::new();
+    for other in &manager.chunks {
+        let other_min = other.position.map(|value| value - half);
+        if other_min.x >= region_max.x || other_min.y >= region_max.y || other_min.z >= region_max.z ||
+           other_min.x + (chunk::SIZE as i32) <= region_min.x ||
+           other_min.y + (chunk::SIZE as i32) <= region_min.y ||
+           other_min.z + (chunk::SIZE as i32) <= region_min.z {
+            continue;
+        }
+        for x in 0..chunk::SIZE {
+            for y in 0..chunk::SIZE {
+                for z in 0..chunk::SIZE {
+                    let rx = other_min.x + x as i32 - region_min.x;
+                    let ry = other_min.y + y as i32 - region_min.y;
+                    let rz = other_min.z + z as i32 - region_min.z;
+                    if rx < 0 || ry < 0 || rz < 0 || rx >= REGION_SIZE as i32 || ry >= REGION_SIZE as i32 || rz >= REGION_SIZE as i32 {
+                        continue;
+                    }
+                    let block = other.get_block(Point3::new(x, y, z));
+                    let index = region_index(rx as usize, ry as usize, rz as usize);
+                    blocks[index] = block;
+                    if block != 0 && block_types::get(block).is_emissive() {
+                        light[index] = block_types::get(block).get_emission();
+                        queue.push_back((rx as usize, ry as usize, rz as usize));
+                    }
+                }
+            }
+        }
+    }
+
+    if queue.is_empty() {
+        return LightMap::new();
+    }
+
+    while let Some((x, y, z)) = queue.pop_front() {
+        let current = light[region_index(x, y, z)];
+        if current.iter().all(|channel| *channel <= 1) {
+            continue;
+        }
+        let spread = current.map(|channel| channel.saturating_sub(1));
+        for (dx, dy, dz) in [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)] {
+            let (nx, ny, nz) = (x as i32 + dx, y as i32 + dy, z as i32 + dz);
+            if nx < 0 || ny < 0 || nz < 0 || nx >= REGION_SIZE as i32 || ny >= REGION_SIZE as i32 || nz >= REGION_SIZE as i32 {
+                continue;
+            }
+            let index = region_index(nx as usize, ny as usize, nz as usize);
+            if blocks[index] != 0 && block_types::get(blocks[index]).is_opaque() {
+                continue;
+            }
+            let old = light[index];
+            let new = [old[0].max(spread[0]), old[1].max(spread[1]), old[2].max(spread[2])];
+            if new != old {
+                light[index] = new;
+                queue.push_back((nx as usize, ny as usize, nz as usize));
+            }
+        }
+    }
+
+    let mut map = LightMap::new();
+    for x in 0..MAP_SIZE {
+        for y in 0..MAP_SIZE {
+            for z in 0..MAP_SIZE {
+                let offset = MARGIN as usize - 1;
+                map.values[(x * MAP_SIZE + y) * MAP_SIZE + z] = light[region_index(x + offset, y + offset, z + offset)];
+            }
+        }
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAMP: u32 = 5;
+    const STONE: u32 = 3;
+
+    /// A manager holding the given chunks, flooded from the first one.
+    fn flood_first(chunks: Vec<Chunk>) -> LightMap {
+        block_types::init_for_tests();
+        let mut manager = ChunkManager::new();
+        for chunk in chunks {
+            manager.add_chunk(chunk);
+        }
+        flood(&manager, manager.get_chunk(0))
+    }
+
+    #[test]
+    fn channels_fall_off_separately() {
+        let mut chunk = Chunk::new_empty(Point3::new(0, 0, 0));
+        chunk.set_block(Point3::new(8, 8, 8), LAMP);
+        let map = flood_first(vec![chunk]);
+        assert_eq!(map.get(Point3::new(8, 8, 8)), [15, 13, 9]);
+        assert_eq!(map.get(Point3::new(12, 8, 8)), [11, 9, 5]);
+        // Light goes around corners, so it falls off with the number of steps rather than the distance
+        assert_eq!(map.get(Point3::new(11, 6, 10)), [8, 6, 2]);
+        // Blue runs out first
+        assert_eq!(map.get(Point3::new(8, 8, -1)), [6, 4, 0]);
+        assert_eq!(map.get(Point3::new(0, 0, 8)), [0, 0, 0]);
+    }
+
+    #[test]
+    fn opaque_blocks_stop_light() {
+        let mut chunk = Chunk::new_empty(Point3::new(0, 0, 0));
+        chunk.set_block(Point3::new(8, 8, 8), LAMP);
+        for (x, y, z) in [(7, 8, 8), (9, 8, 8), (8, 7, 8), (8, 9, 8), (8, 8, 7)] {
+            chunk.set_block(Point3::new(x, y, z), STONE);
+        }
+        let map = flood_first(vec![chunk]);
+        // The stone itself stays dark, and light only gets out of the one open side
+        assert_eq!(map.get(Point3::new(9, 8, 8)), [0, 0, 0]);
+        assert_eq!(map.get(Point3::new(8, 8, 9)), [14, 12, 8]);
+        assert_eq!(map.get(Point3::new(10, 8, 8)), [11, 9, 5]);
+        // Behind the lamp is two blocks away, but eight steps around the stone
+        assert_eq!(map.get(Point3::new(8, 8, 6)), [7, 5, 1]);
+    }
+
+    #[test]
+    fn light_crosses_chunk_borders() {
+        let mut neighbour = Chunk::new_empty(Point3::new(1, 0, 0));
+        // Ten blocks past the edge of the chunk being lit, so only found through the margin
+        neighbour.set_block(Point3::new(10, 8, 8), LAMP);
+        let map = flood_first(vec![Chunk::new_empty(Point3::new(0, 0, 0)), neighbour]);
+        // The border of the map, one block outside of the chunk
+        assert_eq!(map.get(Point3::new(16, 8, 8)), [5, 3, 0]);
+        assert_eq!(map.get(Point3::new(15, 8, 8)), [4, 2, 0]);
+        assert_eq!(map.get(Point3::new(14, 8, 8)), [3, 1, 0]);
+        assert_eq!(map.get(Point3::new(0, 8, 8)), [0, 0, 0]);
+    }
+}
@@ -1,42 +1,113 @@
-use std::iter;
+use std::{collections::{HashMap, HashSet}, iter, io, path::PathBuf, process, sync::mpsc, thread, time::{Duration, Instant}};
 
-use crate::render::chunk_buffers::ChunkBuffers;
+use crate::render::chunk_buffers::{ChunkBuffers, ChunkInstance, ChunkMesh};
 use crate::render::*;
-use cgmath::{Point3, Vector3};
-use chunk::Chunk;
+use cgmath::{MetricSpace, Point3};
 use chunk_manager::ChunkManager;
-use log::LevelFilter;
+use clap::Parser;
+use cli::Arguments;
+use debug_overlay::{DebugInfo, FrameTimes};
+use direction::Direction;
+use gamepad::Gamepad;
+use hotbar::Hotbar;
+use input::{Action, Binding, KeyBindings, ScrollDirection};
+use level::{Level, PlayerData};
+use menu::{Menu, MenuAction, Screen};
+use light::Light;
+use log::{debug, info, warn, LevelFilter};
 use once_cell::sync::OnceCell;
-use render::texture::Texture;
+use render::{fog::{Fog, FogMode}, frustum::Frustum, outline::Outline, sky::Sky, texture::Texture, ui::{Ui, UiRenderer}};
 use simple_logger::SimpleLogger;
 use wgpu::util::DeviceExt;
 use winit::{
     event::*,
     event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder, CursorGrabMode},
+    dpi::PhysicalSize,
+    window::{Window, WindowBuilder, CursorGrabMode, Fullscreen},
 };
 use noise::Perlin;
+use player::{MoveMode, Player};
+use raycaster::RayHit;
+use region::WorldStorage;
+use saves::Saves;
+use settings::SettingsFile;
+use timestep::FixedTimestep;
+use world_time::WorldTime;
 
 mod block_types;
 mod chunk;
 mod chunk_manager;
+mod cli;
+mod palette;
+mod debug_overlay;
 mod direction;
+mod light;
 mod render;
 mod math;
 mod camera;
+mod gamepad;
+mod hotbar;
+mod hud;
+mod input;
+mod level;
+mod menu;
+mod raycaster;
+mod region;
+mod player;
+mod saves;
+mod settings;
+mod start_menu;
+#[cfg(test)]
+mod test_util;
+mod timestep;
+mod world_time;
 
-pub const RENDER_DISTANCE: i32 = 2;
+pub const FOG_MODE: FogMode = FogMode::Linear;
+/// Fraction of the render distance at which the fog starts.
+pub const FOG_START: f32 = 0.6;
+/// How far away blocks can be targeted, in blocks.
+pub const REACH: f32 = 8.0;
+/// Where worlds are saved, relative to where the game is started. Each world has a directory of
+/// its own in here, with its chunks in a `region` directory inside of that.
+pub const SAVES_DIRECTORY: &str = "saves";
 pub static PERLIN: OnceCell<Perlin> = OnceCell::new();
 
+/// A chunk vertex packed into two words. The first holds the position relative to the chunk's
+/// origin (5 bits per axis), the face's `Direction` id (3 bits), which corner of the texture it
+/// uses (1 bit each for u and v) and the texture index (8 bits). The second holds the block light
+/// (4 bits per channel) and ambient occlusion (2 bits).
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
-    position: [f32; 3],
-    tex_coords: [f32; 2],
-    light: f32,
+    packed: u32,
+    light: u32,
 }
 
 impl Vertex {
+    pub fn new(position: [u8; 3], direction: Direction, uv: [u8; 2], texture: u32, ambient_occlusion: u8, block_light: Light) -> Vertex {
+        Vertex {
+            packed: position[0] as u32
+                | (position[1] as u32) << 5
+                | (position[2] as u32) << 10
+                | (direction.get_id() as u32) << 15
+                | (uv[0] as u32) << 18
+                | (uv[1] as u32) << 19
+                | texture << 20,
+            light: block_light[0] as u32
+                | (block_light[1] as u32) << 4
+                | (block_light[2] as u32) << 8
+                | (ambient_occlusion as u32) << 12,
+        }
+    }
+
+    pub fn get_position(&self) -> Point3<u8> {
+        Point3::new(
+            (self.packed & 31) as u8,
+            (self.packed >> 5 & 31) as u8,
+            (self.packed >> 10 & 31) as u8,
+        )
+    }
+
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         use std::mem;
         wgpu::VertexBufferLayout {
@@ -46,24 +117,77 @@ impl Vertex {
                 wgpu::VertexAttribute {
                     offset: 0,
                     shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
+                    format: wgpu::VertexFormat::Uint32,
                 },
                 wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    offset: mem::size_of::<u32>() as wgpu::BufferAddress,
                     shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x2,
-                },
-                wgpu::VertexAttribute {
-                    offset: (mem::size_of::<[f32; 3]>() + mem::size_of::<[f32; 2]>())
-                        as wgpu::BufferAddress,
-                    shader_location: 2,
-                    format: wgpu::VertexFormat::Float32,
+                    format: wgpu::VertexFormat::Uint32,
                 },
             ],
         }
     }
 }
 
+/// Chunk geometry is drawn three times with the same shader, once per `RenderType`.
+#[allow(clippy::too_many_arguments)]
+fn create_chunk_pipeline(
+    device: &wgpu::Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    format: wgpu::TextureFormat,
+    label: &str,
+    fragment_entry_point: &str,
+    blend: wgpu::BlendState,
+    depth_write_enabled: bool,
+) -> wgpu::RenderPipeline {
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[Vertex::desc(), ChunkInstance::desc()],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: shader,
+            entry_point: fragment_entry_point,
+            targets: &[Some(wgpu::ColorTargetState {
+                format,
+                blend: Some(blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+            polygon_mode: wgpu::PolygonMode::Fill,
+            // Requires Features::DEPTH_CLIP_CONTROL
+            unclipped_depth: false,
+            // Requires Features::CONSERVATIVE_RASTERIZATION
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: texture::Texture::DEPTH_FORMAT,
+            depth_write_enabled,
+            depth_compare: wgpu::CompareFunction::Less, // 1.
+            stencil: wgpu::StencilState::default(),     // 2.
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        // If the pipeline will be used with a multiview render pass, this
+        // indicates how many array layers the attachments will have.
+        multiview: None,
+    })
+}
+
 struct State {
     surface: wgpu::Surface,
     device: wgpu::Device,
@@ -71,27 +195,57 @@ struct State {
     config: wgpu::SurfaceConfiguration,
     size: winit::dpi::PhysicalSize<u32>,
     render_pipeline: wgpu::RenderPipeline,
+    cutout_pipeline: wgpu::RenderPipeline,
+    translucent_pipeline: wgpu::RenderPipeline,
     pre_render_pipeline: wgpu::RenderPipeline,
     chunk_manager: ChunkManager,
     chunk_buffers: ChunkBuffers,
     #[allow(dead_code)]
     diffuse_texture: texture::Texture,
     diffuse_bind_group: wgpu::BindGroup,
-    #[allow(dead_code)]
-    sky_diffuse_texture: texture::Texture,
-    sky_diffuse_bind_group: wgpu::BindGroup,
+    sky: Sky,
+    fog: Fog,
     camera: camera::Camera,
     camera_controller: camera::CameraController,
+    gamepad: Gamepad,
+    player: Player,
     camera_uniform: camera::CameraUniform,
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
     depth_texture: Texture,
+    world_time: WorldTime,
+    level: Level,
+    /// Where the world being played is saved.
+    world_directory: PathBuf,
+    commands: mpsc::Receiver<String>,
     tick: u64,
+    timestep: FixedTimestep,
     pause: bool,
+    menu: Menu,
+    /// Set by the menu's save and quit button, the event loop exits once it sees it.
+    quit: bool,
+    settings: SettingsFile,
+    /// The block the camera is looking at, if one is within reach.
+    target: Option<RayHit>,
+    outline: Outline,
+    outline_pipeline: wgpu::RenderPipeline,
+    /// Holds the block placed with the right mouse button.
+    hotbar: Hotbar,
+    ui: Ui,
+    ui_renderer: UiRenderer,
+    /// Whether the debug overlay is open.
+    debug: bool,
+    frame_times: FrameTimes,
 }
 
 impl State {
-    async fn new(window: &Window) -> Self {
+    async fn new(
+        window: &Window,
+        commands: mpsc::Receiver<String>,
+        level: Level,
+        world_directory: PathBuf,
+        settings: SettingsFile,
+    ) -> Self {
         let size = window.inner_size();
 
         // The instance is a handle to our GPU
@@ -106,11 +260,13 @@ impl State {
             })
             .await
             .unwrap();
+        // Chunks are drawn with one call per pass where these are available, see `ChunkBuffers`
+        let features = adapter.features() & (wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE);
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: None,
-                    features: wgpu::Features::empty(),
+                    features,
                     limits: wgpu::Limits::default(),
                 },
                 None, // Trace path
@@ -123,7 +279,7 @@ impl State {
             format: surface.get_supported_formats(&adapter)[0],
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: settings.get().get_present_mode(),
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
         };
         surface.configure(&device, &config);
@@ -139,14 +295,6 @@ impl State {
             "assets/textures/textures.png",
         );
 
-        let sky_diffuse_bytes = include_bytes!("assets/textures/sky.png");
-        let sky_diffuse_texture = texture::Texture::from_bytes(
-            &device,
-            &queue,
-            sky_diffuse_bytes,
-            "assets/textures/sky.png",
-        );
-
         let texture_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[
@@ -185,33 +333,32 @@ impl State {
             label: Some("diffuse_bind_group"),
         });
 
-        let sky_diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&sky_diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&sky_diffuse_texture.sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
+        // Resume where the player left off, or start at the world's spawn point
+        let player_data = PlayerData::load(&world_directory).unwrap_or_else(|error| {
+            warn!("Failed to load the player, starting at spawn: {}", error);
+            None
         });
+        let player = match &player_data {
+            Some(player_data) => player_data.get_player(),
+            None => Player::new(level.get_spawn()),
+        };
 
         let camera = camera::Camera {
-            pos: (0.0, 1.0, 2.0).into(),
+            pos: player.get_eye_position(),
             up: cgmath::Vector3::unit_y(),
             aspect: config.width as f32 / config.height as f32,
-            fovy: 70.0,
+            fovy: settings.get().fov,
             znear: 0.1,
-            zfar: 100.0,
-            pitch: 0.0,
-            yaw: 0.0,
+            zfar: lod::get_far_plane(settings.get().render_distance, &settings.get().lod_distances),
+            pitch: player_data.as_ref().map_or(0.0, |player_data| player_data.pitch),
+            yaw: player_data.as_ref().map_or(0.0, |player_data| player_data.yaw),
             resolution: window.inner_size(),
         };
-        let camera_controller = camera::CameraController::new(0.2);
+        let mut camera_controller = camera::CameraController::new();
+        camera_controller.sensitivity = settings.get().mouse_sensitivity;
+        for (binding, actions) in settings.get().key_bindings.get_conflicts() {
+            warn!("{} is bound to more than one action: {:?}", binding, actions);
+        }
 
         let mut camera_uniform = camera::CameraUniform::new();
         camera_uniform.update_view_proj(&camera);
@@ -226,7 +373,7 @@ impl State {
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -246,28 +393,81 @@ impl State {
             label: Some("camera_bind_group"),
         });
 
+        let world_time = WorldTime::new(level.time);
+        let fog = Fog::from_render_distance(FOG_MODE, lod::get_view_distance(settings.get().render_distance, &settings.get().lod_distances), FOG_START);
+        let sky = Sky::new(&device, &world_time, &fog);
+
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Shader"),
             source: wgpu::ShaderSource::Wgsl(include_str!("assets/shaders/shader.wgsl").into()),
         });
 
+        let sky_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sky Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("assets/shaders/sky.wgsl").into()),
+        });
+
+        let outline_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Outline Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("assets/shaders/outline.wgsl").into()),
+        });
+
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout],
+                bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout, &sky.bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let sky_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Sky Render Pipeline Layout"),
+                bind_group_layouts: &[&camera_bind_group_layout, &sky.bind_group_layout],
                 push_constant_ranges: &[],
             });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
+        let render_pipeline = create_chunk_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            config.format,
+            "Render Pipeline",
+            "fs_main",
+            wgpu::BlendState::REPLACE,
+            true,
+        );
+        let cutout_pipeline = create_chunk_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            config.format,
+            "Cutout Render Pipeline",
+            "fs_cutout",
+            wgpu::BlendState::REPLACE,
+            true,
+        );
+        // Translucent geometry is blended over what's behind it, so it mustn't hide anything drawn after it
+        let translucent_pipeline = create_chunk_pipeline(
+            &device,
+            &render_pipeline_layout,
+            &shader,
+            config.format,
+            "Translucent Render Pipeline",
+            "fs_main",
+            wgpu::BlendState::ALPHA_BLENDING,
+            false,
+        );
+
+        let pre_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky Render Pipeline"),
+            layout: Some(&sky_pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: &sky_shader,
                 entry_point: "vs_main",
-                buffers: &[Vertex::desc()],
+                buffers: &[Sky::desc()],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: &sky_shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
                     format: config.format,
@@ -282,18 +482,15 @@ impl State {
                 topology: wgpu::PrimitiveTopology::TriangleList,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                cull_mode: None,
                 polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
                 unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
                 conservative: false,
             },
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: texture::Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less, // 1.
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always, // 1.
                 stencil: wgpu::StencilState::default(),     // 2.
                 bias: wgpu::DepthBiasState::default(),
             }),
@@ -302,36 +499,39 @@ impl State {
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
-            // If the pipeline will be used with a multiview render pass, this
-            // indicates how many array layers the attachments will have.
             multiview: None,
         });
 
-        let pre_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Sky Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
+        let outline_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Outline Render Pipeline Layout"),
+                bind_group_layouts: &[&camera_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        // Lines around the targeted block, tested against but not written to the depth buffer
+        let outline_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Outline Render Pipeline"),
+            layout: Some(&outline_pipeline_layout),
             vertex: wgpu::VertexState {
-                module: &shader,
+                module: &outline_shader,
                 entry_point: "vs_main",
-                buffers: &[Vertex::desc()],
+                buffers: &[Outline::desc()],
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: &outline_shader,
                 entry_point: "fs_main",
                 targets: &[Some(wgpu::ColorTargetState {
                     format: config.format,
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent::REPLACE,
-                        alpha: wgpu::BlendComponent::REPLACE,
-                    }),
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
+                topology: wgpu::PrimitiveTopology::LineList,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
+                cull_mode: None,
                 polygon_mode: wgpu::PolygonMode::Fill,
                 unclipped_depth: false,
                 conservative: false,
@@ -339,8 +539,8 @@ impl State {
             depth_stencil: Some(wgpu::DepthStencilState {
                 format: texture::Texture::DEPTH_FORMAT,
                 depth_write_enabled: false,
-                depth_compare: wgpu::CompareFunction::Less, // 1.
-                stencil: wgpu::StencilState::default(),     // 2.
+                depth_compare: wgpu::CompareFunction::LessEqual,
+                stencil: wgpu::StencilState::default(),
                 bias: wgpu::DepthBiasState::default(),
             }),
             multisample: wgpu::MultisampleState {
@@ -350,43 +550,16 @@ impl State {
             },
             multiview: None,
         });
+        let outline = Outline::new(&device);
+        let ui = Ui::new(size, settings.get().ui_scale);
+        let ui_renderer = UiRenderer::new(&device, &queue, config.format, &diffuse_texture);
 
-        let mut chunk_manager = ChunkManager::new();
-        //chunk_manager.add_chunk(Chunk::new_filled(Vector3::new(0, 0, 0), 1));
-        //chunk_manager.add_chunk(Chunk::new_layered(Vector3::new(0, 1, 0), 1, 2, 3));
-        //chunk_manager.add_chunk(Chunk::new_filled(Vector3::new(-1, 0, 0), 3));
-        //chunk_manager.add_chunk(Chunk::new_filled(Vector3::new(-1, 1, 0), 4));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(0, 0, 0), 1));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(0, 1, 0), 2));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(-1, 0, 0), 3));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(-1, 1, 0), 4));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(1, 0, 0), 1));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(1, 1, 0), 2));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(-1, 2, 0), 3));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(0, 2, 0), 4));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(1, 2, 0), 1));
-
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(0, 0, 1), 1));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(0, 1, 1), 2));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(-1, 0, 1), 3));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(-1, 1, 1), 4));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(1, 0, 1), 1));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(1, 1, 1), 2));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(-1, 2, 1), 3));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(0, 2, 1), 4));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(1, 2, 1), 1));
-
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(0, 0, 2), 1));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(0, 1, 2), 2));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(-1, 0, 2), 3));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(-1, 1, 2), 4));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(1, 0, 2), 1));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(1, 1, 2), 2));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(-1, 2, 2), 3));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(0, 2, 2), 4));
-        chunk_manager.add_chunk(Chunk::new_perlin(Point3::new(1, 2, 2), 1));
-
-        let chunk_buffers = ChunkBuffers::new(&device, &chunk_manager);
+        let mut chunk_manager = ChunkManager::with_storage(WorldStorage::new(world_directory.join("region")), level.seed);
+        chunk_manager.set_render_distance(settings.get().render_distance, &settings.get().lod_distances);
+        let mut chunk_buffers = ChunkBuffers::new(&device);
+        // Starts with the player's chunk, the rest load over the first ticks
+        chunk_manager.update(&player.get_eye_position(), &mut chunk_buffers);
+        chunk_manager.remesh(&mut chunk_buffers, &device, &queue);
 
         Self {
             surface,
@@ -395,21 +568,41 @@ impl State {
             config,
             size,
             render_pipeline,
+            cutout_pipeline,
+            translucent_pipeline,
             pre_render_pipeline,
             chunk_manager,
             chunk_buffers,
             diffuse_texture,
             diffuse_bind_group,
-            sky_diffuse_texture,
-            sky_diffuse_bind_group,
+            sky,
+            fog,
             camera,
             camera_controller,
+            gamepad: Gamepad::open(),
+            player,
             camera_buffer,
             camera_bind_group,
             camera_uniform,
             depth_texture,
+            world_time,
+            level,
+            world_directory,
+            commands,
             tick: 0,
-            pause: false
+            timestep: FixedTimestep::new(timestep::TICKS_PER_SECOND),
+            pause: false,
+            menu: Menu::new(),
+            quit: false,
+            settings,
+            target: None,
+            outline,
+            outline_pipeline,
+            hotbar: player_data.as_ref().map(PlayerData::get_hotbar).unwrap_or_default(),
+            ui,
+            ui_renderer,
+            debug: false,
+            frame_times: FrameTimes::new(),
         }
     }
 
@@ -422,19 +615,260 @@ impl State {
             self.surface.configure(&self.device, &self.config);
 
             self.camera.aspect = self.config.width as f32 / self.config.height as f32;
+            self.ui.resize(new_size);
         }
         self.depth_texture =
             texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
     }
 
     fn input(&mut self, device_event: Option<&DeviceEvent>, window_event: Option<&WindowEvent>) -> bool {
-        self.camera_controller.process_events(device_event, window_event)
+        let (binding, state) = match window_event {
+            Some(WindowEvent::MouseInput { state, button, .. }) => (Binding::Mouse(*button), *state),
+            Some(WindowEvent::KeyboardInput { input: KeyboardInput { state, virtual_keycode: Some(key), .. }, .. }) => (Binding::Key(*key), *state),
+            Some(WindowEvent::MouseWheel { delta, .. }) => {
+                let y = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32,
+                };
+                if y == 0.0 {
+                    return false;
+                }
+                let binding = Binding::Scroll(if y > 0.0 { ScrollDirection::Up } else { ScrollDirection::Down });
+                self.handle_binding(binding, true);
+                return self.handle_binding(binding, false);
+            }
+            Some(WindowEvent::CursorMoved { position, .. }) => {
+                let scale = self.ui.get_scale();
+                self.menu.set_cursor(position.x as f32 / scale, position.y as f32 / scale);
+                return false;
+            }
+            // The cursor is free to move around the menu without turning the camera
+            _ if self.pause => return false,
+            _ => return self.camera_controller.process_events(device_event),
+        };
+        self.handle_binding(binding, state == ElementState::Pressed)
+    }
+
+    /// Presses or releases every action bound to a key or button, returns whether there were any.
+    fn handle_binding(&mut self, binding: Binding, pressed: bool) -> bool {
+        if self.pause {
+            if self.menu.get_rebinding().is_some() {
+                // Escape gives up on rebinding rather than binding the action to it
+                if pressed {
+                    if binding == Binding::Key(VirtualKeyCode::Escape) {
+                        self.menu.set_rebinding(None);
+                    } else if let Some(key_bindings) = self.menu.rebind(&self.settings.get().key_bindings, binding) {
+                        self.settings.change(|settings| settings.key_bindings = key_bindings.clone());
+                        self.save_settings();
+                    }
+                }
+                return true;
+            }
+            if binding == Binding::Mouse(MouseButton::Left) {
+                if pressed {
+                    let buttons = self.menu.layout(self.ui.get_size(), self.settings.get());
+                    if let Some(action) = self.menu.click(&buttons) {
+                        self.run_menu_action(action);
+                    }
+                }
+                return true;
+            }
+        }
+        let actions = self.settings.get().key_bindings.get_actions(binding);
+        for action in &actions {
+            if self.pause && !menu::passes_while_paused(*action, pressed) {
+                continue;
+            }
+            if self.camera_controller.process_action(*action, pressed) {
+                self.run_action(*action);
+            }
+        }
+        !actions.is_empty()
+    }
+
+    /// Does whatever an action does the moment its key is pressed. Movement happens while keys are held instead.
+    fn run_action(&mut self, action: Action) {
+        match action {
+            // Escape backs out of the menu one screen at a time
+            Action::TogglePause if self.pause => self.pause = self.menu.back(),
+            Action::TogglePause => {
+                self.pause = true;
+                self.menu.open(Screen::Pause);
+            }
+            Action::ToggleDebug => self.debug = !self.debug,
+            Action::Break if !self.pause => self.break_block(),
+            Action::Place if !self.pause => self.place_block(),
+            Action::ToggleFly | Action::ToggleNoclip => {
+                self.player.toggle_mode(if action == Action::ToggleFly { MoveMode::Fly } else { MoveMode::Noclip });
+                info!("Movement mode {:?}", self.player.mode);
+            }
+            Action::HotbarNext => {
+                self.hotbar.scroll(1);
+                self.log_selected_block();
+            }
+            Action::HotbarPrevious => {
+                self.hotbar.scroll(-1);
+                self.log_selected_block();
+            }
+            _ => if let Some(slot) = action.get_hotbar_slot() {
+                self.hotbar.select(slot);
+                self.log_selected_block();
+            },
+        }
+    }
+
+    fn run_menu_action(&mut self, action: MenuAction) {
+        match action {
+            MenuAction::Resume => self.pause = false,
+            MenuAction::Open(screen) => self.menu.open(screen),
+            MenuAction::SaveAndQuit => {
+                self.save();
+                self.quit = true;
+            }
+            MenuAction::RenderDistance(step) => {
+                let render_distance = settings::step(self.settings.get().render_distance, step, &settings::RENDER_DISTANCES);
+                self.settings.change(|settings| settings.render_distance = render_distance);
+                self.apply_settings();
+            }
+            MenuAction::Fov(step) => {
+                let fov = settings::step(self.settings.get().fov, step, &settings::FOVS);
+                self.settings.change(|settings| settings.fov = fov);
+                self.apply_settings();
+            }
+            MenuAction::MouseSensitivity(step) => {
+                let sensitivity = settings::step(self.settings.get().mouse_sensitivity, step, &settings::MOUSE_SENSITIVITIES);
+                // Steps of a tenth add up to values like 1.2000001 otherwise
+                let sensitivity = (sensitivity * 10.0).round() / 10.0;
+                self.settings.change(|settings| settings.mouse_sensitivity = sensitivity);
+                self.apply_settings();
+            }
+            MenuAction::ToggleVsync => {
+                let vsync = !self.settings.get().vsync;
+                self.settings.change(|settings| settings.vsync = vsync);
+                self.apply_settings();
+            }
+            MenuAction::Rebind(action) => self.menu.set_rebinding(Some(action)),
+            MenuAction::ResetControls => {
+                self.settings.change(|settings| settings.key_bindings = KeyBindings::default());
+                self.save_settings();
+            }
+        }
+    }
+
+    /// Puts changed settings into effect and saves them.
+    fn apply_settings(&mut self) {
+        let settings = self.settings.get();
+        self.camera.fovy = settings.fov;
+        self.camera.zfar = lod::get_far_plane(settings.render_distance, &settings.lod_distances);
+        self.camera_controller.sensitivity = settings.mouse_sensitivity;
+        self.chunk_manager.set_render_distance(settings.render_distance, &settings.lod_distances);
+        self.fog = Fog::from_render_distance(FOG_MODE, lod::get_view_distance(settings.render_distance, &settings.lod_distances), FOG_START);
+        let present_mode = settings.get_present_mode();
+        if self.config.present_mode != present_mode {
+            self.config.present_mode = present_mode;
+            self.surface.configure(&self.device, &self.config);
+        }
+        self.save_settings();
+    }
+
+    fn save_settings(&self) {
+        if let Err(error) = self.settings.save() {
+            warn!("{}", error);
+        }
+    }
+
+    /// Saves the chunks, the level and the player, for when the game closes.
+    fn save(&mut self) {
+        self.chunk_manager.save_all();
+        self.level.time = self.world_time.get_time();
+        if let Err(error) = self.level.save(&self.world_directory) {
+            warn!("Failed to save the level: {}", error);
+        }
+        let player_data = PlayerData::new(&self.player, self.camera.yaw, self.camera.pitch, &self.hotbar);
+        if let Err(error) = player_data.save(&self.world_directory) {
+            warn!("Failed to save the player: {}", error);
+        }
+    }
+
+    fn log_selected_block(&self) {
+        info!("Selected {}", block_types::get(self.hotbar.get_selected_block()).name);
+    }
+
+    fn break_block(&mut self) {
+        if let Some(hit) = self.target {
+            self.chunk_manager.edit_block(hit.position, 0);
+        }
+    }
+
+    /// Places the selected block against the targeted face, replacing air or fluids but never inside of the player.
+    fn place_block(&mut self) {
+        let position = match self.target {
+            Some(hit) => hit.get_adjacent(),
+            None => return,
+        };
+        let current = self.chunk_manager.get_block(position);
+        if current != 0 && block_types::get(current).get_fluid().is_none() {
+            return;
+        }
+        let aabb = self.player.get_aabb();
+        let block = position.map(|value| value as f32);
+        let overlaps = (0..3).all(|axis| block[axis] + 0.5 > aabb.min[axis] && block[axis] - 0.5 < aabb.max[axis]);
+        let selected = self.hotbar.get_selected_block();
+        if overlaps && self.player.mode != MoveMode::Noclip && block_types::get(selected).is_solid() {
+            return;
+        }
+        self.chunk_manager.edit_block(position, selected);
     }
 
-    fn update(&mut self) {
+    /// Advances the simulation by one fixed length tick.
+    fn tick(&mut self) {
         self.tick += 1;
 
-        self.camera_controller.update_camera(&mut self.camera);
+        while let Ok(command) = self.commands.try_recv() {
+            match self.world_time.execute(&command) {
+                Ok(message) => info!("{}", message),
+                Err(message) => warn!("{}", message),
+            }
+        }
+
+        self.world_time.tick();
+
+        // Hold the player still until the chunk it is in has loaded, so it doesn't fall through missing terrain
+        let feet = self.player.position.map(|value| value.round() as i32);
+        if self.player.mode == MoveMode::Noclip || self.chunk_manager.get_pos_chunk(feet).is_some() {
+            let chunk_manager = &self.chunk_manager;
+            let input = self.camera_controller.get_input();
+            self.player.tick(&input, self.camera.yaw, self.camera.pitch, |pos| block_types::get(chunk_manager.get_block(pos)).is_solid());
+        } else {
+            self.player.previous_position = self.player.position;
+        }
+
+        if (self.tick % 5) == 0 {
+            let player_pos = self.player.get_eye_position();
+            self.chunk_manager.update(&player_pos, &mut self.chunk_buffers);
+        }
+        self.chunk_manager.remesh(&mut self.chunk_buffers, &self.device, &self.queue);
+    }
+
+    /// Runs as many ticks as the time since the last frame calls for, then places the camera
+    /// between the last two ticks for rendering.
+    fn update(&mut self, frame_time: Duration) {
+        self.frame_times.push(frame_time);
+        for (button, pressed) in self.gamepad.poll() {
+            self.handle_binding(Binding::Gamepad(button), pressed);
+        }
+        if self.pause {
+            // The world stands still behind the menu, sticks included
+            self.camera_controller.set_analog((0.0, 0.0), (0.0, 0.0));
+        } else {
+            self.camera_controller.set_analog(self.gamepad.get_movement(), self.gamepad.get_look());
+            for _ in 0..self.timestep.advance(frame_time) {
+                self.tick();
+            }
+        }
+
+        self.camera_controller.update_camera(&mut self.camera, frame_time.as_secs_f32());
+        self.camera.pos = self.player.get_interpolated_eye_position(self.timestep.get_alpha());
         self.camera_uniform.update_view_proj(&self.camera);
         self.queue.write_buffer(
             &self.camera_buffer,
@@ -442,28 +876,115 @@ impl State {
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
 
-        if (self.tick % 5) == 0 {
-            self.chunk_manager.update(&self.camera.pos, &mut self.chunk_buffers, &self.device);
-        }
-
-        /*match raycaster::block_ray(&self.chunk_manager, self.camera.eye, self.camera.target, 0.1, 100.0) {
-            Some(hit) => {
-                self.chunk_manager.set_block(math::pointi32(hit.position), 0);
-                let break_chunk_index = self.chunk_manager.get_pos_index(math::to_vector(math::pointi32(hit.position)));
-                let break_chunk = self.chunk_manager.get_pos_chunk(math::pointi32(hit.position));
-                if break_chunk_index.is_some() && break_chunk.is_some() {
-                    self.chunk_buffers.update_chunk(
-                        &self.device,
-                        break_chunk_index.unwrap(),
-                        break_chunk.unwrap(),
-                    );
+        // Swap to the fluid's fog while the camera is inside of one
+        let camera_block = self.chunk_manager.get_block(Point3::new(
+            self.camera.pos.x.round() as i32,
+            self.camera.pos.y.round() as i32,
+            self.camera.pos.z.round() as i32,
+        ));
+        let fog = match block_types::get(camera_block).get_fluid() {
+            Some(fluid) => Fog::from_fluid(fluid),
+            None => self.fog,
+        };
+        self.sky.update(&self.queue, &self.world_time, &fog);
+
+        let target = raycaster::block_ray(&self.chunk_manager, self.camera.pos, self.camera.get_direction(), REACH);
+        if target.map(|hit| hit.position) != self.target.map(|hit| hit.position) {
+            debug!("Looking at {:?}", target);
+        }
+        self.target = target;
+        self.outline.update(&self.queue, target.map(|hit| hit.position));
+    }
+
+    fn get_camera_chunk(&self) -> Point3<i32> {
+        math::get_chunk_position(Point3::new(
+            self.camera.pos.x.round() as i32,
+            self.camera.pos.y.round() as i32,
+            self.camera.pos.z.round() as i32,
+        ))
+    }
+
+    /// Chunk positions that can be seen from the camera through the chunks' visibility graph,
+    /// or `None` if the camera is outside of the loaded chunks.
+    fn find_visible_chunks(&self) -> Option<HashSet<Point3<i32>>> {
+        let meshes: HashMap<Point3<i32>, &ChunkMesh> = self.chunk_manager.chunks.iter()
+            .zip(self.chunk_buffers.get_buffers())
+            .map(|(chunk, mesh)| (chunk.get_chunk_position(), mesh))
+            .collect();
+        visibility::find_visible(self.get_camera_chunk(), |position| meshes.get(&position).map(|mesh| mesh.visibility))
+    }
+
+    /// Indices of the chunk meshes that pass frustum and visibility culling.
+    fn find_drawn_chunks(&self) -> Vec<usize> {
+        let frustum = Frustum::from_matrix(self.camera.build_view_projection_matrix());
+        let visible = self.find_visible_chunks();
+        let mut drawn = Vec::new();
+        for (index, mesh) in self.chunk_buffers.get_buffers().iter().enumerate() {
+            if mesh.is_empty() || !frustum.intersects_aabb(&mesh.aabb) {
+                continue;
+            }
+            if let Some(visible) = &visible {
+                if !visible.contains(&self.chunk_manager.get_chunk(index).get_chunk_position()) {
+                    continue;
                 }
-            },
-            None => {},
-        };*/
+            }
+            drawn.push(index);
+        }
+        drawn
+    }
+
+    fn get_debug_info(&self) -> DebugInfo {
+        let camera_block = self.camera.pos.map(|value| value.round() as i32);
+        DebugInfo {
+            position: self.camera.pos,
+            yaw: self.camera.yaw,
+            pitch: self.camera.pitch,
+            facing: Direction::get_facing(self.camera.get_direction()),
+            loaded_chunks: self.chunk_manager.get_chunk_count(),
+            pending_loads: self.chunk_manager.get_pending_load_count(),
+            pending_remeshes: self.chunk_manager.get_pending_remesh_count(),
+            meshes: self.chunk_buffers.get_stats(),
+            target: self.target.map(|hit| {
+                let block = block_types::get(self.chunk_manager.get_block(hit.position));
+                (block.name.clone(), hit.position)
+            }),
+            light: self.chunk_manager.get_light(camera_block),
+            generator: self.level.generator.clone(),
+            seed: self.level.seed,
+        }
+    }
+
+    /// Lays out this frame's UI.
+    fn build_ui(&mut self) {
+        self.ui.clear();
+        hud::draw(&mut self.ui, &self.hotbar);
+        if self.debug {
+            let info = self.get_debug_info();
+            debug_overlay::draw(&mut self.ui, &info, &self.frame_times);
+        }
+        if self.pause {
+            self.menu.draw(&mut self.ui, self.settings.get());
+        }
     }
 
     fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        // Quads within a chunk are sorted too, but only for the chunk the camera is in where it matters most
+        let camera_chunk = self.get_camera_chunk();
+        if let Some(index) = self.chunk_manager.chunks.iter().position(|chunk| chunk.get_chunk_position() == camera_chunk) {
+            self.chunk_buffers.sort_translucent(&self.queue, index, self.camera.pos);
+        }
+
+        let drawn = self.find_drawn_chunks();
+        // Furthest chunks first, so closer translucent geometry blends over them
+        let meshes = self.chunk_buffers.get_buffers();
+        let mut translucent_order = drawn.clone();
+        translucent_order.sort_by(|a, b| {
+            let a = self.camera.pos.distance2(meshes[*a].get_center());
+            let b = self.camera.pos.distance2(meshes[*b].get_center());
+            b.total_cmp(&a)
+        });
+        let draws = self.chunk_buffers.prepare_draws(&self.device, &self.queue, &drawn, &translucent_order);
+
         let output = self.surface.get_current_texture()?;
         let view = output
             .texture
@@ -476,23 +997,13 @@ impl State {
             });
 
         {
-            let (sky0, sky1, sky2) = sky::build(
-                &self.device,
-                Vector3::new(self.camera.pos.x, self.camera.pos.y, self.camera.pos.z),
-            );
-
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.3,
-                            g: 0.3,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(self.sky.uniform.get_fog_color()),
                         store: true,
                     },
                 })],
@@ -507,22 +1018,34 @@ impl State {
             });
 
             render_pass.set_pipeline(&self.pre_render_pipeline);
-            render_pass.set_bind_group(0, &self.sky_diffuse_bind_group, &[]);
+            render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.sky.bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.sky.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(self.sky.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            render_pass.draw_indexed(0..self.sky.index_count, 0, 0..1);
+
+            render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
             render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, sky0.slice(..));
-            render_pass.set_index_buffer(sky1.slice(..), wgpu::IndexFormat::Uint32);
-            render_pass.draw_indexed(0..sky2, 0, 0..1);
+            render_pass.set_bind_group(2, &self.sky.bind_group, &[]);
 
             render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
+            self.chunk_buffers.draw(&mut render_pass, &draws.opaque);
+            render_pass.set_pipeline(&self.cutout_pipeline);
+            self.chunk_buffers.draw(&mut render_pass, &draws.cutout);
+            render_pass.set_pipeline(&self.translucent_pipeline);
+            self.chunk_buffers.draw(&mut render_pass, &draws.translucent);
 
-            for buffers in self.chunk_buffers.get_buffers() {
-                render_pass.set_vertex_buffer(0, buffers.0.slice(..));
-                render_pass.set_index_buffer(buffers.1.slice(..), wgpu::IndexFormat::Uint32);
-                render_pass.draw_indexed(0..buffers.2, 0, 0..1);
+            if self.outline.is_visible() {
+                render_pass.set_pipeline(&self.outline_pipeline);
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, self.outline.vertex_buffer.slice(..));
+                render_pass.draw(0..self.outline.vertex_count, 0..1);
             }
         }
 
+        self.build_ui();
+        self.ui_renderer.draw(&self.device, &self.queue, &mut encoder, &view, &self.ui);
+
         self.queue.submit(iter::once(encoder.finish()));
         output.present();
 
@@ -531,21 +1054,87 @@ impl State {
 }
 
 fn main() {
+    let arguments = Arguments::parse();
+    // Nothing is logged yet, since the log level is one of the settings
+    let mut settings = SettingsFile::load(arguments.settings.clone()).unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        process::exit(2);
+    });
+    if let Err(error) = settings.override_for_run(|settings| arguments.apply(settings)) {
+        eprintln!("Invalid command line: {}", error);
+        process::exit(2);
+    }
+
     SimpleLogger::new()
             .with_module_level("wgpu_hal", LevelFilter::Error)
             .with_module_level("wgpu_core", LevelFilter::Error)
             .with_module_level("naga", LevelFilter::Error)
             .with_module_level("winit", LevelFilter::Error)
-            .with_level(LevelFilter::Trace).init().unwrap();
+            .with_level(settings.get().log_level).init().unwrap();
     
-    PERLIN.set(Perlin::new(0)).expect("Failed to initialize perlin noise!");
+    let saves = Saves::new(SAVES_DIRECTORY);
+    let name = match arguments.world {
+        Some(name) => name,
+        None => match start_menu::run(&saves, &mut io::stdin().lock(), &mut io::stdout()).expect("Failed to list the worlds") {
+            Some(name) => name,
+            None => return,
+        },
+    };
+    // A world that can't be read is left alone rather than replaced by a new one
+    let level = match saves.load(&name) {
+        Ok(level) => {
+            if let Some(seed) = arguments.seed.filter(|seed| *seed != level.seed) {
+                eprintln!("The world {} already exists with seed {}, --seed {} only applies to new worlds", name, level.seed, seed);
+                process::exit(2);
+            }
+            level
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            let seed = arguments.seed.unwrap_or_else(rand::random);
+            info!("Creating a new world named {} with seed {}", name, seed);
+            saves.create(&name, seed, level::GENERATOR).unwrap_or_else(|error| {
+                eprintln!("Could not create the world {}: {}", name, error);
+                process::exit(1);
+            })
+        }
+        // A bad name from --world is a mistake on the command line, anything else is a broken save
+        Err(error) if error.kind() == io::ErrorKind::InvalidInput => {
+            eprintln!("Invalid world name: {}", error);
+            process::exit(2);
+        }
+        Err(error) => {
+            eprintln!("Could not load the world {}: {}", name, error);
+            process::exit(1);
+        }
+    };
+    if level.generator != level::GENERATOR {
+        warn!("Unknown generator {}, new chunks are generated with {}", level.generator, level::GENERATOR);
+    }
+    PERLIN.set(Perlin::new(level.seed)).expect("Failed to initialize perlin noise!");
     block_types::init();
     
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    let mut window_builder = WindowBuilder::new()
+            .with_inner_size(PhysicalSize::new(settings.get().window_width, settings.get().window_height));
+    if settings.get().fullscreen {
+        window_builder = window_builder.with_fullscreen(Some(Fullscreen::Borderless(None)));
+    }
+    let window = window_builder.build(&event_loop).unwrap();
     
+    // Commands such as `time set noon` can be typed into the terminal while the game is running
+    let (command_sender, commands) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lines() {
+            match line {
+                Ok(line) => if command_sender.send(line).is_err() { break },
+                Err(_) => break,
+            }
+        }
+    });
+
     // State::new uses async code, so we're going to wait for it to finish
-    let mut state = pollster::block_on(State::new(&window));
+    let mut state = pollster::block_on(State::new(&window, commands, level, saves.get_directory(&name), settings));
+    let mut last_frame = Instant::now();
 
     event_loop.run(move |event, _, control_flow| {
         if !state.pause {
@@ -561,18 +1150,13 @@ fn main() {
                 window_id,
             } if window_id == window.id() => {
                 state.input(None, Some(&event));
+                if state.quit {
+                    *control_flow = ControlFlow::Exit;
+                }
                 match event {
-                    WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::KeyboardInput {
-                        input:
-                            KeyboardInput {
-                                state: ElementState::Pressed,
-                                virtual_keycode: Some(VirtualKeyCode::Escape),
-                                ..
-                            },
-                        ..
-                    } => {
-                        state.pause = !state.pause;
+                    WindowEvent::CloseRequested => {
+                        state.save();
+                        *control_flow = ControlFlow::Exit;
                     }
                     WindowEvent::Resized(physical_size) => {
                         state.resize(*physical_size);
@@ -588,7 +1172,9 @@ fn main() {
                 state.input(Some(&event), None);
             }
             Event::RedrawRequested(window_id) if window_id == window.id() => {
-                state.update();
+                let now = Instant::now();
+                state.update(now - last_frame);
+                last_frame = now;
                 match state.render() {
                     Ok(_) => {}
                     // Reconfigure the surface if lost
@@ -2,9 +2,22 @@ use cgmath::Point3;
 
 use crate::chunk;
 
+/// The position of the chunk containing a block. Chunks cover the blocks from their center minus half
+/// their size up to, but not including, their center plus half their size.
 pub fn get_chunk_position(pos: Point3<i32>) -> Point3<i32> {
-    Point3::new(
-        (pos.x as f32 / chunk::SIZE as f32).round() as i32,
-        (pos.y as f32 / chunk::SIZE as f32).round() as i32,
-        (pos.z as f32 / chunk::SIZE as f32).round() as i32)
-}
\ No newline at end of file
+    let size = chunk::SIZE as i32;
+    pos.map(|value| (value + size / 2).div_euclid(size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunk_position_matches_chunk_bounds() {
+        // The chunk at the origin covers -8 to 7 on every axis
+        assert_eq!(get_chunk_position(Point3::new(-8, 0, 7)), Point3::new(0, 0, 0));
+        assert_eq!(get_chunk_position(Point3::new(8, -9, 23)), Point3::new(1, -1, 1));
+        assert_eq!(get_chunk_position(Point3::new(-24, -25, 24)), Point3::new(-1, -2, 2));
+    }
+}
@@ -0,0 +1,348 @@
+use crate::{
+    input::{Action, Binding, KeyBindings},
+    render::{font, ui::{self, Color, Rect, Ui}},
+    settings::Settings,
+};
+
+const BUTTON_WIDTH: f32 = 160.0;
+const BUTTON_HEIGHT: f32 = 16.0;
+/// The `-` and `+` buttons on either side of a setting.
+const STEP_BUTTON_WIDTH: f32 = 16.0;
+const CONTROL_WIDTH: f32 = 200.0;
+const CONTROL_HEIGHT: f32 = 12.0;
+const SPACING: f32 = 4.0;
+const FOV_STEP: f32 = 5.0;
+const MOUSE_SENSITIVITY_STEP: f32 = 0.1;
+const DIM: Color = [0.0, 0.0, 0.0, 0.5];
+const BUTTON_COLOR: Color = [0.2, 0.2, 0.2, 0.8];
+const HOVERED_COLOR: Color = [0.4, 0.4, 0.4, 0.9];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Screen {
+    Pause,
+    Settings,
+    Controls,
+}
+
+/// What clicking a button does, carried out by the game.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MenuAction {
+    Resume,
+    Open(Screen),
+    SaveAndQuit,
+    /// Changes the render distance by this many chunks.
+    RenderDistance(i32),
+    Fov(f32),
+    MouseSensitivity(f32),
+    ToggleVsync,
+    /// Waits for the next key or button to bind to the action.
+    Rebind(Action),
+    ResetControls,
+}
+
+/// A button in UI pixels, or a plain label when it has no action.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Button {
+    pub rect: Rect,
+    pub label: String,
+    pub action: Option<MenuAction>,
+}
+
+impl Button {
+    fn new(rect: Rect, label: impl Into<String>, action: Option<MenuAction>) -> Button {
+        Button { rect, label: label.into(), action }
+    }
+
+    fn contains(&self, (x, y): (f32, f32)) -> bool {
+        x >= self.rect.x && x < self.rect.x + self.rect.width && y >= self.rect.y && y < self.rect.y + self.rect.height
+    }
+}
+
+/// The screens shown while the game is paused. Buttons are laid out again from the settings every
+/// frame, so they never show stale values.
+pub struct Menu {
+    screen: Screen,
+    /// The action waiting for a key on the controls screen.
+    rebinding: Option<Action>,
+    /// Why the last rebind was refused, shown on the controls screen until the next one.
+    conflict: Option<String>,
+    /// In UI pixels.
+    cursor: (f32, f32),
+}
+
+impl Menu {
+    pub fn new() -> Menu {
+        Menu {
+            screen: Screen::Pause,
+            rebinding: None,
+            conflict: None,
+            cursor: (-1.0, -1.0),
+        }
+    }
+
+    pub fn open(&mut self, screen: Screen) {
+        self.screen = screen;
+        self.rebinding = None;
+        self.conflict = None;
+    }
+
+    /// Goes back a screen, returning `false` on the pause screen where there is nothing to go back to.
+    pub fn back(&mut self) -> bool {
+        match self.screen {
+            Screen::Pause => false,
+            Screen::Settings => {
+                self.open(Screen::Pause);
+                true
+            }
+            Screen::Controls => {
+                self.open(Screen::Settings);
+                true
+            }
+        }
+    }
+
+    pub fn set_cursor(&mut self, x: f32, y: f32) {
+        self.cursor = (x, y);
+    }
+
+    pub fn get_rebinding(&self) -> Option<Action> {
+        self.rebinding
+    }
+
+    pub fn set_rebinding(&mut self, action: Option<Action>) {
+        self.rebinding = action;
+        self.conflict = None;
+    }
+
+    /// Binds the action waiting for a key to `binding`, returning the new bindings. A binding already
+    /// used by another action is refused instead, and the conflict is shown on the controls screen.
+    pub fn rebind(&mut self, key_bindings: &KeyBindings, binding: Binding) -> Option<KeyBindings> {
+        let action = self.rebinding.take()?;
+        let mut rebound = key_bindings.clone();
+        rebound.rebind(action, binding);
+        let conflict = rebound.get_conflicts().into_iter().find(|(other, actions)| *other == binding && actions.contains(&action));
+        match conflict {
+            Some((_, actions)) => {
+                let others: Vec<String> = actions.into_iter().filter(|other| *other != action).map(|other| other.get_name().replace('_', " ")).collect();
+                self.conflict = Some(format!("{} is already bound to {}", binding, others.join(", ")));
+                None
+            }
+            None => Some(rebound),
+        }
+    }
+
+    /// The action of the button under the cursor, if there is one.
+    pub fn click(&self, buttons: &[Button]) -> Option<MenuAction> {
+        buttons.iter().find(|button| button.contains(self.cursor)).and_then(|button| button.action)
+    }
+
+    pub fn layout(&self, (width, height): (f32, f32), settings: &Settings) -> Vec<Button> {
+        let center = (width / 2.0).floor();
+        let row = BUTTON_HEIGHT + SPACING;
+        let mut buttons = Vec::new();
+        let column = |buttons: &mut Vec<Button>, top: f32, items: Vec<(String, MenuAction)>| {
+            for (index, (label, action)) in items.into_iter().enumerate() {
+                let rect = Rect::new(center - BUTTON_WIDTH / 2.0, top + index as f32 * row, BUTTON_WIDTH, BUTTON_HEIGHT);
+                buttons.push(Button::new(rect, label, Some(action)));
+            }
+        };
+        match self.screen {
+            Screen::Pause => {
+                let top = get_top(height, 4.0 * row);
+                buttons.push(title(center, top, "Paused"));
+                column(&mut buttons, top + row, vec![
+                    ("Resume".to_string(), MenuAction::Resume),
+                    ("Settings".to_string(), MenuAction::Open(Screen::Settings)),
+                    ("Save and quit".to_string(), MenuAction::SaveAndQuit),
+                ]);
+            }
+            Screen::Settings => {
+                let top = get_top(height, 7.0 * row);
+                buttons.push(title(center, top, "Settings"));
+                let steps = [
+                    (format!("Render distance: {}", settings.render_distance), MenuAction::RenderDistance(-1), MenuAction::RenderDistance(1)),
+                    (format!("FOV: {:.0}", settings.fov), MenuAction::Fov(-FOV_STEP), MenuAction::Fov(FOV_STEP)),
+                    (
+                        format!("Mouse sensitivity: {:.1}", settings.mouse_sensitivity),
+                        MenuAction::MouseSensitivity(-MOUSE_SENSITIVITY_STEP),
+                        MenuAction::MouseSensitivity(MOUSE_SENSITIVITY_STEP),
+                    ),
+                ];
+                for (index, (label, decrease, increase)) in steps.into_iter().enumerate() {
+                    let y = top + (index + 1) as f32 * row;
+                    let left = center - BUTTON_WIDTH / 2.0;
+                    buttons.push(Button::new(Rect::new(left - SPACING - STEP_BUTTON_WIDTH, y, STEP_BUTTON_WIDTH, BUTTON_HEIGHT), "-", Some(decrease)));
+                    buttons.push(Button::new(Rect::new(left, y, BUTTON_WIDTH, BUTTON_HEIGHT), label, None));
+                    buttons.push(Button::new(Rect::new(left + BUTTON_WIDTH + SPACING, y, STEP_BUTTON_WIDTH, BUTTON_HEIGHT), "+", Some(increase)));
+                }
+                column(&mut buttons, top + 4.0 * row, vec![
+                    (format!("Vsync: {}", if settings.vsync { "on" } else { "off" }), MenuAction::ToggleVsync),
+                    ("Controls".to_string(), MenuAction::Open(Screen::Controls)),
+                    ("Back".to_string(), MenuAction::Open(Screen::Pause)),
+                ]);
+            }
+            Screen::Controls => {
+                let rows = Action::ALL.len().div_ceil(2);
+                let control_row = CONTROL_HEIGHT + SPACING;
+                let top = get_top(height, row + rows as f32 * control_row + 2.0 * row);
+                buttons.push(title(center, top, self.conflict.as_deref().unwrap_or("Click an action, then press a key")));
+                for (index, action) in Action::ALL.into_iter().enumerate() {
+                    let (side, line) = (index / rows, index % rows);
+                    let x = center - CONTROL_WIDTH - SPACING / 2.0 + side as f32 * (CONTROL_WIDTH + SPACING);
+                    let y = top + row + line as f32 * control_row;
+                    let bindings = match self.rebinding {
+                        Some(rebinding) if rebinding == action => "press a key".to_string(),
+                        _ => settings.key_bindings.get_bindings(action).iter().map(|binding| binding.to_string()).collect::<Vec<_>>().join(", "),
+                    };
+                    let label = format!("{}: {}", action.get_name().replace('_', " "), bindings);
+                    buttons.push(Button::new(Rect::new(x, y, CONTROL_WIDTH, CONTROL_HEIGHT), label, Some(MenuAction::Rebind(action))));
+                }
+                column(&mut buttons, top + row + rows as f32 * control_row, vec![
+                    ("Reset to defaults".to_string(), MenuAction::ResetControls),
+                    ("Back".to_string(), MenuAction::Open(Screen::Settings)),
+                ]);
+            }
+        }
+        buttons
+    }
+
+    /// Dims the world and draws the current screen over it.
+    pub fn draw(&self, ui: &mut Ui, settings: &Settings) {
+        let (width, height) = ui.get_size();
+        ui.rect(Rect::new(0.0, 0.0, width, height), DIM);
+        for button in self.layout((width, height), settings) {
+            if button.action.is_some() {
+                let color = if button.contains(self.cursor) { HOVERED_COLOR } else { BUTTON_COLOR };
+                ui.rect(button.rect, color);
+            }
+            let x = button.rect.x + ((button.rect.width - ui::get_text_width(&button.label)) / 2.0).floor();
+            let y = button.rect.y + ((button.rect.height - font::GLYPH_SIZE as f32) / 2.0).floor();
+            ui.text(x, y, &button.label, ui::WHITE);
+        }
+    }
+}
+
+/// Whether an action's key still does something while the game is paused. Only unpausing does, but
+/// releases always go through so keys held when the menu opened don't stay held.
+pub fn passes_while_paused(action: Action, pressed: bool) -> bool {
+    !pressed || action == Action::TogglePause
+}
+
+/// Top of a screen `height` UI pixels tall, centered in the window.
+fn get_top(window_height: f32, height: f32) -> f32 {
+    ((window_height - height) / 2.0).floor().max(0.0)
+}
+
+fn title(center: f32, top: f32, text: &str) -> Button {
+    Button::new(Rect::new(center - BUTTON_WIDTH / 2.0, top, BUTTON_WIDTH, BUTTON_HEIGHT), text, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Point3;
+    use winit::event::VirtualKeyCode;
+
+    use crate::{camera::CameraController, player::Player};
+
+    use super::*;
+
+    const SIZE: (f32, f32) = (640.0, 360.0);
+
+    fn click(menu: &mut Menu, button: &Button) -> Option<MenuAction> {
+        let rect = button.rect;
+        menu.set_cursor(rect.x + rect.width / 2.0, rect.y + rect.height / 2.0);
+        menu.click(&menu.layout(SIZE, &Settings::default()))
+    }
+
+    fn find(menu: &Menu, label: &str) -> Button {
+        let buttons = menu.layout(SIZE, &Settings::default());
+        buttons.into_iter().find(|button| button.label == label).unwrap()
+    }
+
+    #[test]
+    fn clicking_a_button_gives_its_action() {
+        let mut menu = Menu::new();
+        for (label, action) in [("Resume", Some(MenuAction::Resume)), ("Settings", Some(MenuAction::Open(Screen::Settings))), ("Paused", None)] {
+            let button = find(&menu, label);
+            assert_eq!(click(&mut menu, &button), action);
+        }
+        menu.set_cursor(0.0, 0.0);
+        assert_eq!(menu.click(&menu.layout(SIZE, &Settings::default())), None);
+    }
+
+    #[test]
+    fn settings_show_their_values() {
+        let mut menu = Menu::new();
+        menu.open(Screen::Settings);
+        let settings = Settings { render_distance: 7, vsync: false, ..Settings::default() };
+        let labels: Vec<String> = menu.layout(SIZE, &settings).into_iter().map(|button| button.label).collect();
+        assert!(labels.contains(&"Render distance: 7".to_string()));
+        assert!(labels.contains(&"FOV: 70".to_string()));
+        assert!(labels.contains(&"Vsync: off".to_string()));
+    }
+
+    #[test]
+    fn controls_list_every_action() {
+        let mut menu = Menu::new();
+        menu.open(Screen::Controls);
+        let buttons = menu.layout(SIZE, &Settings::default());
+        for action in Action::ALL {
+            assert!(buttons.iter().any(|button| button.action == Some(MenuAction::Rebind(action))));
+        }
+        assert_eq!(find(&menu, "jump: Space, GamepadSouth").action, Some(MenuAction::Rebind(Action::Jump)));
+        menu.set_rebinding(Some(Action::Jump));
+        find(&menu, "jump: press a key");
+        // Everything fits in the window
+        assert!(buttons.iter().all(|button| button.rect.y >= 0.0 && button.rect.y + button.rect.height <= SIZE.1));
+    }
+
+    #[test]
+    fn rebinding_to_a_used_key_is_refused() {
+        let mut menu = Menu::new();
+        menu.open(Screen::Controls);
+        let key_bindings = KeyBindings::default();
+        menu.set_rebinding(Some(Action::Jump));
+        assert_eq!(menu.rebind(&key_bindings, Binding::Key(VirtualKeyCode::W)), None);
+        assert_eq!(menu.get_rebinding(), None);
+        find(&menu, "W is already bound to move forward");
+
+        menu.set_rebinding(Some(Action::Jump));
+        let rebound = menu.rebind(&key_bindings, Binding::Key(VirtualKeyCode::Q)).unwrap();
+        assert_eq!(rebound.get_actions(Binding::Key(VirtualKeyCode::Q)), vec![Action::Jump]);
+        assert!(rebound.get_conflicts().is_empty());
+        find(&menu, "Click an action, then press a key");
+    }
+
+    #[test]
+    fn back_returns_to_the_pause_screen() {
+        let mut menu = Menu::new();
+        menu.open(Screen::Controls);
+        menu.set_rebinding(Some(Action::Jump));
+        assert!(menu.back());
+        assert_eq!(menu.screen, Screen::Settings);
+        assert_eq!(menu.get_rebinding(), None);
+        assert!(menu.back());
+        assert!(!menu.back());
+        assert_eq!(menu.screen, Screen::Pause);
+    }
+
+    #[test]
+    fn held_keys_do_not_move_the_player_while_paused() {
+        let mut controller = CameraController::new();
+        // Held from before the menu opened
+        controller.process_action(Action::MoveForward, true);
+        let mut handle = |action: Action, pressed: bool| {
+            if passes_while_paused(action, pressed) {
+                controller.process_action(action, pressed);
+            }
+        };
+        handle(Action::MoveLeft, true);
+        handle(Action::Jump, true);
+        handle(Action::MoveForward, false);
+        assert!(passes_while_paused(Action::TogglePause, true));
+
+        let mut player = Player::new(Point3::new(0.0, 0.0, 0.0));
+        player.tick(&controller.get_input(), 0.0, 0.0, |position| position.y < 0);
+        assert_eq!((player.position.x, player.position.z), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,306 @@
+use std::io::{self, Read, Write};
+
+/// Blocks stored as a palette of the distinct block ids plus an index into it for every block,
+/// packed into as few bits as the palette needs. A chunk of only one block, like all air or all
+/// stone, is just its palette.
+#[derive(Clone, Debug)]
+pub struct PalettedBlocks {
+    len: usize,
+    palette: Vec<u32>,
+    /// Bits per index, 0 while the palette has a single block.
+    bits: u8,
+    /// Indices packed into words, never split across two words.
+    data: Vec<u64>,
+}
+
+impl PalettedBlocks {
+    /// `len` blocks that are all `block`.
+    pub fn new(len: usize, block: u32) -> PalettedBlocks {
+        PalettedBlocks {
+            len,
+            palette: vec![block],
+            bits: 0,
+            data: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, index: usize) -> u32 {
+        debug_assert!(index < self.len);
+        if self.bits == 0 {
+            return self.palette[0];
+        }
+        self.palette[get_packed(&self.data, self.bits, index) as usize]
+    }
+
+    /// Sets a block, adding it to the palette and widening the indices when it is a new one.
+    pub fn set(&mut self, index: usize, block: u32) {
+        debug_assert!(index < self.len);
+        let palette_index = match self.palette.iter().position(|other| *other == block) {
+            Some(palette_index) => palette_index,
+            None => {
+                self.palette.push(block);
+                let bits = get_bits(self.palette.len());
+                if bits != self.bits {
+                    self.repack(bits);
+                }
+                self.palette.len() - 1
+            }
+        };
+        if self.bits > 0 {
+            set_packed(&mut self.data, self.bits, index, palette_index as u64);
+        }
+    }
+
+    /// Every block in order.
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.len).map(|index| self.get(index))
+    }
+
+    /// The distinct blocks. Blocks that were replaced can linger here until `compact` is called, so
+    /// this may contain blocks that no longer appear.
+    pub fn get_palette(&self) -> &[u32] {
+        &self.palette
+    }
+
+    /// The block everything is, if there is only one.
+    pub fn get_uniform(&self) -> Option<u32> {
+        match self.palette.as_slice() {
+            [block] => Some(*block),
+            _ => None,
+        }
+    }
+
+    /// Drops blocks from the palette that no longer appear and narrows the indices to match.
+    pub fn compact(&mut self) {
+        let blocks: Vec<u32> = self.iter().collect();
+        let mut compacted = PalettedBlocks::new(self.len, blocks[0]);
+        // Add every block to the palette first, so the indices are only packed once
+        for block in &blocks {
+            if !compacted.palette.contains(block) {
+                compacted.palette.push(*block);
+            }
+        }
+        compacted.repack(get_bits(compacted.palette.len()));
+        for (index, block) in blocks.into_iter().enumerate() {
+            compacted.set(index, block);
+        }
+        *self = compacted;
+    }
+
+    /// Writes the palette's length as a `u16`, the palette, the bits per index as a `u8` and the
+    /// packed words, all little endian. The length of the blocks isn't written, it is passed to `read`.
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&(self.palette.len() as u16).to_le_bytes())?;
+        for block in &self.palette {
+            writer.write_all(&block.to_le_bytes())?;
+        }
+        writer.write_all(&[self.bits])?;
+        for word in &self.data {
+            writer.write_all(&word.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read(reader: &mut impl Read, len: usize) -> io::Result<PalettedBlocks> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let mut palette_len = [0; 2];
+        reader.read_exact(&mut palette_len)?;
+        let palette_len = u16::from_le_bytes(palette_len) as usize;
+        if palette_len == 0 {
+            return Err(invalid("Empty palette"));
+        }
+        let mut palette = Vec::with_capacity(palette_len);
+        for _ in 0..palette_len {
+            let mut block = [0; 4];
+            reader.read_exact(&mut block)?;
+            palette.push(u32::from_le_bytes(block));
+        }
+        let mut bits = [0];
+        reader.read_exact(&mut bits)?;
+        let bits = bits[0];
+        if bits != get_bits(palette_len) {
+            return Err(invalid("Bits per index don't match the palette"));
+        }
+        let mut data = vec![0; get_word_count(len, bits)];
+        for word in data.iter_mut() {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            *word = u64::from_le_bytes(bytes);
+        }
+        let blocks = PalettedBlocks { len, palette, bits, data };
+        if blocks.bits > 0 && (0..len).any(|index| get_packed(&blocks.data, bits, index) as usize >= palette_len) {
+            return Err(invalid("Index past the end of the palette"));
+        }
+        Ok(blocks)
+    }
+
+    fn repack(&mut self, bits: u8) {
+        let mut data = vec![0; get_word_count(self.len, bits)];
+        if self.bits > 0 {
+            for index in 0..self.len {
+                set_packed(&mut data, bits, index, get_packed(&self.data, self.bits, index));
+            }
+        }
+        self.bits = bits;
+        self.data = data;
+    }
+}
+
+/// Compares the blocks, not how they happen to be stored.
+impl PartialEq for PalettedBlocks {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+/// Bits needed to index a palette of this length, 0 for a single block.
+fn get_bits(palette_len: usize) -> u8 {
+    (usize::BITS - (palette_len - 1).leading_zeros()) as u8
+}
+
+fn get_word_count(len: usize, bits: u8) -> usize {
+    if bits == 0 { 0 } else { len.div_ceil(64 / bits as usize) }
+}
+
+fn get_packed(data: &[u64], bits: u8, index: usize) -> u64 {
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) * bits as usize;
+    (data[index / per_word] >> shift) & ((1 << bits) - 1)
+}
+
+fn set_packed(data: &mut [u64], bits: u8, index: usize, value: u64) {
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) * bits as usize;
+    let mask = ((1 << bits) - 1) << shift;
+    let word = &mut data[index / per_word];
+    *word = (*word & !mask) | (value << shift);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEN: usize = 4096;
+
+    /// Bytes taken up on the heap by the palette and indices.
+    fn memory_usage(blocks: &PalettedBlocks) -> usize {
+        blocks.palette.capacity() * std::mem::size_of::<u32>() + blocks.data.capacity() * std::mem::size_of::<u64>()
+    }
+
+    #[test]
+    fn uniform_blocks_take_no_indices() {
+        let blocks = PalettedBlocks::new(LEN, 0);
+        assert_eq!(blocks.get_uniform(), Some(0));
+        assert_eq!(blocks.get(LEN - 1), 0);
+        assert!(memory_usage(&blocks) <= 16);
+        assert!(PalettedBlocks::new(LEN, 3).iter().all(|block| block == 3));
+    }
+
+    #[test]
+    fn bits_grow_with_the_palette() {
+        assert_eq!(get_bits(1), 0);
+        assert_eq!(get_bits(2), 1);
+        assert_eq!(get_bits(3), 2);
+        assert_eq!(get_bits(4), 2);
+        assert_eq!(get_bits(5), 3);
+        assert_eq!(get_bits(256), 8);
+        assert_eq!(get_bits(257), 9);
+    }
+
+    #[test]
+    fn set_keeps_every_block() {
+        let mut blocks = PalettedBlocks::new(LEN, 0);
+        let expected: Vec<u32> = (0..LEN).map(|index| (index as u32 * 7919) % 300).collect();
+        for (index, block) in expected.iter().enumerate() {
+            blocks.set(index, *block);
+            // Widening the indices must not disturb blocks set before
+            if index % 512 == 0 {
+                assert!(blocks.iter().take(index + 1).eq(expected.iter().take(index + 1).copied()));
+            }
+        }
+        assert!(blocks.iter().eq(expected.iter().copied()));
+        assert_eq!(blocks.bits, 9);
+        assert_eq!(blocks.get_uniform(), None);
+    }
+
+    #[test]
+    fn memory_scales_with_content() {
+        let mut two = PalettedBlocks::new(LEN, 0);
+        two.set(10, 1);
+        let mut many = PalettedBlocks::new(LEN, 0);
+        for index in 0..LEN {
+            many.set(index, index as u32 % 100);
+        }
+        assert!(memory_usage(&two) < LEN * 4 / 16);
+        assert!(memory_usage(&two) < memory_usage(&many));
+        assert!(memory_usage(&many) < LEN * 4);
+    }
+
+    #[test]
+    fn compact_drops_unused_blocks() {
+        let mut blocks = PalettedBlocks::new(LEN, 0);
+        for block in 1..10 {
+            blocks.set(5, block);
+        }
+        blocks.set(6, 2);
+        assert_eq!(blocks.get_palette().len(), 10);
+        let before = blocks.clone();
+        blocks.compact();
+        assert_eq!(blocks.get_palette(), &[0, 9, 2]);
+        assert_eq!(blocks.bits, 2);
+        assert_eq!(blocks, before);
+
+        // Back to a single block
+        blocks.set(5, 0);
+        blocks.set(6, 0);
+        blocks.compact();
+        assert_eq!(blocks.get_uniform(), Some(0));
+    }
+
+    #[test]
+    fn equality_ignores_how_blocks_are_stored() {
+        let mut a = PalettedBlocks::new(LEN, 0);
+        a.set(0, 5);
+        a.set(0, 0);
+        assert_eq!(a, PalettedBlocks::new(LEN, 0));
+        assert_ne!(PalettedBlocks::new(LEN, 0), PalettedBlocks::new(LEN, 1));
+    }
+
+    #[test]
+    fn serialisation_round_trips() {
+        for palette_size in [1, 2, 3, 17, 600] {
+            let mut blocks = PalettedBlocks::new(LEN, 0);
+            for index in 0..LEN {
+                blocks.set(index, (index % palette_size) as u32 * 3);
+            }
+            let mut bytes = Vec::new();
+            blocks.write(&mut bytes).unwrap();
+            let read = PalettedBlocks::read(&mut bytes.as_slice(), LEN).unwrap();
+            assert_eq!(read, blocks);
+            assert_eq!(read.get_palette(), blocks.get_palette());
+        }
+    }
+
+    #[test]
+    fn rejects_broken_data() {
+        let mut blocks = PalettedBlocks::new(LEN, 0);
+        blocks.set(0, 1);
+        blocks.set(1, 2);
+        let mut bytes = Vec::new();
+        blocks.write(&mut bytes).unwrap();
+
+        // Cut short
+        assert!(PalettedBlocks::read(&mut &bytes[..bytes.len() - 1], LEN).is_err());
+        // Palette of three blocks with an index of 3
+        let mut bad_index = bytes.clone();
+        let data_start = 2 + 3 * 4 + 1;
+        bad_index[data_start] = 0b11;
+        assert!(PalettedBlocks::read(&mut bad_index.as_slice(), LEN).is_err());
+        // Wrong bits for the palette
+        let mut bad_bits = bytes.clone();
+        bad_bits[data_start - 1] = 5;
+        assert!(PalettedBlocks::read(&mut bad_bits.as_slice(), LEN).is_err());
+        // No palette at all
+        assert!(PalettedBlocks::read(&mut [0u8, 0, 0].as_slice(), LEN).is_err());
+    }
+}
@@ -0,0 +1,485 @@
+use cgmath::{InnerSpace, Point3, Vector3, Zero};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::render::frustum::Aabb;
+
+pub const WIDTH: f32 = 0.6;
+pub const HEIGHT: f32 = 1.8;
+pub const EYE_HEIGHT: f32 = 1.62;
+/// Ledges up to this high are walked onto without jumping.
+pub const STEP_HEIGHT: f32 = 1.0;
+/// Speeds and accelerations are per tick.
+pub const WALK_SPEED: f32 = 0.1;
+pub const FLY_SPEED: f32 = 0.2;
+pub const GRAVITY: f32 = 0.015;
+pub const JUMP_VELOCITY: f32 = 0.2;
+pub const TERMINAL_VELOCITY: f32 = 1.0;
+/// Keeps the player from resting exactly on a block's surface, where rounding could put it inside.
+const EPSILON: f32 = 1e-4;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveMode {
+    /// Gravity and collisions.
+    Walk,
+    /// Collisions, but moves freely up and down without falling.
+    Fly,
+    /// Moves freely through blocks along the direction the camera looks.
+    Noclip,
+}
+
+/// Which movement keys are held for a tick.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PlayerInput {
+    pub forward: bool,
+    pub backward: bool,
+    pub left: bool,
+    pub right: bool,
+    /// Jumps while walking, rises while flying.
+    pub up: bool,
+    pub down: bool,
+    /// Analog movement on top of the keys, right then forward, each from -1 to 1.
+    pub analog: (f32, f32),
+}
+
+pub struct Player {
+    /// The center of the bottom of the player's box.
+    pub position: Point3<f32>,
+    /// Where the player was before the last tick, rendering moves the camera smoothly between the two.
+    pub previous_position: Point3<f32>,
+    pub velocity: Vector3<f32>,
+    pub on_ground: bool,
+    pub mode: MoveMode,
+}
+
+impl Player {
+    pub fn new(position: Point3<f32>) -> Player {
+        Player {
+            position,
+            previous_position: position,
+            velocity: Vector3::zero(),
+            on_ground: false,
+            mode: MoveMode::Walk,
+        }
+    }
+
+    pub fn get_eye_position(&self) -> Point3<f32> {
+        self.position + Vector3::new(0.0, EYE_HEIGHT, 0.0)
+    }
+
+    /// The eye position `alpha` of the way from the previous tick to the current one.
+    pub fn get_interpolated_eye_position(&self, alpha: f32) -> Point3<f32> {
+        let position = self.previous_position + (self.position - self.previous_position) * alpha;
+        position + Vector3::new(0.0, EYE_HEIGHT, 0.0)
+    }
+
+    pub fn get_aabb(&self) -> Aabb {
+        get_aabb(self.position)
+    }
+
+    /// Switches to the mode, or back to walking when already in it.
+    pub fn toggle_mode(&mut self, mode: MoveMode) {
+        self.mode = if self.mode == mode { MoveMode::Walk } else { mode };
+        self.velocity = Vector3::zero();
+    }
+
+    /// Advances the player by one tick. `yaw` and `pitch` are the camera's, and `is_solid` says
+    /// which blocks can't be walked through.
+    pub fn tick(&mut self, input: &PlayerInput, yaw: f32, pitch: f32, is_solid: impl Fn(Point3<i32>) -> bool) {
+        self.previous_position = self.position;
+        let forward = Vector3::new(yaw.cos(), 0.0, yaw.sin());
+        let right = forward.cross(Vector3::unit_y());
+        let mut horizontal = Vector3::zero();
+        if input.forward { horizontal += forward; }
+        if input.backward { horizontal -= forward; }
+        if input.right { horizontal += right; }
+        if input.left { horizontal -= right; }
+        horizontal += right * input.analog.0 + forward * input.analog.1;
+        // Pressing two keys isn't faster, but a stick pushed half way is slower
+        if horizontal.magnitude2() > 1.0 {
+            horizontal = horizontal.normalize();
+        }
+
+        match self.mode {
+            MoveMode::Noclip => {
+                // Same as the free camera from before there was a player
+                let look = Vector3::new(yaw.cos(), pitch, yaw.sin());
+                let mut movement = Vector3::zero();
+                if input.forward { movement += look; }
+                if input.backward { movement -= look; }
+                let right = look.cross(Vector3::unit_y());
+                if input.right { movement += right; }
+                if input.left { movement -= right; }
+                if input.up { movement.y += 1.0; }
+                if input.down { movement.y -= 1.0; }
+                movement += right * input.analog.0 + look * input.analog.1;
+                if movement.magnitude2() > 1.0 {
+                    movement = movement.normalize();
+                }
+                self.position += movement * FLY_SPEED;
+                self.velocity = Vector3::zero();
+                self.on_ground = false;
+            }
+            MoveMode::Fly => {
+                let mut vertical = 0.0;
+                if input.up { vertical += 1.0; }
+                if input.down { vertical -= 1.0; }
+                self.velocity = Vector3::new(horizontal.x, vertical, horizontal.z) * FLY_SPEED;
+                self.move_colliding(&is_solid, false);
+            }
+            MoveMode::Walk => {
+                if self.on_ground && input.up {
+                    self.velocity.y = JUMP_VELOCITY;
+                }
+                self.velocity.x = horizontal.x * WALK_SPEED;
+                self.velocity.z = horizontal.z * WALK_SPEED;
+                self.velocity.y = (self.velocity.y - GRAVITY).max(-TERMINAL_VELOCITY);
+                let can_step = self.on_ground;
+                self.move_colliding(&is_solid, can_step);
+            }
+        }
+    }
+
+    /// Moves by the velocity one axis at a time, stopping at solid blocks. When `can_step` is set and
+    /// a wall was hit, also tries stepping up onto it and keeps whichever went further.
+    fn move_colliding(&mut self, is_solid: &impl Fn(Point3<i32>) -> bool, can_step: bool) {
+        let wanted = self.velocity;
+        let (mut moved, mut position) = sweep(self.position, wanted, is_solid);
+
+        let blocked = moved.x != wanted.x || moved.z != wanted.z;
+        if can_step && blocked {
+            // Up, across and back down onto whatever is there
+            let (up, stepped) = sweep(self.position, Vector3::new(0.0, STEP_HEIGHT, 0.0), is_solid);
+            let (across, stepped) = sweep(stepped, Vector3::new(wanted.x, 0.0, wanted.z), is_solid);
+            let (down, stepped) = sweep(stepped, Vector3::new(0.0, -up.y, 0.0), is_solid);
+            let horizontal = |moved: Vector3<f32>| moved.x * moved.x + moved.z * moved.z;
+            if horizontal(across) > horizontal(moved) {
+                moved = Vector3::new(across.x, up.y + down.y, across.z);
+                position = stepped;
+            }
+        }
+
+        self.on_ground = wanted.y < 0.0 && moved.y > wanted.y;
+        if moved.x != wanted.x { self.velocity.x = 0.0; }
+        if moved.y != wanted.y { self.velocity.y = 0.0; }
+        if moved.z != wanted.z { self.velocity.z = 0.0; }
+        self.position = position;
+    }
+}
+
+fn get_aabb(position: Point3<f32>) -> Aabb {
+    let half = WIDTH / 2.0;
+    Aabb::new(
+        Point3::new(position.x - half, position.y, position.z - half),
+        Point3::new(position.x + half, position.y + HEIGHT, position.z + half),
+    )
+}
+
+/// Moves the box at `position` by `movement`, vertically first and then along x and z, shortening
+/// each axis so the box stops against the first solid block in its way. Returns how far it moved
+/// and where it ended up.
+fn sweep(position: Point3<f32>, movement: Vector3<f32>, is_solid: &impl Fn(Point3<i32>) -> bool) -> (Vector3<f32>, Point3<f32>) {
+    let mut position = position;
+    let mut moved = Vector3::zero();
+    for axis in [1, 0, 2] {
+        let distance = clip_axis(get_aabb(position), axis, movement[axis], is_solid);
+        position[axis] += distance;
+        moved[axis] = distance;
+    }
+    (moved, position)
+}
+
+/// How far the box can move along one axis before touching a solid block. Blocks the box already
+/// overlaps are ignored, so a player stuck inside terrain can still get out.
+fn clip_axis(aabb: Aabb, axis: usize, distance: f32, is_solid: &impl Fn(Point3<i32>) -> bool) -> f32 {
+    if distance == 0.0 {
+        return 0.0;
+    }
+    // Every block the box passes through on the way, blocks being centered on whole numbers
+    let mut min = aabb.min;
+    let mut max = aabb.max;
+    if distance > 0.0 { max[axis] += distance; } else { min[axis] += distance; }
+    let first = min.map(|value| (value + 0.5).floor() as i32);
+    let last = max.map(|value| (value + 0.5).ceil() as i32 - 1);
+
+    let mut allowed = distance;
+    for x in first.x..=last.x {
+        for y in first.y..=last.y {
+            for z in first.z..=last.z {
+                let block = Point3::new(x, y, z);
+                if !is_solid(block) {
+                    continue;
+                }
+                let block_min = block[axis] as f32 - 0.5;
+                let block_max = block[axis] as f32 + 0.5;
+                if distance > 0.0 && block_min >= aabb.max[axis] - EPSILON {
+                    allowed = allowed.min(block_min - aabb.max[axis] - EPSILON).max(0.0);
+                } else if distance < 0.0 && block_max <= aabb.min[axis] + EPSILON {
+                    allowed = allowed.max(block_max - aabb.min[axis] + EPSILON).min(0.0);
+                }
+            }
+        }
+    }
+    allowed
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    /// A floor of blocks at y = 0 plus any extra blocks, so the floor's surface is at y = 0.5.
+    fn world(extra: &[Point3<i32>]) -> impl Fn(Point3<i32>) -> bool {
+        let extra: HashSet<Point3<i32>> = extra.iter().copied().collect();
+        move |block| block.y == 0 || extra.contains(&block)
+    }
+
+    fn run(player: &mut Player, input: PlayerInput, yaw: f32, ticks: usize, is_solid: &impl Fn(Point3<i32>) -> bool) {
+        for _ in 0..ticks {
+            player.tick(&input, yaw, 0.0, is_solid);
+        }
+    }
+
+    fn forward() -> PlayerInput {
+        PlayerInput { forward: true, ..Default::default() }
+    }
+
+    fn assert_near(value: f32, expected: f32) {
+        assert!((value - expected).abs() < 0.01, "{} is not close to {}", value, expected);
+    }
+
+    #[test]
+    fn falls_and_lands_on_the_floor() {
+        let solid = world(&[]);
+        let mut player = Player::new(Point3::new(0.0, 10.0, 0.0));
+        run(&mut player, PlayerInput::default(), 0.0, 200, &solid);
+        assert!(player.on_ground);
+        assert_near(player.position.y, 0.5);
+        assert_eq!(player.velocity.y, 0.0);
+        // Stays there
+        run(&mut player, PlayerInput::default(), 0.0, 50, &solid);
+        assert_near(player.position.y, 0.5);
+        assert!(player.on_ground);
+    }
+
+    #[test]
+    fn falling_speed_is_capped() {
+        let mut player = Player::new(Point3::new(0.0, 1000.0, 0.0));
+        run(&mut player, PlayerInput::default(), 0.0, 500, &|_| false);
+        assert_eq!(player.velocity.y, -TERMINAL_VELOCITY);
+        assert!(!player.on_ground);
+    }
+
+    #[test]
+    fn fast_fall_does_not_tunnel_through_thin_floor() {
+        let solid = |block: Point3<i32>| block.y == -50;
+        let mut player = Player::new(Point3::new(0.0, 100.0, 0.0));
+        run(&mut player, PlayerInput::default(), 0.0, 1000, &solid);
+        assert_near(player.position.y, -49.5);
+    }
+
+    #[test]
+    fn jump_clears_one_block_and_lands_again() {
+        let solid = world(&[]);
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        run(&mut player, PlayerInput::default(), 0.0, 1, &solid);
+        assert!(player.on_ground);
+
+        let jump = PlayerInput { up: true, ..Default::default() };
+        player.tick(&jump, 0.0, 0.0, &solid);
+        assert!(!player.on_ground);
+        let mut highest: f32 = player.position.y;
+        for _ in 0..100 {
+            player.tick(&PlayerInput::default(), 0.0, 0.0, &solid);
+            highest = highest.max(player.position.y);
+        }
+        assert!(highest - 0.5 > 1.0, "only jumped {}", highest - 0.5);
+        assert!(highest - 0.5 < 2.0, "jumped {}", highest - 0.5);
+        assert!(player.on_ground);
+        assert_near(player.position.y, 0.5);
+    }
+
+    #[test]
+    fn cannot_jump_in_the_air() {
+        let mut player = Player::new(Point3::new(0.0, 10.0, 0.0));
+        let jump = PlayerInput { up: true, ..Default::default() };
+        player.tick(&jump, 0.0, 0.0, |_| false);
+        assert!(player.velocity.y < 0.0);
+    }
+
+    #[test]
+    fn ceiling_stops_a_jump() {
+        // Two blocks of headroom minus a bit, the block above sits at y = 3
+        let solid = world(&[Point3::new(0, 3, 0)]);
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        run(&mut player, PlayerInput::default(), 0.0, 1, &solid);
+        let jump = PlayerInput { up: true, ..Default::default() };
+        player.tick(&jump, 0.0, 0.0, &solid);
+        for _ in 0..100 {
+            player.tick(&PlayerInput::default(), 0.0, 0.0, &solid);
+            assert!(player.get_aabb().max.y <= 2.5);
+        }
+        assert!(player.on_ground);
+    }
+
+    #[test]
+    fn walls_stop_the_player_flush() {
+        // Walking along +x into a two block high wall at x = 3
+        let solid = world(&[Point3::new(3, 1, 0), Point3::new(3, 2, 0)]);
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        run(&mut player, forward(), 0.0, 100, &solid);
+        assert_near(player.get_aabb().max.x, 2.5);
+        assert_near(player.position.z, 0.0);
+        assert_near(player.position.y, 0.5);
+    }
+
+    #[test]
+    fn walks_up_single_steps() {
+        let platform: Vec<Point3<i32>> = (3..20).flat_map(|x| (-1..=1).map(move |z| Point3::new(x, 1, z))).collect();
+        let solid = world(&platform);
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        run(&mut player, forward(), 0.0, 60, &solid);
+        assert!(player.position.x > 3.0);
+        assert_near(player.position.y, 1.5);
+    }
+
+    #[test]
+    fn does_not_step_while_in_the_air() {
+        let solid = |block: Point3<i32>| block.x == 3 && block.y == 11;
+        let mut player = Player::new(Point3::new(2.0, 11.0, 0.0));
+        player.tick(&forward(), 0.0, 0.0, solid);
+        assert!(player.get_aabb().max.x <= 2.5);
+        assert!(player.position.y < 11.0);
+    }
+
+    #[test]
+    fn does_not_step_up_two_blocks() {
+        let solid = world(&[Point3::new(3, 1, 0), Point3::new(3, 2, 0)]);
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        run(&mut player, forward(), 0.0, 100, &solid);
+        assert_near(player.position.y, 0.5);
+    }
+
+    #[test]
+    fn slides_along_walls() {
+        // Moving diagonally into a long wall along z keeps the z part of the movement
+        let wall: Vec<Point3<i32>> = (-20..20).flat_map(|z| [Point3::new(3, 1, z), Point3::new(3, 2, z)]).collect();
+        let solid = world(&wall);
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        run(&mut player, forward(), std::f32::consts::FRAC_PI_4, 100, &solid);
+        assert_near(player.get_aabb().max.x, 2.5);
+        assert!(player.position.z > 5.0);
+    }
+
+    #[test]
+    fn squeezes_through_one_block_gaps_only_when_it_fits() {
+        // A wall at x = 3 with a doorway at z = 0 that is one block wide and two blocks high
+        let wall: Vec<Point3<i32>> = (-5..=5).filter(|z| *z != 0).flat_map(|z| [Point3::new(3, 1, z), Point3::new(3, 2, z)]).collect();
+        let solid = world(&wall);
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        run(&mut player, forward(), 0.0, 100, &solid);
+        assert!(player.position.x > 5.0);
+
+        // A player off to the side of the doorway is stopped by the wall
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.6));
+        run(&mut player, forward(), 0.0, 100, &solid);
+        assert!(player.position.x < 3.0);
+    }
+
+    #[test]
+    fn walking_direction_follows_yaw() {
+        let solid = world(&[]);
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        run(&mut player, forward(), std::f32::consts::FRAC_PI_2, 10, &solid);
+        assert_near(player.position.x, 0.0);
+        assert_near(player.position.z, 10.0 * WALK_SPEED);
+
+        let right = PlayerInput { right: true, ..Default::default() };
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        run(&mut player, right, 0.0, 10, &solid);
+        assert_near(player.position.z, 10.0 * WALK_SPEED);
+    }
+
+    #[test]
+    fn diagonal_movement_is_not_faster() {
+        let solid = world(&[]);
+        let diagonal = PlayerInput { forward: true, right: true, ..Default::default() };
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        run(&mut player, diagonal, 0.0, 10, &solid);
+        let distance = (player.position.x * player.position.x + player.position.z * player.position.z).sqrt();
+        assert_near(distance, 10.0 * WALK_SPEED);
+    }
+
+    #[test]
+    fn flying_ignores_gravity_but_not_blocks() {
+        let solid = world(&[Point3::new(0, 5, 0)]);
+        let mut player = Player::new(Point3::new(0.0, 2.0, 0.0));
+        player.toggle_mode(MoveMode::Fly);
+        run(&mut player, PlayerInput::default(), 0.0, 50, &solid);
+        assert_near(player.position.y, 2.0);
+
+        let up = PlayerInput { up: true, ..Default::default() };
+        run(&mut player, up, 0.0, 50, &solid);
+        assert_near(player.get_aabb().max.y, 4.5);
+
+        let down = PlayerInput { down: true, ..Default::default() };
+        run(&mut player, down, 0.0, 50, &solid);
+        assert_near(player.position.y, 0.5);
+    }
+
+    #[test]
+    fn noclip_moves_through_blocks_like_the_free_camera() {
+        let mut player = Player::new(Point3::new(0.0, 0.0, 0.0));
+        player.toggle_mode(MoveMode::Noclip);
+        run(&mut player, forward(), 0.0, 10, &|_| true);
+        assert_near(player.position.x, 10.0 * FLY_SPEED);
+        // Looking up moves up too
+        let mut player = Player::new(Point3::new(0.0, 0.0, 0.0));
+        player.toggle_mode(MoveMode::Noclip);
+        player.tick(&forward(), 0.0, 1.0, |_| true);
+        assert!(player.position.y > 0.0);
+    }
+
+    #[test]
+    fn toggling_a_mode_twice_walks_again() {
+        let mut player = Player::new(Point3::new(0.0, 0.0, 0.0));
+        player.toggle_mode(MoveMode::Fly);
+        assert_eq!(player.mode, MoveMode::Fly);
+        player.toggle_mode(MoveMode::Noclip);
+        assert_eq!(player.mode, MoveMode::Noclip);
+        player.toggle_mode(MoveMode::Noclip);
+        assert_eq!(player.mode, MoveMode::Walk);
+    }
+
+    #[test]
+    fn can_leave_a_block_it_is_stuck_in() {
+        let solid = world(&[Point3::new(0, 1, 0)]);
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        run(&mut player, forward(), 0.0, 20, &solid);
+        assert!(player.position.x > 1.0);
+    }
+
+    #[test]
+    fn eye_position_is_interpolated_between_ticks() {
+        let mut player = Player::new(Point3::new(0.0, 0.5, 0.0));
+        run(&mut player, forward(), 0.0, 1, &world(&[]));
+        assert_eq!(player.get_interpolated_eye_position(0.0), Point3::new(0.0, 0.5 + EYE_HEIGHT, 0.0));
+        assert_eq!(player.get_interpolated_eye_position(1.0), player.get_eye_position());
+        assert_near(player.get_interpolated_eye_position(0.5).x, WALK_SPEED / 2.0);
+    }
+
+    #[test]
+    fn simulation_is_deterministic() {
+        let solid = world(&[Point3::new(2, 1, 1), Point3::new(4, 1, 3), Point3::new(4, 2, 3)]);
+        let inputs = [forward(), PlayerInput { up: true, right: true, ..Default::default() }, PlayerInput::default()];
+        let simulate = || {
+            let mut player = Player::new(Point3::new(0.0, 3.0, 0.0));
+            for tick in 0..300 {
+                player.tick(&inputs[tick / 37 % inputs.len()], tick as f32 * 0.01, 0.0, &solid);
+            }
+            (player.position, player.velocity, player.on_ground)
+        };
+        assert_eq!(simulate(), simulate());
+    }
+}
@@ -0,0 +1,299 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::{block_types, chunk_manager::ChunkManager, direction::Direction};
+
+/// A block found by a ray.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit {
+    pub position: Point3<i32>,
+    /// The face of the block the ray entered through.
+    pub face: Direction,
+    /// How far along the ray the block was entered.
+    pub distance: f32,
+}
+
+impl RayHit {
+    /// The block in front of the face that was hit, where a new block would be placed.
+    pub fn get_adjacent(&self) -> Point3<i32> {
+        let offset = self.face.get_vec();
+        self.position + Vector3::new(offset.x as i32, offset.y as i32, offset.z as i32)
+    }
+}
+
+/// The first solid block along the ray, skipping air and fluids so blocks under water can be targeted.
+pub fn block_ray(manager: &ChunkManager, origin: Point3<f32>, direction: Vector3<f32>, max_distance: f32) -> Option<RayHit> {
+    cast(origin, direction, max_distance, |position| {
+        let block = manager.get_block(position);
+        block != 0 && block_types::get(block).get_fluid().is_none()
+    })
+}
+
+/// Walks the ray through every block it touches in order, stopping at the first one `is_target`
+/// accepts (Amanatides and Woo's voxel traversal). Blocks are centered on whole numbers. A ray
+/// starting inside a target hits it straight away, on the face it is heading away from.
+pub fn cast(origin: Point3<f32>, direction: Vector3<f32>, max_distance: f32, is_target: impl Fn(Point3<i32>) -> bool) -> Option<RayHit> {
+    if direction.magnitude2() == 0.0 || !max_distance.is_finite() {
+        return None;
+    }
+    let direction = direction.normalize();
+    // Shifted by half a block, so each block spans from its position to its position plus one
+    let start = origin.map(|value| value + 0.5);
+    let mut position = start.map(|value| value.floor() as i32);
+
+    let axes = [direction.x, direction.y, direction.z];
+    let step = axes.map(|value| if value > 0.0 { 1 } else if value < 0.0 { -1 } else { 0 });
+    let delta = axes.map(|value| if value == 0.0 { f32::INFINITY } else { 1.0 / value.abs() });
+    // Distance along the ray to the first block boundary on each axis
+    let mut next = [0.0f32; 3];
+    for axis in 0..3 {
+        let (start, cell) = (start[axis], position[axis] as f32);
+        next[axis] = match step[axis] {
+            1 => (cell + 1.0 - start) * delta[axis],
+            -1 => (start - cell) * delta[axis],
+            _ => f32::INFINITY,
+        };
+    }
+
+    if is_target(position) {
+        let axis = (0..3).max_by(|a, b| axes[*a].abs().total_cmp(&axes[*b].abs())).unwrap();
+        return Some(RayHit { position, face: get_face(axis, step[axis]), distance: 0.0 });
+    }
+
+    loop {
+        // Ties go to the first axis, so rays through edges and corners step one axis at a time
+        let mut axis = 0;
+        for other in 1..3 {
+            if next[other] < next[axis] {
+                axis = other;
+            }
+        }
+        let distance = next[axis];
+        if distance > max_distance {
+            return None;
+        }
+        position[axis] += step[axis];
+        next[axis] += delta[axis];
+        if is_target(position) {
+            return Some(RayHit { position, face: get_face(axis, step[axis]), distance });
+        }
+    }
+}
+
+/// The face looking back at a ray that stepped along `axis` in the `step` direction.
+fn get_face(axis: usize, step: i32) -> Direction {
+    match (axis, step > 0) {
+        (0, true) => Direction::EAST,
+        (0, false) => Direction::WEST,
+        (1, true) => Direction::DOWN,
+        (1, false) => Direction::UP,
+        (2, true) => Direction::NORTH,
+        _ => Direction::SOUTH,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::chunk::Chunk;
+
+    use super::*;
+
+    fn world(blocks: &[Point3<i32>]) -> impl Fn(Point3<i32>) -> bool {
+        let blocks: HashSet<Point3<i32>> = blocks.iter().copied().collect();
+        move |position| blocks.contains(&position)
+    }
+
+    fn assert_hit(hit: Option<RayHit>, position: Point3<i32>, face: Direction, distance: f32) {
+        let hit = hit.expect("Expected the ray to hit a block");
+        assert_eq!(hit.position, position);
+        assert_eq!(hit.face, face);
+        assert!((hit.distance - distance).abs() < 1e-4, "distance {} instead of {}", hit.distance, distance);
+    }
+
+    #[test]
+    fn axis_aligned_rays_hit_the_facing_side() {
+        let origin = Point3::new(0.0, 0.0, 0.0);
+        let cases = [
+            (Vector3::new(1.0, 0.0, 0.0), Point3::new(3, 0, 0), Direction::EAST),
+            (Vector3::new(-1.0, 0.0, 0.0), Point3::new(-3, 0, 0), Direction::WEST),
+            (Vector3::new(0.0, 1.0, 0.0), Point3::new(0, 3, 0), Direction::DOWN),
+            (Vector3::new(0.0, -1.0, 0.0), Point3::new(0, -3, 0), Direction::UP),
+            (Vector3::new(0.0, 0.0, 1.0), Point3::new(0, 0, 3), Direction::NORTH),
+            (Vector3::new(0.0, 0.0, -1.0), Point3::new(0, 0, -3), Direction::SOUTH),
+        ];
+        for (direction, block, face) in cases {
+            // Entered half a block before its center
+            assert_hit(cast(origin, direction, 10.0, world(&[block])), block, face, 2.5);
+        }
+    }
+
+    #[test]
+    fn hit_face_points_back_at_the_origin() {
+        for direction in Direction::ALL {
+            let offset = direction.get_vec();
+            let block = Point3::new(offset.x as i32 * 4, offset.y as i32 * 4, offset.z as i32 * 4);
+            let ray = Vector3::new(offset.x as f32, offset.y as f32, offset.z as f32);
+            let hit = cast(Point3::new(0.0, 0.0, 0.0), ray, 10.0, world(&[block])).unwrap();
+            assert_eq!(hit.face, direction.get_opposite());
+            assert_eq!(hit.get_adjacent(), block - Vector3::new(offset.x as i32, offset.y as i32, offset.z as i32));
+        }
+    }
+
+    #[test]
+    fn misses_beyond_max_distance() {
+        let blocks = world(&[Point3::new(5, 0, 0)]);
+        assert!(cast(Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 4.0, &blocks).is_none());
+        assert!(cast(Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 4.5, &blocks).is_some());
+    }
+
+    #[test]
+    fn misses_blocks_off_the_ray() {
+        let blocks = world(&[Point3::new(3, 1, 0), Point3::new(3, 0, 1), Point3::new(-3, 0, 0)]);
+        assert!(cast(Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 20.0, blocks).is_none());
+    }
+
+    #[test]
+    fn zero_direction_hits_nothing() {
+        assert!(cast(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0), 10.0, |_| true).is_none());
+    }
+
+    #[test]
+    fn starting_inside_a_block_hits_it() {
+        let hit = cast(Point3::new(0.2, 0.1, 0.0), Vector3::new(0.0, 0.0, -1.0), 10.0, world(&[Point3::new(0, 0, 0)]));
+        assert_hit(hit, Point3::new(0, 0, 0), Direction::SOUTH, 0.0);
+    }
+
+    #[test]
+    fn origin_off_center_is_measured_from_where_it_is() {
+        let hit = cast(Point3::new(0.3, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 10.0, world(&[Point3::new(2, 0, 0)]));
+        assert_hit(hit, Point3::new(2, 0, 0), Direction::EAST, 1.2);
+        // Block boundaries sit halfway between whole numbers
+        let hit = cast(Point3::new(0.49, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 10.0, world(&[Point3::new(1, 0, 0)]));
+        assert_hit(hit, Point3::new(1, 0, 0), Direction::EAST, 0.01);
+    }
+
+    #[test]
+    fn ray_length_does_not_matter() {
+        let blocks = world(&[Point3::new(0, 0, 6)]);
+        let short = cast(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.01), 10.0, &blocks);
+        let long = cast(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 100.0), 10.0, &blocks);
+        assert_eq!(short, long);
+        assert_hit(short, Point3::new(0, 0, 6), Direction::NORTH, 5.5);
+    }
+
+    #[test]
+    fn diagonal_ray_in_a_plane_visits_every_block() {
+        // Slightly off the exact diagonal so it never passes through a corner
+        let origin = Point3::new(0.0, 0.0, 0.1);
+        let direction = Vector3::new(1.0, 0.0, 1.0);
+        let visited = std::cell::RefCell::new(Vec::new());
+        cast(origin, direction, 3.0, |position| {
+            visited.borrow_mut().push(position);
+            false
+        });
+        let visited = visited.into_inner();
+        assert_eq!(visited[0], Point3::new(0, 0, 0));
+        // Each step moves exactly one block along one axis
+        for pair in visited.windows(2) {
+            let offset = pair[1] - pair[0];
+            assert_eq!(offset.x + offset.z, 1, "{:?} to {:?}", pair[0], pair[1]);
+            assert_eq!(offset.y, 0);
+        }
+        assert_eq!(&visited[..4], &[Point3::new(0, 0, 0), Point3::new(0, 0, 1), Point3::new(1, 0, 1), Point3::new(1, 0, 2)]);
+    }
+
+    #[test]
+    fn diagonal_rays_hit_the_side_they_cross_last() {
+        // Enters the block's column on z before lining up with it on x
+        let hit = cast(Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.5), 10.0, world(&[Point3::new(4, 0, 2)]));
+        let hit = hit.unwrap();
+        assert_eq!(hit.position, Point3::new(4, 0, 2));
+        assert_eq!(hit.face, Direction::EAST);
+        assert!((hit.distance - 3.5 * (1.25f32).sqrt()).abs() < 1e-4);
+
+        // Steep enough that it comes down through the top
+        let hit = cast(Point3::new(0.0, 5.0, 0.0), Vector3::new(0.2, -1.0, 0.1), 10.0, world(&[Point3::new(1, 0, 0)]));
+        assert_eq!(hit.unwrap().face, Direction::UP);
+    }
+
+    #[test]
+    fn diagonal_ray_through_3d_space() {
+        let direction = Vector3::new(1.0, 1.0, 1.0);
+        let origin = Point3::new(0.1, 0.0, -0.1);
+        let visited = std::cell::RefCell::new(Vec::new());
+        let hit = cast(origin, direction, 20.0, |position| {
+            visited.borrow_mut().push(position);
+            position == Point3::new(5, 5, 5)
+        });
+        assert_eq!(hit.unwrap().position, Point3::new(5, 5, 5));
+        // Three steps per block along the diagonal, one per axis
+        assert_eq!(visited.into_inner().len(), 16);
+    }
+
+    #[test]
+    fn exact_corner_crossings_step_one_axis_at_a_time() {
+        let visited = std::cell::RefCell::new(Vec::new());
+        cast(Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 0.0), 2.0, |position| {
+            visited.borrow_mut().push(position);
+            false
+        });
+        assert_eq!(&visited.into_inner()[..3], &[Point3::new(0, 0, 0), Point3::new(1, 0, 0), Point3::new(1, 1, 0)]);
+    }
+
+    #[test]
+    fn negative_coordinates() {
+        let hit = cast(Point3::new(-10.0, -10.0, -10.0), Vector3::new(0.0, 0.0, -1.0), 10.0, world(&[Point3::new(-10, -10, -17)]));
+        assert_hit(hit, Point3::new(-10, -10, -17), Direction::SOUTH, 6.5);
+    }
+
+    fn manager_with(blocks: &[(Point3<i32>, u32)]) -> ChunkManager {
+        block_types::init_for_tests();
+        let mut manager = ChunkManager::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    manager.add_chunk(Chunk::new_empty(Point3::new(x, y, z)));
+                }
+            }
+        }
+        for (position, block) in blocks {
+            manager.set_block(*position, *block);
+        }
+        manager
+    }
+
+    #[test]
+    fn crosses_chunk_boundaries() {
+        // The chunk at the origin covers -8 to 7, so these are in the chunks on either side
+        let manager = manager_with(&[(Point3::new(12, 0, 0), 1), (Point3::new(0, -13, 0), 1)]);
+        assert_hit(block_ray(&manager, Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 20.0), Point3::new(12, 0, 0), Direction::EAST, 11.5);
+        assert_hit(block_ray(&manager, Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, -1.0, 0.0), 20.0), Point3::new(0, -13, 0), Direction::UP, 12.5);
+        // Right on the boundary between two chunks
+        let manager = manager_with(&[(Point3::new(8, 3, 0), 1)]);
+        assert_hit(block_ray(&manager, Point3::new(0.0, 3.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 20.0), Point3::new(8, 3, 0), Direction::EAST, 7.5);
+    }
+
+    #[test]
+    fn diagonal_ray_across_chunks() {
+        let manager = manager_with(&[(Point3::new(-10, 9, 11), 1)]);
+        let direction = Vector3::new(-10.0, 9.0, 11.0);
+        let hit = block_ray(&manager, Point3::new(0.0, 0.0, 0.0), direction, 30.0).unwrap();
+        assert_eq!(hit.position, Point3::new(-10, 9, 11));
+    }
+
+    #[test]
+    fn unloaded_chunks_are_empty() {
+        let manager = manager_with(&[]);
+        assert!(block_ray(&manager, Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 100.0).is_none());
+    }
+
+    #[test]
+    fn fluids_are_passed_through() {
+        block_types::init_for_tests();
+        let water = 8;
+        assert!(block_types::get(water).get_fluid().is_some());
+        let manager = manager_with(&[(Point3::new(2, 0, 0), water), (Point3::new(3, 0, 0), water), (Point3::new(4, 0, 0), 1)]);
+        assert_hit(block_ray(&manager, Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), 10.0), Point3::new(4, 0, 0), Direction::EAST, 3.5);
+    }
+}
@@ -0,0 +1,313 @@
+//! Chunks are saved in region files, each holding a cube of `REGION_SIZE` chunks along every axis.
+//! A region file starts with a table giving, for every chunk in the region, the sector its data
+//! starts at and the data's length in bytes. The chunks' zlib compressed `PalettedBlocks` follow in
+//! sectors of `SECTOR_SIZE` bytes. A rewritten chunk reuses its sectors when it still fits in them, and is
+//! moved to the end of the file otherwise.
+
+use std::{collections::HashMap, fs::{self, File, OpenOptions}, io::{self, Read, Seek, SeekFrom, Write}, path::PathBuf};
+
+use cgmath::Point3;
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{chunk::{self, Chunk}, palette::PalettedBlocks};
+
+/// Chunks along each axis of a region.
+pub const REGION_SIZE: i32 = 32;
+const CHUNKS_PER_REGION: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+const SECTOR_SIZE: u64 = 4096;
+const MAGIC: &[u8; 4] = b"RGN2";
+/// Each chunk's entry in the table is its first sector and its length, as little endian `u32`s.
+const ENTRY_SIZE: u64 = 8;
+/// Sectors taken up by the magic number and the table. Sector 0 is among them, so it marks missing chunks.
+const HEADER_SECTORS: u32 = (MAGIC.len() as u64 + CHUNKS_PER_REGION as u64 * ENTRY_SIZE).div_ceil(SECTOR_SIZE) as u32;
+const BLOCKS_PER_CHUNK: usize = chunk::SIZE as usize * chunk::SIZE as usize * chunk::SIZE as usize;
+
+/// Saved chunks of one world, stored as region files in a directory.
+pub struct WorldStorage {
+    directory: PathBuf,
+    /// Region files that have been opened so far, by region position.
+    regions: HashMap<Point3<i32>, Region>,
+}
+
+impl WorldStorage {
+    /// The directory is only created once the first chunk is saved.
+    pub fn new(directory: impl Into<PathBuf>) -> WorldStorage {
+        WorldStorage {
+            directory: directory.into(),
+            regions: HashMap::new(),
+        }
+    }
+
+    /// The saved chunk at a chunk position, or `None` if it was never saved.
+    pub fn load_chunk(&mut self, position: Point3<i32>) -> io::Result<Option<Chunk>> {
+        let (region_position, index) = get_region_position(position);
+        if !self.regions.contains_key(&region_position) && !self.get_path(region_position).exists() {
+            return Ok(None);
+        }
+        match self.get_region(region_position)?.read(index)? {
+            Some(data) => decode(position, &data).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn save_chunk(&mut self, chunk: &Chunk) -> io::Result<()> {
+        let (region_position, index) = get_region_position(chunk.get_chunk_position());
+        let data = encode(chunk)?;
+        self.get_region(region_position)?.write(index, &data)
+    }
+
+    /// Makes sure everything saved so far has reached the disk.
+    pub fn flush(&mut self) -> io::Result<()> {
+        for region in self.regions.values_mut() {
+            region.file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn get_path(&self, region_position: Point3<i32>) -> PathBuf {
+        self.directory.join(format!("r.{}.{}.{}.region", region_position.x, region_position.y, region_position.z))
+    }
+
+    fn get_region(&mut self, region_position: Point3<i32>) -> io::Result<&mut Region> {
+        if !self.regions.contains_key(&region_position) {
+            fs::create_dir_all(&self.directory)?;
+            let region = Region::open(self.get_path(region_position))?;
+            self.regions.insert(region_position, region);
+        }
+        Ok(self.regions.get_mut(&region_position).unwrap())
+    }
+}
+
+/// The region a chunk position is in, and the chunk's index in the region's table.
+fn get_region_position(position: Point3<i32>) -> (Point3<i32>, usize) {
+    let region = position.map(|value| value.div_euclid(REGION_SIZE));
+    let local = position.map(|value| value.rem_euclid(REGION_SIZE) as usize);
+    let size = REGION_SIZE as usize;
+    (region, (local.x * size + local.y) * size + local.z)
+}
+
+struct Region {
+    file: File,
+    /// First sector and length in bytes of every chunk, the sector is 0 for chunks that were never saved.
+    entries: Vec<(u32, u32)>,
+    /// Sectors in the file, new data goes after them.
+    sector_count: u32,
+}
+
+impl Region {
+    /// Opens a region file, creating it with an empty table if it doesn't exist yet.
+    fn open(path: PathBuf) -> io::Result<Region> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        let length = file.metadata()?.len();
+        if length == 0 {
+            file.write_all(MAGIC)?;
+            file.set_len(HEADER_SECTORS as u64 * SECTOR_SIZE)?;
+            return Ok(Region { file, entries: vec![(0, 0); CHUNKS_PER_REGION], sector_count: HEADER_SECTORS });
+        }
+
+        let mut header = vec![0; MAGIC.len() + CHUNKS_PER_REGION * ENTRY_SIZE as usize];
+        file.read_exact(&mut header).map_err(|_| invalid_data(format!("{} is too short to be a region file", path.display())))?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(invalid_data(format!("{} is not a region file", path.display())));
+        }
+        let entries = header[MAGIC.len()..].chunks_exact(ENTRY_SIZE as usize)
+            .map(|entry| (
+                u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+                u32::from_le_bytes(entry[4..8].try_into().unwrap()),
+            ))
+            .collect();
+        let sector_count = length.div_ceil(SECTOR_SIZE) as u32;
+        Ok(Region { file, entries, sector_count: sector_count.max(HEADER_SECTORS) })
+    }
+
+    fn read(&mut self, index: usize) -> io::Result<Option<Vec<u8>>> {
+        let (sector, length) = self.entries[index];
+        if sector == 0 {
+            return Ok(None);
+        }
+        let mut data = vec![0; length as usize];
+        self.file.seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE))?;
+        self.file.read_exact(&mut data)?;
+        Ok(Some(data))
+    }
+
+    fn write(&mut self, index: usize, data: &[u8]) -> io::Result<()> {
+        let sectors = get_sectors(data.len() as u32);
+        let (old_sector, old_length) = self.entries[index];
+        let sector = if old_sector != 0 && sectors <= get_sectors(old_length) {
+            old_sector
+        } else {
+            let sector = self.sector_count;
+            self.sector_count += sectors;
+            self.file.set_len(self.sector_count as u64 * SECTOR_SIZE)?;
+            sector
+        };
+        self.file.seek(SeekFrom::Start(sector as u64 * SECTOR_SIZE))?;
+        self.file.write_all(data)?;
+
+        // The table is updated last, so a crash while moving a chunk leaves the old copy in place
+        self.entries[index] = (sector, data.len() as u32);
+        let mut entry = [0; ENTRY_SIZE as usize];
+        entry[0..4].copy_from_slice(&sector.to_le_bytes());
+        entry[4..8].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        self.file.seek(SeekFrom::Start(MAGIC.len() as u64 + index as u64 * ENTRY_SIZE))?;
+        self.file.write_all(&entry)
+    }
+}
+
+fn get_sectors(length: u32) -> u32 {
+    (length as u64).div_ceil(SECTOR_SIZE) as u32
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// The chunk's blocks the way `PalettedBlocks` writes them, compressed with zlib. Blocks that were
+/// replaced are dropped from the palette first, so they don't take up space in the save.
+fn encode(chunk: &Chunk) -> io::Result<Vec<u8>> {
+    let mut blocks = chunk.blocks.clone();
+    blocks.compact();
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    blocks.write(&mut encoder)?;
+    encoder.finish()
+}
+
+fn decode(position: Point3<i32>, data: &[u8]) -> io::Result<Chunk> {
+    let mut decoder = ZlibDecoder::new(data);
+    let blocks = PalettedBlocks::read(&mut decoder, BLOCKS_PER_CHUNK)
+        .map_err(|error| invalid_data(format!("Chunk {:?} is damaged: {}", position, error)))?;
+    if decoder.read(&mut [0])? != 0 {
+        return Err(invalid_data(format!("Chunk {:?} has data past its blocks", position)));
+    }
+    let mut chunk = Chunk::new_empty(position);
+    chunk.blocks = blocks;
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TestDirectory;
+
+    use super::*;
+
+    /// A chunk with blocks that differ everywhere, so mixed up blocks or chunks get noticed.
+    fn patterned_chunk(position: Point3<i32>, seed: u32) -> Chunk {
+        let mut chunk = Chunk::new_empty(position);
+        for index in 0..BLOCKS_PER_CHUNK {
+            chunk.blocks.set(index, (index as u32).wrapping_mul(2654435761).wrapping_add(seed) % 9);
+        }
+        chunk
+    }
+
+    #[test]
+    fn chunks_round_trip() {
+        let directory = TestDirectory::new("round_trip");
+        let mut storage = WorldStorage::new(directory.path());
+        let positions = [Point3::new(0, 0, 0), Point3::new(1, 2, 3), Point3::new(-1, -1, -1), Point3::new(31, 0, -32), Point3::new(100, -70, 5)];
+        for (seed, position) in positions.iter().enumerate() {
+            storage.save_chunk(&patterned_chunk(*position, seed as u32)).unwrap();
+        }
+        for (seed, position) in positions.iter().enumerate() {
+            let chunk = storage.load_chunk(*position).unwrap().unwrap();
+            assert_eq!(chunk.position, patterned_chunk(*position, seed as u32).position);
+            assert!(chunk.blocks == patterned_chunk(*position, seed as u32).blocks, "blocks of {:?} differ", position);
+        }
+    }
+
+    #[test]
+    fn chunks_survive_reopening() {
+        let directory = TestDirectory::new("reopen");
+        let chunk = patterned_chunk(Point3::new(3, -4, 5), 7);
+        {
+            let mut storage = WorldStorage::new(directory.path());
+            storage.save_chunk(&chunk).unwrap();
+            storage.flush().unwrap();
+        }
+        let mut storage = WorldStorage::new(directory.path());
+        assert!(storage.load_chunk(Point3::new(3, -4, 5)).unwrap().unwrap().blocks == chunk.blocks);
+        assert!(storage.load_chunk(Point3::new(3, -4, 6)).unwrap().is_none());
+    }
+
+    #[test]
+    fn missing_chunks_create_no_files() {
+        let directory = TestDirectory::new("missing");
+        let mut storage = WorldStorage::new(directory.path());
+        assert!(storage.load_chunk(Point3::new(0, 0, 0)).unwrap().is_none());
+        assert!(!directory.path().exists());
+    }
+
+    #[test]
+    fn rewriting_a_chunk_replaces_it() {
+        let directory = TestDirectory::new("rewrite");
+        let mut storage = WorldStorage::new(directory.path());
+        let position = Point3::new(2, 2, 2);
+        let neighbour = patterned_chunk(Point3::new(2, 2, 3), 1);
+
+        // An empty chunk compresses to almost nothing, the patterned one needs more sectors and moves
+        storage.save_chunk(&Chunk::new_empty(position)).unwrap();
+        storage.save_chunk(&neighbour).unwrap();
+        storage.save_chunk(&patterned_chunk(position, 2)).unwrap();
+        assert!(storage.load_chunk(position).unwrap().unwrap().blocks == patterned_chunk(position, 2).blocks);
+
+        // Back down to a smaller chunk, which fits where the patterned one was
+        let mut edited = Chunk::new_filled(Point3::new(2, 2, 2), 3);
+        edited.set_block(Point3::new(4, 5, 6), 8);
+        storage.save_chunk(&edited).unwrap();
+        assert!(storage.load_chunk(position).unwrap().unwrap().blocks == edited.blocks);
+        assert!(storage.load_chunk(neighbour.get_chunk_position()).unwrap().unwrap().blocks == neighbour.blocks);
+    }
+
+    #[test]
+    fn rewriting_in_place_does_not_grow_the_file() {
+        let directory = TestDirectory::new("in_place");
+        let mut storage = WorldStorage::new(directory.path());
+        storage.save_chunk(&patterned_chunk(Point3::new(0, 0, 0), 0)).unwrap();
+        let path = storage.get_path(Point3::new(0, 0, 0));
+        let length = fs::metadata(&path).unwrap().len();
+        for seed in 1..5 {
+            storage.save_chunk(&patterned_chunk(Point3::new(0, 0, 0), seed)).unwrap();
+        }
+        assert_eq!(fs::metadata(&path).unwrap().len(), length);
+    }
+
+    #[test]
+    fn regions_cover_cubes_of_chunks() {
+        assert_eq!(get_region_position(Point3::new(0, 0, 0)), (Point3::new(0, 0, 0), 0));
+        assert_eq!(get_region_position(Point3::new(0, 0, 1)), (Point3::new(0, 0, 0), 1));
+        assert_eq!(get_region_position(Point3::new(31, 31, 31)), (Point3::new(0, 0, 0), CHUNKS_PER_REGION - 1));
+        assert_eq!(get_region_position(Point3::new(32, 0, 0)), (Point3::new(1, 0, 0), 0));
+        assert_eq!(get_region_position(Point3::new(-1, 0, 0)).0, Point3::new(-1, 0, 0));
+        assert_eq!(get_region_position(Point3::new(-32, -33, 0)).0, Point3::new(-1, -2, 0));
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_regions() {
+        let directory = TestDirectory::new("corrupt");
+        fs::create_dir_all(directory.path()).unwrap();
+        let mut storage = WorldStorage::new(directory.path());
+        fs::write(storage.get_path(Point3::new(0, 0, 0)), b"not a region file").unwrap();
+        let error = storage.load_chunk(Point3::new(1, 1, 1)).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn replaced_blocks_are_not_saved() {
+        let mut chunk = Chunk::new_empty(Point3::new(0, 0, 0));
+        for block in 1..100 {
+            chunk.set_block(Point3::new(1, 1, 1), block);
+        }
+        chunk.set_block(Point3::new(1, 1, 1), 0);
+        let loaded = decode(Point3::new(0, 0, 0), &encode(&chunk).unwrap()).unwrap();
+        assert_eq!(loaded.blocks.get_uniform(), Some(0));
+        assert!(loaded.blocks == chunk.blocks);
+    }
+
+    #[test]
+    fn rejects_chunks_of_the_wrong_size() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[1, 2, 3]).unwrap();
+        let data = encoder.finish().unwrap();
+        assert_eq!(decode(Point3::new(0, 0, 0), &data).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,219 @@
+/// A range handed out by an `Allocator`, in whatever unit the allocator was created with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Allocation {
+    pub offset: u32,
+    pub size: u32,
+}
+
+impl Allocation {
+    pub fn get_end(&self) -> u32 {
+        self.offset + self.size
+    }
+}
+
+/// First fit free list allocator used to place chunk meshes inside one large buffer. Only does the
+/// bookkeeping, the memory itself lives wherever the caller keeps it.
+pub struct Allocator {
+    capacity: u32,
+    /// Free ranges sorted by offset, never touching each other since neighbours are merged.
+    free: Vec<Allocation>,
+}
+
+impl Allocator {
+    pub fn new(capacity: u32) -> Allocator {
+        let mut free = Vec::new();
+        if capacity > 0 {
+            free.push(Allocation { offset: 0, size: capacity });
+        }
+        Allocator { capacity, free }
+    }
+
+    /// Reserves `size` units at the lowest offset they fit, or `None` when no free range is large enough.
+    pub fn allocate(&mut self, size: u32) -> Option<Allocation> {
+        if size == 0 {
+            return None;
+        }
+        let index = self.free.iter().position(|range| range.size >= size)?;
+        let range = &mut self.free[index];
+        let allocation = Allocation { offset: range.offset, size };
+        range.offset += size;
+        range.size -= size;
+        if range.size == 0 {
+            self.free.remove(index);
+        }
+        Some(allocation)
+    }
+
+    /// Returns an allocation to the free list, merging it with the free ranges on either side.
+    pub fn free(&mut self, allocation: Allocation) {
+        if allocation.size == 0 {
+            return;
+        }
+        let index = self.free.partition_point(|range| range.offset < allocation.offset);
+        debug_assert!(allocation.get_end() <= self.capacity, "Freed {:?} outside of the allocator", allocation);
+        debug_assert!(index == self.free.len() || allocation.get_end() <= self.free[index].offset, "Double free of {:?}", allocation);
+        debug_assert!(index == 0 || self.free[index - 1].get_end() <= allocation.offset, "Double free of {:?}", allocation);
+
+        let merges_next = index < self.free.len() && self.free[index].offset == allocation.get_end();
+        let merges_previous = index > 0 && self.free[index - 1].get_end() == allocation.offset;
+        match (merges_previous, merges_next) {
+            (true, true) => {
+                self.free[index - 1].size += allocation.size + self.free[index].size;
+                self.free.remove(index);
+            }
+            (true, false) => self.free[index - 1].size += allocation.size,
+            (false, true) => {
+                self.free[index].offset = allocation.offset;
+                self.free[index].size += allocation.size;
+            }
+            (false, false) => self.free.insert(index, allocation),
+        }
+    }
+
+    /// Adds space to the end, keeping every existing allocation where it is.
+    pub fn grow(&mut self, capacity: u32) {
+        if capacity <= self.capacity {
+            return;
+        }
+        let added = Allocation { offset: self.capacity, size: capacity - self.capacity };
+        self.capacity = capacity;
+        self.free(added);
+    }
+
+    pub fn get_capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    pub fn get_free_space(&self) -> u32 {
+        self.free.iter().map(|range| range.size).sum()
+    }
+
+    pub fn get_used_space(&self) -> u32 {
+        self.capacity - self.get_free_space()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocations_are_packed_from_the_start() {
+        let mut allocator = Allocator::new(100);
+        assert_eq!(allocator.allocate(10), Some(Allocation { offset: 0, size: 10 }));
+        assert_eq!(allocator.allocate(20), Some(Allocation { offset: 10, size: 20 }));
+        assert_eq!(allocator.allocate(70), Some(Allocation { offset: 30, size: 70 }));
+        assert_eq!(allocator.get_free_space(), 0);
+        assert_eq!(allocator.get_used_space(), 100);
+    }
+
+    #[test]
+    fn allocation_fails_when_full() {
+        let mut allocator = Allocator::new(100);
+        assert!(allocator.allocate(101).is_none());
+        allocator.allocate(60).unwrap();
+        assert!(allocator.allocate(41).is_none());
+        assert!(allocator.allocate(40).is_some());
+        assert!(allocator.allocate(1).is_none());
+    }
+
+    #[test]
+    fn empty_allocations_are_refused() {
+        let mut allocator = Allocator::new(100);
+        assert!(allocator.allocate(0).is_none());
+        assert!(Allocator::new(0).allocate(1).is_none());
+    }
+
+    #[test]
+    fn freed_space_is_reused() {
+        let mut allocator = Allocator::new(100);
+        let a = allocator.allocate(30).unwrap();
+        allocator.allocate(70).unwrap();
+        allocator.free(a);
+        assert_eq!(allocator.allocate(20), Some(Allocation { offset: 0, size: 20 }));
+        assert_eq!(allocator.allocate(10), Some(Allocation { offset: 20, size: 10 }));
+    }
+
+    #[test]
+    fn first_fit_picks_lowest_offset() {
+        let mut allocator = Allocator::new(100);
+        let a = allocator.allocate(10).unwrap();
+        allocator.allocate(10).unwrap();
+        let c = allocator.allocate(30).unwrap();
+        allocator.allocate(10).unwrap();
+        allocator.free(c);
+        allocator.free(a);
+        // Too large for the gap at 0, fits the one at 20
+        assert_eq!(allocator.allocate(15), Some(Allocation { offset: 20, size: 15 }));
+        assert_eq!(allocator.allocate(5), Some(Allocation { offset: 0, size: 5 }));
+    }
+
+    #[test]
+    fn neighbours_merge_when_freed() {
+        let mut allocator = Allocator::new(30);
+        let a = allocator.allocate(10).unwrap();
+        let b = allocator.allocate(10).unwrap();
+        let c = allocator.allocate(10).unwrap();
+        allocator.free(a);
+        allocator.free(c);
+        assert!(allocator.allocate(30).is_none());
+        // Merges with the free range on both sides
+        allocator.free(b);
+        assert_eq!(allocator.allocate(30), Some(Allocation { offset: 0, size: 30 }));
+    }
+
+    #[test]
+    fn merges_in_any_order() {
+        for order in [[0, 1, 2, 3], [3, 2, 1, 0], [1, 3, 0, 2], [2, 0, 3, 1]] {
+            let mut allocator = Allocator::new(40);
+            let allocations: Vec<Allocation> = (0..4).map(|_| allocator.allocate(10).unwrap()).collect();
+            for index in order {
+                allocator.free(allocations[index]);
+            }
+            assert_eq!(allocator.get_free_space(), 40);
+            assert_eq!(allocator.allocate(40), Some(Allocation { offset: 0, size: 40 }), "order {:?}", order);
+        }
+    }
+
+    #[test]
+    fn fragmented_space_is_not_contiguous() {
+        let mut allocator = Allocator::new(40);
+        let allocations: Vec<Allocation> = (0..4).map(|_| allocator.allocate(10).unwrap()).collect();
+        allocator.free(allocations[0]);
+        allocator.free(allocations[2]);
+        assert_eq!(allocator.get_free_space(), 20);
+        assert!(allocator.allocate(20).is_none());
+    }
+
+    #[test]
+    fn growing_keeps_allocations_and_extends_the_tail() {
+        let mut allocator = Allocator::new(30);
+        allocator.allocate(10).unwrap();
+        allocator.allocate(15).unwrap();
+        allocator.grow(60);
+        assert_eq!(allocator.get_capacity(), 60);
+        // The 5 units left at the end join the new space
+        assert_eq!(allocator.allocate(35), Some(Allocation { offset: 25, size: 35 }));
+        assert_eq!(allocator.get_free_space(), 0);
+    }
+
+    #[test]
+    fn growing_a_full_allocator() {
+        let mut allocator = Allocator::new(10);
+        allocator.allocate(10).unwrap();
+        allocator.grow(20);
+        assert_eq!(allocator.allocate(10), Some(Allocation { offset: 10, size: 10 }));
+        allocator.grow(5);
+        assert_eq!(allocator.get_capacity(), 20);
+    }
+
+    #[test]
+    #[should_panic(expected = "Double free")]
+    fn double_free_is_caught() {
+        let mut allocator = Allocator::new(30);
+        let a = allocator.allocate(10).unwrap();
+        allocator.allocate(10).unwrap();
+        allocator.free(a);
+        allocator.free(a);
+    }
+}
@@ -0,0 +1,79 @@
+use log::info;
+use wgpu::{Buffer, BufferUsages, Device, Queue};
+
+use super::allocator::{Allocation, Allocator};
+
+/// One large GPU buffer split between many owners by an `Allocator`, counted in elements of a fixed size.
+/// Doubles in size when it runs out of room, copying what was already uploaded.
+pub struct BufferPool {
+    buffer: Buffer,
+    allocator: Allocator,
+    element_size: u64,
+    usage: BufferUsages,
+    label: &'static str,
+}
+
+impl BufferPool {
+    pub fn new(device: &Device, label: &'static str, element_size: u64, capacity: u32, usage: BufferUsages) -> BufferPool {
+        let usage = usage | BufferUsages::COPY_DST | BufferUsages::COPY_SRC;
+        BufferPool {
+            buffer: create_buffer(device, label, element_size * capacity as u64, usage),
+            allocator: Allocator::new(capacity),
+            element_size,
+            usage,
+            label,
+        }
+    }
+
+    /// Reserves room for `count` elements, growing the buffer if needed. Returns `None` for empty allocations.
+    pub fn allocate(&mut self, device: &Device, queue: &Queue, count: u32) -> Option<Allocation> {
+        if count == 0 {
+            return None;
+        }
+        if let Some(allocation) = self.allocator.allocate(count) {
+            return Some(allocation);
+        }
+
+        let old_capacity = self.allocator.get_capacity();
+        let mut capacity = old_capacity.max(1);
+        while capacity - old_capacity < count {
+            capacity *= 2;
+        }
+        info!(
+            "Growing {} from {} to {} elements, {} of which are in use",
+            self.label, old_capacity, capacity, self.allocator.get_used_space(),
+        );
+
+        let buffer = create_buffer(device, self.label, self.element_size * capacity as u64, self.usage);
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Buffer Pool Copy Encoder"),
+        });
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, &buffer, 0, self.element_size * old_capacity as u64);
+        queue.submit(std::iter::once(encoder.finish()));
+        self.buffer = buffer;
+        self.allocator.grow(capacity);
+        self.allocator.allocate(count)
+    }
+
+    pub fn free(&mut self, allocation: Allocation) {
+        self.allocator.free(allocation);
+    }
+
+    pub fn write<T: bytemuck::Pod>(&self, queue: &Queue, allocation: Allocation, data: &[T]) {
+        debug_assert_eq!(std::mem::size_of_val(data) as u64, self.element_size * allocation.size as u64);
+        queue.write_buffer(&self.buffer, self.element_size * allocation.offset as u64, bytemuck::cast_slice(data));
+    }
+
+    pub fn get_buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+}
+
+fn create_buffer(device: &Device, label: &str, size: u64, usage: BufferUsages) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage,
+        mapped_at_creation: false,
+    })
+}
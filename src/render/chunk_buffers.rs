@@ -1,48 +1,305 @@
-use wgpu::{Buffer, Device};
+use cgmath::{MetricSpace, Point3};
+use log::info;
+use wgpu::{util::{DeviceExt, DrawIndexedIndirect}, Buffer, Device, Queue};
 
-use crate::{chunk_manager::ChunkManager, chunk::Chunk};
+use crate::{chunk::{self, Chunk}, Vertex};
 
-use super::chunk_builder;
+use super::{allocator::Allocation, buffer_pool::BufferPool, chunk_builder::{self, ChunkGeometry}, frustum::Aabb, quad, visibility::VisibilitySet};
+
+/// Enough quads for every face of every block in a chunk, more than any single pass can have.
+const MAX_QUADS: u32 = chunk::SIZE as u32 * chunk::SIZE as u32 * chunk::SIZE as u32 * 6;
+/// Starting sizes of the pools, which grow when they fill up.
+const VERTEX_POOL_CAPACITY: u32 = 1 << 20;
+const INSTANCE_POOL_CAPACITY: u32 = 256;
+
+/// Per chunk data handed to the vertex shader as a single instance.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ChunkInstance {
+    /// World position of the chunk's minimum corner, which vertex positions are relative to.
+    origin: [f32; 3],
+    /// Size of a mesh cell in blocks, from the level of detail the chunk was meshed at.
+    scale: f32,
+}
+
+impl ChunkInstance {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<ChunkInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x3,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32,
+                },
+            ],
+        }
+    }
+}
+
+/// Where a chunk's geometry lives in the shared pools, split by how it has to be blended.
+/// Each part is `None` when the chunk has no quads of that kind.
+pub struct ChunkMesh {
+    pub opaque: Option<Allocation>,
+    /// Drawn like opaque geometry, but fully transparent pixels are discarded.
+    pub cutout: Option<Allocation>,
+    /// Alpha blended without writing depth, so it has to be drawn back to front.
+    pub translucent: Option<Allocation>,
+    instance: Allocation,
+    pub lod: u8,
+    pub aabb: Aabb,
+    pub visibility: VisibilitySet,
+    /// Vertices of each translucent quad with its center in world space.
+    translucent_quads: Vec<([Vertex; 4], Point3<f32>)>,
+    sorted_from: Option<Point3<i32>>,
+}
+
+impl ChunkMesh {
+    pub fn is_empty(&self) -> bool {
+        self.opaque.is_none() && self.cutout.is_none() && self.translucent.is_none()
+    }
+
+    pub fn get_center(&self) -> Point3<f32> {
+        self.aabb.min + (self.aabb.max - self.aabb.min) / 2.0
+    }
+
+    fn get_draw(&self, vertices: Option<Allocation>) -> Option<DrawIndexedIndirect> {
+        let vertices = vertices?;
+        Some(DrawIndexedIndirect {
+            vertex_count: vertices.size / 4 * quad::INDICES.len() as u32,
+            instance_count: 1,
+            base_index: 0,
+            vertex_offset: vertices.offset as i32,
+            base_instance: self.instance.offset,
+        })
+    }
+}
+
+/// Draw commands for one pass, written to the indirect buffer by `ChunkBuffers::prepare_draws`.
+pub struct DrawList {
+    offset: u64,
+    draws: Vec<DrawIndexedIndirect>,
+}
+
+/// The draws for every pass of a frame.
+pub struct ChunkDraws {
+    pub opaque: DrawList,
+    pub cutout: DrawList,
+    pub translucent: DrawList,
+}
+
+/// Totals over every chunk mesh, for the debug overlay.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MeshStats {
+    pub meshes: usize,
+    pub vertices: u64,
+    pub indices: u64,
+}
 
 pub struct ChunkBuffers {
-    buffers: Vec<(Buffer, Buffer, u32)>
+    buffers: Vec<ChunkMesh>,
+    /// Every chunk mesh is a list of quads, so they all share one index buffer.
+    index_buffer: Buffer,
+    vertex_pool: BufferPool,
+    instance_pool: BufferPool,
+    /// Only used when the device can draw every chunk in a pass with one `multi_draw_indexed_indirect` call.
+    indirect_buffer: Option<Buffer>,
+    indirect_capacity: u32,
 }
 
 impl ChunkBuffers {
-    pub fn new(device: &Device, manager: &ChunkManager) -> ChunkBuffers {
-        let mut buffers: Vec<(Buffer, Buffer, u32)> = Vec::new();
-        for chunk in &manager.chunks {
-            buffers.push(chunk_builder::build(device, chunk));
+    /// Starts out without any meshes, they are added by `ChunkManager::remesh`.
+    pub fn new(device: &Device) -> ChunkBuffers {
+        let indices: Vec<u32> = (0..MAX_QUADS).flat_map(|index| quad::INDICES.map(|offset| index * 4 + offset)).collect();
+        let multi_draw = device.features().contains(wgpu::Features::MULTI_DRAW_INDIRECT | wgpu::Features::INDIRECT_FIRST_INSTANCE);
+        info!("Drawing chunks with {}", if multi_draw { "multi draw indirect" } else { "one draw call each" });
+
+        let mut chunk_buffers = ChunkBuffers {
+            buffers: Vec::new(),
+            index_buffer: device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Quad Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX,
+            }),
+            vertex_pool: BufferPool::new(
+                device,
+                "Chunk Vertex Pool",
+                std::mem::size_of::<Vertex>() as u64,
+                VERTEX_POOL_CAPACITY,
+                wgpu::BufferUsages::VERTEX,
+            ),
+            instance_pool: BufferPool::new(
+                device,
+                "Chunk Instance Pool",
+                std::mem::size_of::<ChunkInstance>() as u64,
+                INSTANCE_POOL_CAPACITY,
+                wgpu::BufferUsages::VERTEX,
+            ),
+            indirect_buffer: None,
+            indirect_capacity: 0,
+        };
+        if multi_draw {
+            chunk_buffers.indirect_buffer = Some(create_indirect_buffer(device, INSTANCE_POOL_CAPACITY));
+            chunk_buffers.indirect_capacity = INSTANCE_POOL_CAPACITY;
         }
-        ChunkBuffers {
-            buffers
+        chunk_buffers
+    }
+
+    pub fn update_chunk(&mut self, device: &Device, queue: &Queue, index: usize, chunk: &Chunk, lod: u8) {
+        let mesh = self.upload(device, queue, chunk_builder::build(chunk, lod));
+        if index < self.buffers.len() {
+            let old = std::mem::replace(&mut self.buffers[index], mesh);
+            self.release(old);
+        } else {
+            self.buffers.push(mesh);
         }
     }
 
-    pub fn refresh(&mut self, device: &Device, manager: &ChunkManager) {
-        self.buffers.clear();
-        for chunk in &manager.chunks {
-            self.buffers.push(chunk_builder::build(device, chunk));
+    /// Frees the mesh of an unloaded chunk. Like the chunks, the meshes after it move down by one.
+    pub fn remove_chunk(&mut self, index: usize) {
+        let mesh = self.buffers.remove(index);
+        self.release(mesh);
+    }
+
+    fn upload(&mut self, device: &Device, queue: &Queue, geometry: ChunkGeometry) -> ChunkMesh {
+        let translucent: Vec<Vertex> = geometry.translucent.iter().flat_map(|(vertices, _)| *vertices).collect();
+        let instance = self.instance_pool.allocate(device, queue, 1).unwrap();
+        self.instance_pool.write(queue, instance, &[ChunkInstance {
+            origin: geometry.origin.into(),
+            scale: (1 << geometry.lod) as f32,
+        }]);
+        ChunkMesh {
+            opaque: self.upload_vertices(device, queue, &geometry.opaque),
+            cutout: self.upload_vertices(device, queue, &geometry.cutout),
+            translucent: self.upload_vertices(device, queue, &translucent),
+            instance,
+            lod: geometry.lod,
+            aabb: geometry.aabb,
+            visibility: geometry.visibility,
+            translucent_quads: geometry.translucent,
+            sorted_from: None,
         }
     }
 
-    pub fn update_chunk(&mut self, device: &Device, index: usize, chunk: &Chunk) {
-        if index < self.buffers.len() {
-            self.buffers[index] = chunk_builder::build(device, chunk);
-        } else {
-            self.buffers.push(chunk_builder::build(device, chunk));
+    fn upload_vertices(&mut self, device: &Device, queue: &Queue, vertices: &[Vertex]) -> Option<Allocation> {
+        let allocation = self.vertex_pool.allocate(device, queue, vertices.len() as u32)?;
+        self.vertex_pool.write(queue, allocation, vertices);
+        Some(allocation)
+    }
+
+    fn release(&mut self, mesh: ChunkMesh) {
+        for allocation in [mesh.opaque, mesh.cutout, mesh.translucent].into_iter().flatten() {
+            self.vertex_pool.free(allocation);
+        }
+        self.instance_pool.free(mesh.instance);
+    }
+
+    /// Reorders the translucent quads of a chunk from furthest to closest to the camera. Only redone
+    /// once the camera has moved to another block, since the order barely changes within one.
+    pub fn sort_translucent(&mut self, queue: &Queue, index: usize, camera_pos: Point3<f32>) {
+        let mesh = &mut self.buffers[index];
+        let block_pos = camera_pos.map(|value| value.round() as i32);
+        let allocation = match mesh.translucent {
+            Some(allocation) if mesh.sorted_from != Some(block_pos) => allocation,
+            _ => return,
+        };
+        mesh.translucent_quads.sort_by(|(_, a), (_, b)| {
+            camera_pos.distance2(*b).total_cmp(&camera_pos.distance2(*a))
+        });
+        let vertices: Vec<Vertex> = mesh.translucent_quads.iter().flat_map(|(vertices, _)| *vertices).collect();
+        self.vertex_pool.write(queue, allocation, &vertices);
+        mesh.sorted_from = Some(block_pos);
+    }
+
+    /// Builds the draw commands for the chunks drawn in each pass, uploading them to the indirect
+    /// buffer when multi draw is supported. Translucent chunks are drawn in the order given.
+    pub fn prepare_draws(&mut self, device: &Device, queue: &Queue, drawn: &[usize], translucent_order: &[usize]) -> ChunkDraws {
+        let list = |offset: u64, order: &[usize], pick: fn(&ChunkMesh) -> Option<Allocation>| {
+            let draws: Vec<DrawIndexedIndirect> = order.iter()
+                .filter_map(|index| self.buffers[*index].get_draw(pick(&self.buffers[*index])))
+                .collect();
+            DrawList { offset, draws }
+        };
+        let stride = std::mem::size_of::<DrawIndexedIndirect>() as u64;
+        let opaque = list(0, drawn, |mesh| mesh.opaque);
+        let cutout = list(opaque.draws.len() as u64 * stride, drawn, |mesh| mesh.cutout);
+        let translucent = list(cutout.offset + cutout.draws.len() as u64 * stride, translucent_order, |mesh| mesh.translucent);
+        let draws = ChunkDraws { opaque, cutout, translucent };
+
+        if self.indirect_buffer.is_some() {
+            let count = (draws.opaque.draws.len() + draws.cutout.draws.len() + draws.translucent.draws.len()) as u32;
+            if count > self.indirect_capacity {
+                self.indirect_capacity = count.next_power_of_two();
+                self.indirect_buffer = Some(create_indirect_buffer(device, self.indirect_capacity));
+            }
+            let bytes: Vec<u8> = [&draws.opaque, &draws.cutout, &draws.translucent].iter()
+                .flat_map(|list| list.draws.iter())
+                .flat_map(|draw| draw.as_bytes().to_vec())
+                .collect();
+            if !bytes.is_empty() {
+                queue.write_buffer(self.indirect_buffer.as_ref().unwrap(), 0, &bytes);
+            }
+        }
+        draws
+    }
+
+    /// Draws a list from `prepare_draws` with whichever pipeline is currently set.
+    pub fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, list: &DrawList) {
+        if list.draws.is_empty() {
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.vertex_pool.get_buffer().slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_pool.get_buffer().slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        match &self.indirect_buffer {
+            Some(indirect_buffer) => render_pass.multi_draw_indexed_indirect(indirect_buffer, list.offset, list.draws.len() as u32),
+            None => {
+                for draw in &list.draws {
+                    render_pass.draw_indexed(
+                        draw.base_index..draw.base_index + draw.vertex_count,
+                        draw.vertex_offset,
+                        draw.base_instance..draw.base_instance + draw.instance_count,
+                    );
+                }
+            }
         }
     }
 
-    pub fn get_buffers(&self) -> &Vec<(wgpu::Buffer, wgpu::Buffer, u32)> {
+    pub fn get_buffers(&self) -> &Vec<ChunkMesh> {
         &self.buffers
     }
 
-    pub fn get_buffer(&self, index: usize) -> &(Buffer, Buffer, u32) {
+    pub fn get_buffer(&self, index: usize) -> &ChunkMesh {
         &self.buffers[index]
     }
 
     pub fn get_buffer_count(&self) -> usize {
         self.buffers.len()
     }
-}
\ No newline at end of file
+
+    pub fn get_stats(&self) -> MeshStats {
+        let mut stats = MeshStats { meshes: self.get_buffer_count(), ..MeshStats::default() };
+        for mesh in &self.buffers {
+            for vertices in [mesh.opaque, mesh.cutout, mesh.translucent].into_iter().flatten() {
+                stats.vertices += vertices.size as u64;
+                stats.indices += (vertices.size / 4 * quad::INDICES.len() as u32) as u64;
+            }
+        }
+        stats
+    }
+}
+
+fn create_indirect_buffer(device: &Device, capacity: u32) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Chunk Indirect Buffer"),
+        size: capacity as u64 * std::mem::size_of::<DrawIndexedIndirect>() as u64,
+        usage: wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
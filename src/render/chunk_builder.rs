@@ -1,67 +1,179 @@
-use cgmath::{Vector3, Point3};
-use wgpu::{util::DeviceExt, Device};
+use cgmath::{EuclideanSpace, Point3, Vector3};
 
-use crate::{chunk::Chunk, direction::Direction, Vertex};
+use crate::{block_types::{self, RenderType}, chunk::{self, Chunk}, direction::Direction, Vertex};
 use crate::render::quad::Quad;
 
-use super::quad::block_quad;
+use super::{frustum::Aabb, lod::LodGrid, quad::block_quad, visibility::{self, VisibilitySet}};
 
-pub fn build(device: &Device, chunk: &Chunk) -> (wgpu::Buffer, wgpu::Buffer, u32) {
-    let chunk_size = chunk.blocks.len();
+/// How far skirts reach down past the surface, in cells of the chunk's level of detail.
+pub const SKIRT_CELLS: u8 = 1;
+
+/// A chunk's meshed geometry on the CPU, before `ChunkBuffers` uploads it.
+pub struct ChunkGeometry {
+    /// World position vertex positions are relative to, the chunk's minimum corner at full detail
+    /// and a cell before it at coarser levels, to leave room for skirts.
+    pub origin: Point3<f32>,
+    /// Level of detail the chunk was meshed at, vertex positions are in cells of `2^lod` blocks.
+    pub lod: u8,
+    pub opaque: Vec<Vertex>,
+    pub cutout: Vec<Vertex>,
+    /// Vertices of each translucent quad with its center in world space, so they can be sorted.
+    pub translucent: Vec<([Vertex; 4], Point3<f32>)>,
+    pub aabb: Aabb,
+    pub visibility: VisibilitySet,
+}
+
+/// Meshes the chunk at the given level of detail. Coarser levels merge blocks into cells with
+/// `LodGrid` and mesh the cells like blocks.
+///
+/// Faces on the chunk's border are always kept since neighbouring chunks aren't looked at. Between
+/// chunks of different levels the surfaces don't line up, so coarser levels also hang a skirt one
+/// cell down from the top of every side face on the border. Skirts can reach below the chunk, which
+/// is why those levels start their vertex positions a cell before the chunk's corner.
+pub fn build(chunk: &Chunk, lod: u8) -> ChunkGeometry {
+    let chunk_size = chunk::SIZE as usize;
     let pos = chunk.position;
+    let scale = 1 << lod;
+    let grid = if lod > 0 { Some(LodGrid::downsample(chunk, scale)) } else { None };
+    // Chunks of only air have nothing to mesh
+    let cell_count = match &grid {
+        _ if chunk.blocks.get_uniform() == Some(0) => 0,
+        Some(grid) => grid.get_size(),
+        None => chunk_size as i16,
+    };
+    let get_cell = |pos: Point3<i16>| match &grid {
+        Some(grid) => grid.get(pos),
+        None => chunk.get_block_s(pos),
+    };
+
+    // Blocks are centered on whole numbers, so their corners are half a block off the chunk's bounds
+    let half = chunk_size as f32 / 2.0 + 0.5;
+    let padding = if lod > 0 { SKIRT_CELLS } else { 0 };
+    let origin = Point3::new(pos.x as f32 - half, pos.y as f32 - half, pos.z as f32 - half)
+        .map(|value| value - (padding * scale) as f32);
 
-    let mut quads: Vec<Quad> = Vec::new();
-    let mut index = 0;
-    for x in 0..chunk_size {
-        for y in 0..chunk.blocks[x].len() {
-            for z in 0..chunk.blocks[x][y].len() {
-                if chunk.get_block(Point3::new(x as u8, y as u8, z as u8)) == 0 {
+    // One list of quads per render pass
+    let mut opaque: Vec<Quad> = Vec::new();
+    let mut cutout: Vec<Quad> = Vec::new();
+    let mut translucent: Vec<Quad> = Vec::new();
+    for x in 0..cell_count {
+        for y in 0..cell_count {
+            for z in 0..cell_count {
+                let block = get_cell(Point3::new(x, y, z));
+                if block == 0 {
                     continue;
                 }
-                let mut count = 0;
-
-                if chunk.get_block(Point3::new(x as u8, y as u8 + 1, z as u8)) == 0 {
-                    quads.push(block_quad(chunk.get_block(Point3::new(x as u8, y as u8, z as u8)), count + index, Direction::UP,  Vector3::new((pos.x + x as i32) as f32 - (chunk_size as f32 / 2.0), (pos.y + y as i32) as f32 + 0.5 - (chunk_size as f32 / 2.0), (pos.z + z as i32) as f32 - (chunk_size as f32 / 2.0))));
-                    count += 1;
-                } if chunk.get_block_s(Point3::new(x as i16, y as i16 - 1, z as i16)) == 0 {
-                    quads.push(block_quad(chunk.get_block(Point3::new(x as u8, y as u8, z as u8)), count + index, Direction::DOWN,  Vector3::new((pos.x + x as i32) as f32 - (chunk_size as f32 / 2.0), (pos.y + y as i32) as f32 - 0.5 - (chunk_size as f32 / 2.0), (pos.z + z as i32) as f32 - (chunk_size as f32 / 2.0))));
-                    count += 1;
-                } if chunk.get_block_s(Point3::new(x as i16, y as i16, z as i16 - 1)) == 0 {
-                    quads.push(block_quad(chunk.get_block(Point3::new(x as u8, y as u8, z as u8)), count + index, Direction::NORTH, Vector3::new((pos.x + x as i32) as f32 - (chunk_size as f32 / 2.0), (pos.y + y as i32) as f32 - (chunk_size as f32 / 2.0), (pos.z + z as i32) as f32 - 0.5 - (chunk_size as f32 / 2.0))));
-                    count += 1;
-                } if chunk.get_block(Point3::new(x as u8, y as u8, z as u8 + 1)) == 0 {
-                    quads.push(block_quad(chunk.get_block(Point3::new(x as u8, y as u8, z as u8)), count + index, Direction::SOUTH, Vector3::new((pos.x + x as i32) as f32 - (chunk_size as f32 / 2.0), (pos.y + y as i32) as f32 - (chunk_size as f32 / 2.0), (pos.z + z as i32) as f32 + 0.5 - (chunk_size as f32 / 2.0))));
-                    count += 1;
-                } if chunk.get_block(Point3::new(x as u8 + 1, y as u8, z as u8)) == 0 {
-                    quads.push(block_quad(chunk.get_block(Point3::new(x as u8, y as u8, z as u8)), count + index, Direction::WEST, Vector3::new((pos.x + x as i32) as f32 + 0.5 - (chunk_size as f32 / 2.0), (pos.y + y as i32) as f32 - (chunk_size as f32 / 2.0), (pos.z + z as i32) as f32 - (chunk_size as f32 / 2.0))));
-                    count += 1;
-                } if chunk.get_block_s(Point3::new(x as i16 - 1, y as i16, z as i16)) == 0 {
-                    quads.push(block_quad(chunk.get_block(Point3::new(x as u8, y as u8, z as u8)), count + index, Direction::EAST, Vector3::new((pos.x + x as i32) as f32 - 0.5 - (chunk_size as f32 / 2.0), (pos.y + y as i32) as f32 - (chunk_size as f32 / 2.0), (pos.z + z as i32) as f32 - (chunk_size as f32 / 2.0))));
-                    count += 1;
+                let quads = match block_types::get(block).get_render_type() {
+                    RenderType::Opaque => &mut opaque,
+                    RenderType::Cutout => &mut cutout,
+                    RenderType::Translucent => &mut translucent,
+                };
+
+                for direction in Direction::ALL {
+                    let offset = direction.get_vec();
+                    let neighbour_pos = Point3::new(x + offset.x as i16, y + offset.y as i16, z + offset.z as i16);
+                    if !is_face_visible(block, get_cell(neighbour_pos)) {
+                        continue;
+                    }
+                    let light = chunk.light.get(neighbour_pos.map(|value| value * scale as i16));
+                    let position = Point3::new(x as u8, y as u8, z as u8).map(|value| value + padding);
+                    quads.push(block_quad(block, direction, position, light));
+
+                    let on_border = neighbour_pos.map(|value| value < 0 || value >= cell_count);
+                    let is_side = offset.y == 0;
+                    if padding > 0 && is_side && (on_border.x || on_border.z) && get_cell(Point3::new(x, y - 1, z)) == 0 {
+                        quads.push(block_quad(block, direction, position - Vector3::new(0, SKIRT_CELLS, 0), light));
+                    }
                 }
-                index += count;
             }
         }
     }
 
-    let mut vertices: Vec<Vertex> = vec![];
-    let mut indices: Vec<u32> = vec![];
-    for quad in quads.iter() {
-        vertices.extend(quad.vertices);
-        indices.extend(quad.indices);
+    // Empty chunks fall back to the chunk's bounds, they are never drawn anyway
+    let to_world = |position: Point3<f32>| origin + position.to_vec() * scale as f32;
+    let all_vertices = opaque.iter().chain(cutout.iter()).chain(translucent.iter()).flat_map(|quad| quad.vertices);
+    let aabb = Aabb::from_points(all_vertices.map(|vertex| to_world(vertex.get_position().map(|value| value as f32))))
+        .unwrap_or(Aabb::new(origin, origin + Vector3::new(chunk_size as f32, chunk_size as f32, chunk_size as f32)));
+
+    ChunkGeometry {
+        origin,
+        lod,
+        opaque: opaque.iter().flat_map(|quad| quad.vertices).collect(),
+        cutout: cutout.iter().flat_map(|quad| quad.vertices).collect(),
+        translucent: translucent.iter().map(|quad| (quad.vertices, to_world(quad.get_center()))).collect(),
+        aabb,
+        visibility: visibility::compute(chunk, |block| block_types::get(block).is_opaque()),
+    }
+}
+
+/// A face is hidden behind opaque blocks and between two blocks of the same type, like water next to water.
+fn is_face_visible(block: u32, neighbour: u32) -> bool {
+    neighbour == 0 || (neighbour != block && !block_types::get(neighbour).is_opaque())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::block_types;
+
+    use super::*;
+
+    const STONE: u32 = 3;
+
+    /// A chunk at the origin with its bottom `height` blocks filled with stone.
+    fn ground(height: u8) -> Chunk {
+        let mut chunk = Chunk::new_empty(Point3::new(0, 0, 0));
+        for x in 0..chunk::SIZE {
+            for y in 0..height {
+                for z in 0..chunk::SIZE {
+                    chunk.set_block(Point3::new(x, y, z), STONE);
+                }
+            }
+        }
+        chunk
+    }
+
+    #[test]
+    fn full_detail_has_no_skirts() {
+        block_types::init_for_tests();
+        let geometry = build(&ground(8), 0);
+        // The top and bottom, and four sides of 16 by 8 faces
+        assert_eq!(geometry.opaque.len(), (2 * 16 * 16 + 4 * 16 * 8) * 4);
+        assert_eq!(geometry.aabb.min, Point3::new(-8.5, -8.5, -8.5));
+    }
+
+    #[test]
+    fn coarser_levels_hang_skirts_on_the_border() {
+        block_types::init_for_tests();
+        let geometry = build(&ground(8), 1);
+        let faces = 2 * 8 * 8 + 4 * 8 * 4;
+        // One skirt under the lowest face of each column on the four sides
+        let skirts = 4 * 8;
+        assert_eq!(geometry.opaque.len(), (faces + skirts) * 4);
+        let below = geometry.opaque.iter().filter(|vertex| vertex.get_position().y == 0).count();
+        assert_eq!(below, skirts * 2);
+        // The skirts reach a cell of two blocks below the chunk
+        assert_eq!(geometry.origin, Point3::new(-10.5, -10.5, -10.5));
+        assert_eq!(geometry.aabb.min.y, -10.5);
+        assert_eq!(geometry.aabb.max.y, -0.5);
+    }
+
+    #[test]
+    fn skirts_hang_under_overhangs() {
+        block_types::init_for_tests();
+        let mut chunk = Chunk::new_empty(Point3::new(0, 0, 0));
+        // A floating slab one cell thick at level 2, so its skirts hang into the air under it
+        for x in 0..chunk::SIZE {
+            for y in 8..12 {
+                for z in 0..chunk::SIZE {
+                    chunk.set_block(Point3::new(x, y, z), STONE);
+                }
+            }
+        }
+        let geometry = build(&chunk, 2);
+        let faces = 2 * 4 * 4 + 4 * 4;
+        assert_eq!(geometry.opaque.len(), (faces + 4 * 4) * 4);
+        // The slab is cell 2, one cell up for the padding, and the skirts go down to the cell below
+        let lowest = geometry.opaque.iter().map(|vertex| vertex.get_position().y).min();
+        assert_eq!(lowest, Some(2));
     }
-    
-    (
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        }),
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX,
-        }),
-        indices.len() as u32,
-    )
 }
@@ -0,0 +1,100 @@
+use crate::{block_types::Fluid, chunk};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FogMode {
+    Linear,
+    Exponential,
+    ExponentialSquared,
+}
+
+impl FogMode {
+    /// The id `shader.wgsl` uses to pick the fog formula.
+    pub fn get_id(&self) -> u32 {
+        match self {
+            FogMode::Linear => 0,
+            FogMode::Exponential => 1,
+            FogMode::ExponentialSquared => 2,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub start: f32,
+    pub end: f32,
+    /// Colour to use instead of the sky's horizon colour, e.g. when the camera is inside a fluid.
+    pub color: Option<[f32; 3]>,
+}
+
+impl Fog {
+    /// Fog that hides the edge of the loaded chunks, starting at `start` of the way there.
+    pub fn from_render_distance(mode: FogMode, render_distance: i32, start: f32) -> Fog {
+        let end = ((render_distance * chunk::SIZE as i32) as f32 - chunk::SIZE as f32 / 2.0).max(1.0);
+        Fog {
+            mode,
+            start: end * start,
+            end,
+            color: None,
+        }
+    }
+
+    /// Thick fog in the colour of the fluid the camera is inside of.
+    pub fn from_fluid(fluid: &Fluid) -> Fog {
+        Fog {
+            mode: FogMode::Exponential,
+            start: 0.0,
+            end: fluid.fog_distance,
+            color: Some(fluid.fog_color),
+        }
+    }
+
+    /// Density for the exponential modes, chosen so the fog is 95% opaque at `end`.
+    pub fn get_density(&self) -> f32 {
+        match self.mode {
+            FogMode::Linear => 0.0,
+            FogMode::Exponential => 3.0 / self.end,
+            FogMode::ExponentialSquared => 3.0f32.sqrt() / self.end,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fog_ends_before_the_last_chunks() {
+        let fog = Fog::from_render_distance(FogMode::Linear, 6, 0.6);
+        // Half a chunk short of six chunks, so the edge of the outermost ones is hidden
+        assert_eq!(fog.end, 88.0);
+        assert!((fog.start - 52.8).abs() < 1e-4);
+        assert_eq!(fog.color, None);
+        assert_eq!(fog.get_density(), 0.0);
+
+        let fog = Fog::from_render_distance(FogMode::Exponential, 0, 0.5);
+        assert_eq!((fog.start, fog.end), (0.5, 1.0));
+    }
+
+    #[test]
+    fn exponential_fog_is_almost_opaque_at_its_end() {
+        for mode in [FogMode::Exponential, FogMode::ExponentialSquared] {
+            let fog = Fog::from_render_distance(mode, 4, 0.6);
+            let density = fog.get_density();
+            let visibility = match mode {
+                FogMode::Exponential => (-density * fog.end).exp(),
+                _ => (-(density * fog.end).powi(2)).exp(),
+            };
+            assert!((visibility - 0.05).abs() < 0.001, "{:?} lets {} through", mode, visibility);
+        }
+    }
+
+    #[test]
+    fn fluid_fog_takes_the_fluids_colour() {
+        let fog = Fog::from_fluid(&Fluid { fog_color: [0.1, 0.2, 0.3], fog_distance: 12.0 });
+        assert_eq!(fog.mode, FogMode::Exponential);
+        assert_eq!((fog.start, fog.end), (0.0, 12.0));
+        assert_eq!(fog.color, Some([0.1, 0.2, 0.3]));
+        assert_eq!(fog.get_density(), 0.25);
+    }
+}
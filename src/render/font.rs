@@ -0,0 +1,230 @@
+use image::{Rgba, RgbaImage};
+
+/// Size of a glyph's cell in the atlas, in pixels.
+pub const GLYPH_SIZE: u32 = 8;
+const FIRST_CHAR: char = ' ';
+const COLUMNS: u32 = 16;
+/// The printable ASCII characters, followed by one cell that is all white for untextured quads.
+const CELLS: u32 = GLYPHS.len() as u32 + 1;
+const ROWS: u32 = CELLS.div_ceil(COLUMNS);
+pub const ATLAS_WIDTH: u32 = COLUMNS * GLYPH_SIZE;
+pub const ATLAS_HEIGHT: u32 = ROWS * GLYPH_SIZE;
+/// Advance of a space, which has no pixels to measure.
+const SPACE_WIDTH: u32 = 4;
+
+/// Where a character's pixels are in the atlas, trimmed to the columns it uses so text can be
+/// spaced by each glyph's own width.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Glyph {
+    /// Top left corner in the atlas, in pixels.
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+}
+
+impl Glyph {
+    /// Horizontal distance to the next glyph, leaving a column free between the two.
+    pub fn get_advance(&self) -> u32 {
+        self.width + 1
+    }
+}
+
+/// Glyph of a character, with characters outside of printable ASCII drawn as `?`.
+pub fn get_glyph(character: char) -> Glyph {
+    let index = match character {
+        ' '..='~' => character as usize - FIRST_CHAR as usize,
+        _ => '?' as usize - FIRST_CHAR as usize,
+    };
+    let (x, y) = get_cell(index as u32);
+    let rows = GLYPHS[index];
+    let used = rows.iter().fold(0u8, |used, row| used | row);
+    if used == 0 {
+        return Glyph { x, y, width: SPACE_WIDTH };
+    }
+    // Bit 0 is the leftmost column
+    let left = used.trailing_zeros();
+    let right = 7 - used.leading_zeros();
+    Glyph { x: x + left, y, width: right - left + 1 }
+}
+
+/// Width of a line of text in pixels at a scale of 1, without the gap after the last glyph.
+pub fn get_text_width(text: &str) -> u32 {
+    let width: u32 = text.chars().map(|character| get_glyph(character).get_advance()).sum();
+    width.saturating_sub(1)
+}
+
+/// Center of the all white cell, for quads with a flat colour.
+pub fn get_white_pixel() -> (u32, u32) {
+    let (x, y) = get_cell(GLYPHS.len() as u32);
+    (x + GLYPH_SIZE / 2, y + GLYPH_SIZE / 2)
+}
+
+/// White glyphs on a transparent background, so they can be tinted any colour.
+pub fn create_atlas() -> RgbaImage {
+    let mut atlas = RgbaImage::from_pixel(ATLAS_WIDTH, ATLAS_HEIGHT, Rgba([255, 255, 255, 0]));
+    for (index, rows) in GLYPHS.iter().enumerate() {
+        let (x, y) = get_cell(index as u32);
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..GLYPH_SIZE {
+                if bits & (1 << column) != 0 {
+                    atlas.put_pixel(x + column, y + row as u32, Rgba([255; 4]));
+                }
+            }
+        }
+    }
+    let (x, y) = get_cell(GLYPHS.len() as u32);
+    for row in 0..GLYPH_SIZE {
+        for column in 0..GLYPH_SIZE {
+            atlas.put_pixel(x + column, y + row, Rgba([255; 4]));
+        }
+    }
+    atlas
+}
+
+fn get_cell(index: u32) -> (u32, u32) {
+    ((index % COLUMNS) * GLYPH_SIZE, (index / COLUMNS) * GLYPH_SIZE)
+}
+
+/// The public domain font8x8 glyphs for `' '` to `'~'`, one byte per row from the top.
+#[rustfmt::skip]
+const GLYPHS: [[u8; 8]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00], // !
+    [0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+    [0x36, 0x36, 0x7F, 0x36, 0x7F, 0x36, 0x36, 0x00], // #
+    [0x0C, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x0C, 0x00], // $
+    [0x00, 0x63, 0x33, 0x18, 0x0C, 0x66, 0x63, 0x00], // %
+    [0x1C, 0x36, 0x1C, 0x6E, 0x3B, 0x33, 0x6E, 0x00], // &
+    [0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], // '
+    [0x18, 0x0C, 0x06, 0x06, 0x06, 0x0C, 0x18, 0x00], // (
+    [0x06, 0x0C, 0x18, 0x18, 0x18, 0x0C, 0x06, 0x00], // )
+    [0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00], // *
+    [0x00, 0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ,
+    [0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00], // .
+    [0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x01, 0x00], // /
+    [0x3E, 0x63, 0x73, 0x7B, 0x6F, 0x67, 0x3E, 0x00], // 0
+    [0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00], // 1
+    [0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00], // 2
+    [0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00], // 3
+    [0x38, 0x3C, 0x36, 0x33, 0x7F, 0x30, 0x78, 0x00], // 4
+    [0x3F, 0x03, 0x1F, 0x30, 0x30, 0x33, 0x1E, 0x00], // 5
+    [0x1C, 0x06, 0x03, 0x1F, 0x33, 0x33, 0x1E, 0x00], // 6
+    [0x3F, 0x33, 0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x00], // 7
+    [0x1E, 0x33, 0x33, 0x1E, 0x33, 0x33, 0x1E, 0x00], // 8
+    [0x1E, 0x33, 0x33, 0x3E, 0x30, 0x18, 0x0E, 0x00], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x00], // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ;
+    [0x18, 0x0C, 0x06, 0x03, 0x06, 0x0C, 0x18, 0x00], // <
+    [0x00, 0x00, 0x3F, 0x00, 0x00, 0x3F, 0x00, 0x00], // =
+    [0x06, 0x0C, 0x18, 0x30, 0x18, 0x0C, 0x06, 0x00], // >
+    [0x1E, 0x33, 0x30, 0x18, 0x0C, 0x00, 0x0C, 0x00], // ?
+    [0x3E, 0x63, 0x7B, 0x7B, 0x7B, 0x03, 0x1E, 0x00], // @
+    [0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00], // A
+    [0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00], // B
+    [0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00], // C
+    [0x1F, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1F, 0x00], // D
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x46, 0x7F, 0x00], // E
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x06, 0x0F, 0x00], // F
+    [0x3C, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7C, 0x00], // G
+    [0x33, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x33, 0x00], // H
+    [0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // I
+    [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x00], // J
+    [0x67, 0x66, 0x36, 0x1E, 0x36, 0x66, 0x67, 0x00], // K
+    [0x0F, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7F, 0x00], // L
+    [0x63, 0x77, 0x7F, 0x7F, 0x6B, 0x63, 0x63, 0x00], // M
+    [0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63, 0x63, 0x00], // N
+    [0x1C, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1C, 0x00], // O
+    [0x3F, 0x66, 0x66, 0x3E, 0x06, 0x06, 0x0F, 0x00], // P
+    [0x1E, 0x33, 0x33, 0x33, 0x3B, 0x1E, 0x38, 0x00], // Q
+    [0x3F, 0x66, 0x66, 0x3E, 0x36, 0x66, 0x67, 0x00], // R
+    [0x1E, 0x33, 0x07, 0x0E, 0x38, 0x33, 0x1E, 0x00], // S
+    [0x3F, 0x2D, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // T
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F, 0x00], // U
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // V
+    [0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00], // W
+    [0x63, 0x63, 0x36, 0x1C, 0x1C, 0x36, 0x63, 0x00], // X
+    [0x33, 0x33, 0x33, 0x1E, 0x0C, 0x0C, 0x1E, 0x00], // Y
+    [0x7F, 0x63, 0x31, 0x18, 0x4C, 0x66, 0x7F, 0x00], // Z
+    [0x1E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1E, 0x00], // [
+    [0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00], // \
+    [0x1E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1E, 0x00], // ]
+    [0x08, 0x1C, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF], // _
+    [0x0C, 0x0C, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // `
+    [0x00, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // a
+    [0x07, 0x06, 0x06, 0x3E, 0x66, 0x66, 0x3B, 0x00], // b
+    [0x00, 0x00, 0x1E, 0x33, 0x03, 0x33, 0x1E, 0x00], // c
+    [0x38, 0x30, 0x30, 0x3E, 0x33, 0x33, 0x6E, 0x00], // d
+    [0x00, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00], // e
+    [0x1C, 0x36, 0x06, 0x0F, 0x06, 0x06, 0x0F, 0x00], // f
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x1F], // g
+    [0x07, 0x06, 0x36, 0x6E, 0x66, 0x66, 0x67, 0x00], // h
+    [0x0C, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // i
+    [0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E], // j
+    [0x07, 0x06, 0x66, 0x36, 0x1E, 0x36, 0x67, 0x00], // k
+    [0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // l
+    [0x00, 0x00, 0x33, 0x7F, 0x7F, 0x6B, 0x63, 0x00], // m
+    [0x00, 0x00, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x00], // n
+    [0x00, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // o
+    [0x00, 0x00, 0x3B, 0x66, 0x66, 0x3E, 0x06, 0x0F], // p
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x78], // q
+    [0x00, 0x00, 0x3B, 0x6E, 0x66, 0x06, 0x0F, 0x00], // r
+    [0x00, 0x00, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x00], // s
+    [0x08, 0x0C, 0x3E, 0x0C, 0x0C, 0x2C, 0x18, 0x00], // t
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00], // u
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // v
+    [0x00, 0x00, 0x63, 0x6B, 0x7F, 0x7F, 0x36, 0x00], // w
+    [0x00, 0x00, 0x63, 0x36, 0x1C, 0x36, 0x63, 0x00], // x
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F], // y
+    [0x00, 0x00, 0x3F, 0x19, 0x0C, 0x26, 0x3F, 0x00], // z
+    [0x38, 0x0C, 0x0C, 0x07, 0x0C, 0x0C, 0x38, 0x00], // {
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // |
+    [0x07, 0x0C, 0x0C, 0x38, 0x0C, 0x0C, 0x07, 0x00], // }
+    [0x6E, 0x3B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ~
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glyphs_are_trimmed_to_their_pixels() {
+        // `l` uses columns 1 to 4
+        let l = get_glyph('l');
+        assert_eq!(l.width, 4);
+        assert_eq!(l.x % GLYPH_SIZE, 1);
+        assert_eq!(get_glyph('_').width, 8);
+        assert!(get_glyph('i').width < get_glyph('m').width);
+        assert_eq!(get_glyph(' ').width, SPACE_WIDTH);
+    }
+
+    #[test]
+    fn unknown_characters_are_question_marks() {
+        assert_eq!(get_glyph('é'), get_glyph('?'));
+        assert_eq!(get_glyph('\n'), get_glyph('?'));
+    }
+
+    #[test]
+    fn text_width_adds_up_the_glyphs() {
+        assert_eq!(get_text_width(""), 0);
+        assert_eq!(get_text_width("l"), 4);
+        assert_eq!(get_text_width("ll"), 9);
+        assert_eq!(get_text_width("a b"), get_glyph('a').width + 1 + SPACE_WIDTH + 1 + get_glyph('b').width);
+    }
+
+    #[test]
+    fn atlas_holds_every_glyph_and_the_white_cell() {
+        let atlas = create_atlas();
+        assert_eq!(atlas.dimensions(), (ATLAS_WIDTH, ATLAS_HEIGHT));
+        let (x, y) = get_white_pixel();
+        assert_eq!(atlas.get_pixel(x, y).0, [255; 4]);
+        // The bottom row of `_` is filled, its top isn't
+        let underscore = get_glyph('_');
+        assert_eq!(atlas.get_pixel(underscore.x, underscore.y + 7).0[3], 255);
+        assert_eq!(atlas.get_pixel(underscore.x, underscore.y).0[3], 0);
+        // Every glyph cell fits
+        assert!(get_cell(CELLS - 1).1 + GLYPH_SIZE <= ATLAS_HEIGHT);
+    }
+}
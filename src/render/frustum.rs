@@ -0,0 +1,147 @@
+use cgmath::{Matrix, Matrix4, Point3, Vector4};
+
+/// Axis aligned bounding box in world space.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /// The smallest box containing every point, or `None` if there are no points.
+    pub fn from_points<I: IntoIterator<Item = Point3<f32>>>(points: I) -> Option<Aabb> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        let mut aabb = Aabb::new(first, first);
+        for point in points {
+            aabb.min = Point3::new(aabb.min.x.min(point.x), aabb.min.y.min(point.y), aabb.min.z.min(point.z));
+            aabb.max = Point3::new(aabb.max.x.max(point.x), aabb.max.y.max(point.y), aabb.max.z.max(point.z));
+        }
+        Some(aabb)
+    }
+}
+
+/// The six planes bounding everything the camera can see, each stored as `(a, b, c, d)`
+/// where a point is inside when `a * x + b * y + c * z + d >= 0`.
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from an OpenGL style view projection matrix, like the one built by `Camera`.
+    pub fn from_matrix(matrix: Matrix4<f32>) -> Frustum {
+        let (row0, row1, row2, row3) = (matrix.row(0), matrix.row(1), matrix.row(2), matrix.row(3));
+        Frustum {
+            planes: [
+                row3 + row0, // Left
+                row3 - row0, // Right
+                row3 + row1, // Bottom
+                row3 - row1, // Top
+                row3 + row2, // Near
+                row3 - row2, // Far
+            ],
+        }
+    }
+
+    /// Whether any part of the box may be visible. Boxes close to the corners of the
+    /// frustum can be reported as visible even when they aren't, but never the other way around.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        for plane in &self.planes {
+            // The corner of the box furthest along the plane's normal
+            let x = if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x };
+            let y = if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y };
+            let z = if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z };
+            if plane.x * x + plane.y * y + plane.z * z + plane.w < 0.0 {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Vector3};
+
+    use super::*;
+
+    fn frustum_looking_at(eye: Point3<f32>, target: Point3<f32>) -> Frustum {
+        let view = Matrix4::look_at_rh(eye, target, Vector3::unit_y());
+        let proj = cgmath::perspective(Deg(70.0), 1.0, 0.1, 100.0);
+        Frustum::from_matrix(proj * view)
+    }
+
+    fn cube(center: Point3<f32>, half_size: f32) -> Aabb {
+        Aabb::new(
+            center - Vector3::new(half_size, half_size, half_size),
+            center + Vector3::new(half_size, half_size, half_size),
+        )
+    }
+
+    #[test]
+    fn box_in_front_is_visible() {
+        let frustum = frustum_looking_at(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0));
+        assert!(frustum.intersects_aabb(&cube(Point3::new(0.0, 0.0, -10.0), 1.0)));
+    }
+
+    #[test]
+    fn box_behind_is_culled() {
+        let frustum = frustum_looking_at(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0));
+        assert!(!frustum.intersects_aabb(&cube(Point3::new(0.0, 0.0, 10.0), 1.0)));
+    }
+
+    #[test]
+    fn boxes_to_the_sides_are_culled() {
+        let frustum = frustum_looking_at(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0));
+        assert!(!frustum.intersects_aabb(&cube(Point3::new(-30.0, 0.0, -10.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&cube(Point3::new(30.0, 0.0, -10.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&cube(Point3::new(0.0, 30.0, -10.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&cube(Point3::new(0.0, -30.0, -10.0), 1.0)));
+    }
+
+    #[test]
+    fn box_beyond_far_plane_is_culled() {
+        let frustum = frustum_looking_at(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0));
+        assert!(!frustum.intersects_aabb(&cube(Point3::new(0.0, 0.0, -150.0), 1.0)));
+    }
+
+    #[test]
+    fn box_crossing_a_plane_is_visible() {
+        let frustum = frustum_looking_at(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0));
+        // Mostly off to the left, but its right edge pokes into view
+        let aabb = Aabb::new(Point3::new(-40.0, -1.0, -11.0), Point3::new(-6.0, 1.0, -9.0));
+        assert!(frustum.intersects_aabb(&aabb));
+        // Straddles the far plane
+        assert!(frustum.intersects_aabb(&cube(Point3::new(0.0, 0.0, -100.0), 5.0)));
+    }
+
+    #[test]
+    fn box_around_camera_is_visible() {
+        let frustum = frustum_looking_at(Point3::new(5.0, 5.0, 5.0), Point3::new(6.0, 5.0, 5.0));
+        assert!(frustum.intersects_aabb(&cube(Point3::new(5.0, 5.0, 5.0), 8.0)));
+    }
+
+    #[test]
+    fn culling_follows_camera_direction() {
+        let frustum = frustum_looking_at(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0));
+        assert!(frustum.intersects_aabb(&cube(Point3::new(20.0, 0.0, 0.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&cube(Point3::new(-20.0, 0.0, 0.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&cube(Point3::new(0.0, 0.0, -20.0), 1.0)));
+    }
+
+    #[test]
+    fn aabb_from_points() {
+        let aabb = Aabb::from_points([
+            Point3::new(1.0, -2.0, 3.0),
+            Point3::new(-1.0, 4.0, 0.0),
+            Point3::new(0.0, 0.0, 5.0),
+        ]);
+        assert_eq!(aabb, Some(Aabb::new(Point3::new(-1.0, -2.0, 0.0), Point3::new(1.0, 4.0, 5.0))));
+        assert_eq!(Aabb::from_points(Vec::new()), None);
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+
+use cgmath::Point3;
+
+use crate::chunk::{self, Chunk};
+
+use super::chunk_builder;
+
+/// Outer edge of each level of detail ring past the render distance, in chunks. Chunks in the first
+/// ring are meshed in cells of 2x2x2 blocks, then 4x4x4 and 8x8x8.
+pub const DEFAULT_DISTANCES: [i32; 3] = [1, 2, 4];
+/// The coarsest level, which merges 8 blocks along every axis into one.
+pub const MAX_LEVEL: usize = 3;
+
+/// How far chunks are loaded and drawn, in chunks from the camera's chunk, when they are drawn at
+/// full detail up to `render_distance` and at lower levels of detail out to the `lod_distances` rings.
+pub fn get_view_distance(render_distance: i32, lod_distances: &[i32]) -> i32 {
+    render_distance + lod_distances.last().copied().unwrap_or(0)
+}
+
+/// Far enough to see the corners of the furthest loaded chunks from anywhere in the camera's chunk,
+/// along with the skirts hanging under them.
+pub fn get_far_plane(render_distance: i32, lod_distances: &[i32]) -> f32 {
+    let skirt = chunk_builder::SKIRT_CELLS as i32 * (1 << lod_distances.len());
+    let reach = (get_view_distance(render_distance, lod_distances) + 1) * chunk::SIZE as i32 + skirt;
+    reach as f32 * 3f32.sqrt()
+}
+
+/// The level of detail for a chunk `distance` chunks away from the camera's chunk. Level 0 is full
+/// detail, each level after it merges twice as many blocks along every axis into one.
+pub fn get_level(distance: i32, render_distance: i32, lod_distances: &[i32]) -> u8 {
+    if distance <= render_distance {
+        return 0;
+    }
+    match lod_distances.iter().position(|ring| distance <= render_distance + *ring) {
+        Some(index) => index as u8 + 1,
+        None => lod_distances.len() as u8,
+    }
+}
+
+/// Distance between two chunk positions, measured like the cube of chunks that is loaded around the camera.
+pub fn get_distance(a: Point3<i32>, b: Point3<i32>) -> i32 {
+    (a.x - b.x).abs().max((a.y - b.y).abs()).max((a.z - b.z).abs())
+}
+
+/// A chunk's blocks merged into cells of `scale` blocks along each axis.
+pub struct LodGrid {
+    size: i16,
+    cells: Vec<u32>,
+}
+
+impl LodGrid {
+    /// Each cell becomes its most common block, or air when less than half of it is solid.
+    pub fn downsample(chunk: &Chunk, scale: u8) -> LodGrid {
+        let size = chunk::SIZE / scale;
+        let mut cells = Vec::with_capacity(size as usize * size as usize * size as usize);
+        for x in 0..size {
+            for y in 0..size {
+                for z in 0..size {
+                    let mut counts: HashMap<u32, u32> = HashMap::new();
+                    for dx in 0..scale {
+                        for dy in 0..scale {
+                            for dz in 0..scale {
+                                let block = chunk.get_block(Point3::new(x * scale + dx, y * scale + dy, z * scale + dz));
+                                if block != 0 {
+                                    *counts.entry(block).or_insert(0) += 1;
+                                }
+                            }
+                        }
+                    }
+                    let solid: u32 = counts.values().sum();
+                    let volume = scale as u32 * scale as u32 * scale as u32;
+                    // Ties go to the lowest id, so the result doesn't depend on the map's order
+                    let block = match counts.into_iter().max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(b.cmp(a))) {
+                        Some((block, _)) if solid * 2 >= volume => block,
+                        _ => 0,
+                    };
+                    cells.push(block);
+                }
+            }
+        }
+        LodGrid { size: size as i16, cells }
+    }
+
+    pub fn get_size(&self) -> i16 {
+        self.size
+    }
+
+    /// The cell at a position in cells, or air outside of the grid.
+    pub fn get(&self, pos: Point3<i16>) -> u32 {
+        if pos.x < 0 || pos.y < 0 || pos.z < 0 || pos.x >= self.size || pos.y >= self.size || pos.z >= self.size {
+            return 0;
+        }
+        self.cells[((pos.x * self.size + pos.y) * self.size + pos.z) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_follow_the_rings() {
+        let rings = &DEFAULT_DISTANCES;
+        for render_distance in [1, 2, 8] {
+            assert_eq!(get_level(0, render_distance, rings), 0);
+            assert_eq!(get_level(render_distance, render_distance, rings), 0);
+            assert_eq!(get_level(render_distance + 1, render_distance, rings), 1);
+            for (index, ring) in rings.iter().enumerate() {
+                assert_eq!(get_level(render_distance + *ring, render_distance, rings), index as u8 + 1);
+            }
+            let view_distance = get_view_distance(render_distance, rings);
+            assert_eq!(get_level(view_distance, render_distance, rings), rings.len() as u8);
+            assert_eq!(get_level(view_distance + 10, render_distance, rings), rings.len() as u8);
+        }
+    }
+
+    #[test]
+    fn rings_are_configurable() {
+        assert_eq!(get_view_distance(3, &[2, 6]), 9);
+        assert_eq!(get_level(5, 3, &[2, 6]), 1);
+        assert_eq!(get_level(6, 3, &[2, 6]), 2);
+        // Without rings everything is drawn at full detail
+        assert_eq!(get_view_distance(3, &[]), 3);
+        assert_eq!(get_level(3, 3, &[]), 0);
+    }
+
+    #[test]
+    fn far_plane_reaches_the_furthest_corner() {
+        let rings = &DEFAULT_DISTANCES;
+        for render_distance in [1, 2, 12] {
+            // From the corner of the camera's chunk to the opposite corner of the furthest chunk,
+            // and the deepest skirt below that
+            let reach = ((get_view_distance(render_distance, rings) + 1) * chunk::SIZE as i32) as f32;
+            let corner = (3.0 * reach * reach).sqrt();
+            let skirt = (2.0 * reach * reach + (reach + 8.0).powi(2)).sqrt();
+            assert!(get_far_plane(render_distance, rings) >= corner.max(skirt));
+        }
+        assert!(get_far_plane(12, rings) > get_far_plane(2, rings));
+        assert!(get_far_plane(2, &[1, 2, 8]) > get_far_plane(2, rings));
+    }
+
+    #[test]
+    fn distance_is_the_largest_axis() {
+        assert_eq!(get_distance(Point3::new(0, 0, 0), Point3::new(3, -5, 1)), 5);
+        assert_eq!(get_distance(Point3::new(2, 2, 2), Point3::new(2, 2, 2)), 0);
+    }
+
+    #[test]
+    fn downsampled_solid_chunk_is_solid() {
+        let chunk = Chunk::new_filled(Point3::new(0, 0, 0), 3);
+        for scale in [2, 4, 8] {
+            let grid = LodGrid::downsample(&chunk, scale);
+            assert_eq!(grid.get_size(), (chunk::SIZE / scale) as i16);
+            assert_eq!(grid.get(Point3::new(0, 0, 0)), 3);
+            assert_eq!(grid.get(Point3::new(grid.get_size() - 1, 0, 0)), 3);
+            assert_eq!(grid.get(Point3::new(grid.get_size(), 0, 0)), 0);
+            assert_eq!(grid.get(Point3::new(-1, 0, 0)), 0);
+        }
+    }
+
+    #[test]
+    fn mostly_empty_cells_become_air() {
+        let mut chunk = Chunk::new_empty(Point3::new(0, 0, 0));
+        // 3 of the 8 blocks in the first 2x2x2 cell
+        chunk.set_block(Point3::new(0, 0, 0), 1);
+        chunk.set_block(Point3::new(1, 0, 0), 1);
+        chunk.set_block(Point3::new(0, 1, 0), 1);
+        // Exactly half of the next cell
+        for x in 2..4 {
+            for z in 0..2 {
+                chunk.set_block(Point3::new(x, 0, z), 2);
+            }
+        }
+        let grid = LodGrid::downsample(&chunk, 2);
+        assert_eq!(grid.get(Point3::new(0, 0, 0)), 0);
+        assert_eq!(grid.get(Point3::new(1, 0, 0)), 2);
+    }
+
+    #[test]
+    fn cells_take_the_most_common_block() {
+        let mut chunk = Chunk::new_filled(Point3::new(0, 0, 0), 1);
+        for x in 0..2 {
+            for y in 0..2 {
+                chunk.set_block(Point3::new(x, y, 0), 2);
+                chunk.set_block(Point3::new(x, y, 1), 2);
+            }
+        }
+        chunk.set_block(Point3::new(0, 0, 0), 3);
+        let grid = LodGrid::downsample(&chunk, 2);
+        assert_eq!(grid.get(Point3::new(0, 0, 0)), 2);
+        assert_eq!(grid.get(Point3::new(0, 0, 1)), 1);
+    }
+
+    #[test]
+    fn ties_go_to_the_lowest_id() {
+        let mut chunk = Chunk::new_empty(Point3::new(0, 0, 0));
+        for x in 0..2 {
+            for y in 0..2 {
+                chunk.set_block(Point3::new(x, y, 0), 5);
+                chunk.set_block(Point3::new(x, y, 1), 4);
+            }
+        }
+        assert_eq!(LodGrid::downsample(&chunk, 2).get(Point3::new(0, 0, 0)), 4);
+    }
+}
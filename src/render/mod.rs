@@ -1,5 +1,14 @@
+pub mod allocator;
+pub mod buffer_pool;
 pub mod chunk_builder;
 pub mod chunk_buffers;
+pub mod fog;
+pub mod font;
+pub mod frustum;
+pub mod lod;
+pub mod outline;
 pub mod sky;
 pub mod texture;
+pub mod ui;
+pub mod visibility;
 mod quad;
\ No newline at end of file
@@ -0,0 +1,79 @@
+use cgmath::Point3;
+use wgpu::{Device, Queue};
+
+/// How far the outline sits outside of the block, so it isn't hidden by the block's own faces.
+const MARGIN: f32 = 0.005;
+
+/// The 12 edges of the targeted block, drawn as a line list.
+pub struct Outline {
+    pub vertex_buffer: wgpu::Buffer,
+    pub vertex_count: u32,
+    target: Option<Point3<i32>>,
+}
+
+impl Outline {
+    pub fn new(device: &Device) -> Outline {
+        Outline {
+            vertex_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Outline Vertex Buffer"),
+                size: (std::mem::size_of::<[f32; 3]>() * 24) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            vertex_count: 24,
+            target: None,
+        }
+    }
+
+    /// Moves the outline to the given block, only writing to the buffer when it changed.
+    pub fn update(&mut self, queue: &Queue, target: Option<Point3<i32>>) {
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+        if let Some(target) = target {
+            queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&get_edges(target)));
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.target.is_some()
+    }
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Float32x3,
+            }],
+        }
+    }
+}
+
+fn get_edges(block: Point3<i32>) -> [[f32; 3]; 24] {
+    let half = 0.5 + MARGIN;
+    let corner = |x: usize, y: usize, z: usize| {
+        let offset = |bit: usize| if bit == 1 { half } else { -half };
+        [block.x as f32 + offset(x), block.y as f32 + offset(y), block.z as f32 + offset(z)]
+    };
+    let mut edges = [[0.0; 3]; 24];
+    let mut index = 0;
+    for a in 0..2 {
+        for b in 0..2 {
+            // One edge along each axis for every combination of the other two
+            for (start, end) in [
+                (corner(0, a, b), corner(1, a, b)),
+                (corner(a, 0, b), corner(a, 1, b)),
+                (corner(a, b, 0), corner(a, b, 1)),
+            ] {
+                edges[index] = start;
+                edges[index + 1] = end;
+                index += 2;
+            }
+        }
+    }
+    edges
+}
@@ -1,155 +1,67 @@
-use cgmath::Vector3;
+use cgmath::{EuclideanSpace, Point3, Vector3};
 
-use crate::{Vertex, direction::Direction};
-
-const TEXTURE_INCREMENT: f32 = 1.0 / 256.0;
-const SKY_INCREMENT: f32 = 1.0 / 6.0;
+use crate::{Vertex, direction::Direction, light::Light};
 
 pub struct Quad {
     pub vertices: [Vertex; 4],
-    pub indices: [u32; 6],
 }
 
 impl Quad {
-    fn new(vertices: [Vertex; 4], indices: [u32; 6]) -> Self {
-        Self { vertices, indices }
+    fn new(vertices: [Vertex; 4]) -> Self {
+        Self { vertices }
     }
-}
 
-pub fn block_quad(
-    id: u32,
-    index: u32,
-    direction: Direction,
-    position: Vector3<f32>,
-) -> Quad {
-    let block_type = crate::block_types::get(id);
-    let text_id = block_type.get_texture(direction);
-    let text_0: f32 = TEXTURE_INCREMENT * text_id as f32;
-    let text_1: f32 = TEXTURE_INCREMENT * (text_id + 1) as f32;
-    quad(text_0, text_1, index, direction, position, true)
+    /// Center of the quad relative to the chunk's origin.
+    pub fn get_center(&self) -> Point3<f32> {
+        let sum = self.vertices.iter().fold(Vector3::new(0.0, 0.0, 0.0), |sum, vertex| {
+            sum + vertex.get_position().map(|value| value as f32).to_vec()
+        });
+        Point3::from_vec(sum / 4.0)
+    }
 }
 
-pub fn sky_quad(
-    index: u32,
-    direction: Direction,
-    position: Vector3<f32>,
-) -> Quad {
-    let text_0: f32 = SKY_INCREMENT * direction.get_id() as f32;
-    let text_1: f32 = SKY_INCREMENT * (direction.get_id() + 1) as f32;
-    quad(text_0, text_1, index, direction, position, false)
-}
+/// Every quad shares the same index pattern, so its four vertices are emitted in the order that
+/// keeps the pattern counter clockwise for the face's direction.
+pub const INDICES: [u32; 6] = [0, 1, 2, 2, 1, 3];
 
-pub fn quad(
-    text_0: f32,
-    text_1: f32,
-    index: u32,
-    direction: Direction,
-    position: Vector3<f32>,
-    lighting: bool,
-) -> Quad {
-    let indices_f = [
-        0 + (index * 4),
-        1 + (index * 4),
-        2 + (index * 4),
-        2 + (index * 4),
-        1 + (index * 4),
-        3 + (index * 4),
-    ];
-    let indices_b = [
-        2 + (index * 4),
-        1 + (index * 4),
-        0 + (index * 4),
-        3 + (index * 4),
-        1 + (index * 4),
-        2 + (index * 4),
-    ];
+pub fn block_quad(id: u32, direction: Direction, position: Point3<u8>, block_light: Light) -> Quad {
+    let block_type = crate::block_types::get(id);
+    let texture = block_type.get_texture(direction);
+    quad(texture, direction, position, block_light)
+}
 
-    let light = if lighting {
-        match direction {
-            Direction::UP => 1.0,
-            Direction::DOWN => 0.25,
-            Direction::NORTH => 0.5,
-            Direction::SOUTH => 0.5,
-            Direction::WEST => 0.75,
-            Direction::EAST => 0.25,
-        }
-    } else {
-        1.0
-    };
+/// The face of the block at the chunk local `position`, with corners on whole numbers from the chunk's origin.
+pub fn quad(texture: u32, direction: Direction, position: Point3<u8>, block_light: Light) -> Quad {
+    let (x, y, z) = (position.x, position.y, position.z);
+    let vertex = |corner: [u8; 3], uv: [u8; 2]| Vertex::new(corner, direction, uv, texture, 0, block_light);
 
-    let vertices_ud = [
-        Vertex {
-            position: [-0.5 + position.x, position.y, 0.5 + position.z],
-            tex_coords: [text_0, 1.0],
-            light
-        }, // A
-        Vertex {
-            position: [0.5 + position.x, position.y, 0.5 + position.z],
-            tex_coords: [text_1, 1.0],
-            light
-        }, // B
-        Vertex {
-            position: [-0.5 + position.x, position.y, -0.5 + position.z],
-            tex_coords: [text_0, 0.0],
-            light
-        }, // C
-        Vertex {
-            position: [0.5 + position.x, position.y, -0.5 + position.z],
-            tex_coords: [text_1, 0.0],
-            light
-        }, // D
+    let vertices_ud = |y: u8| [
+        vertex([x, y, z + 1], [0, 1]), // A
+        vertex([x + 1, y, z + 1], [1, 1]), // B
+        vertex([x, y, z], [0, 0]), // C
+        vertex([x + 1, y, z], [1, 0]), // D
     ];
-    let vertices_ns = [
-        Vertex {
-            position: [-0.5 + position.x, 0.5 + position.y, position.z],
-            tex_coords: [text_1, 0.0],
-            light
-        }, // A
-        Vertex {
-            position: [0.5 + position.x, 0.5 + position.y, position.z],
-            tex_coords: [text_0, 0.0],
-            light
-        }, // B
-        Vertex {
-            position: [-0.5 + position.x, -0.5 + position.y, position.z],
-            tex_coords: [text_1, 1.0],
-            light
-        }, // C
-        Vertex {
-            position: [0.5 + position.x, -0.5 + position.y, position.z],
-            tex_coords: [text_0, 1.0],
-            light
-        }, // D
+    let vertices_ns = |z: u8| [
+        vertex([x, y + 1, z], [1, 0]), // A
+        vertex([x + 1, y + 1, z], [0, 0]), // B
+        vertex([x, y, z], [1, 1]), // C
+        vertex([x + 1, y, z], [0, 1]), // D
     ];
-    let vertices_we = [
-        Vertex {
-            position: [position.x, 0.5 + position.y, -0.5 + position.z],
-            tex_coords: [text_1, 0.0],
-            light
-        }, // A
-        Vertex {
-            position: [position.x, 0.5 + position.y, 0.5 + position.z],
-            tex_coords: [text_0, 0.0],
-            light
-        }, // B
-        Vertex {
-            position: [position.x, -0.5 + position.y, -0.5 + position.z],
-            tex_coords: [text_1, 1.0],
-            light
-        }, // C
-        Vertex {
-            position: [position.x, -0.5 + position.y, 0.5 + position.z],
-            tex_coords: [text_0, 1.0],
-            light
-        }, // D
+    let vertices_we = |x: u8| [
+        vertex([x, y + 1, z], [1, 0]), // A
+        vertex([x, y + 1, z + 1], [0, 0]), // B
+        vertex([x, y, z], [1, 1]), // C
+        vertex([x, y, z + 1], [0, 1]), // D
     ];
+    // Faces looking the other way swap B and C to flip their winding
+    let back = |[a, b, c, d]: [Vertex; 4]| [a, c, b, d];
 
     match direction {
-        Direction::UP => Quad::new(vertices_ud, indices_f),
-        Direction::DOWN => Quad::new(vertices_ud, indices_b),
-        Direction::NORTH => Quad::new(vertices_ns, indices_f),
-        Direction::SOUTH => Quad::new(vertices_ns, indices_b),
-        Direction::WEST => Quad::new(vertices_we, indices_f),
-        Direction::EAST => Quad::new(vertices_we, indices_b),
+        Direction::UP => Quad::new(vertices_ud(y + 1)),
+        Direction::DOWN => Quad::new(back(vertices_ud(y))),
+        Direction::NORTH => Quad::new(vertices_ns(z)),
+        Direction::SOUTH => Quad::new(back(vertices_ns(z + 1))),
+        Direction::WEST => Quad::new(vertices_we(x + 1)),
+        Direction::EAST => Quad::new(back(vertices_we(x))),
     }
-}
\ No newline at end of file
+}
@@ -1,36 +1,175 @@
-use cgmath::Vector3;
-use wgpu::{Device, util::DeviceExt};
-
-use crate::{direction::Direction, Vertex};
-
-use super::quad::sky_quad;
-
-pub fn build(device: &Device, pos: Vector3<f32>) -> (wgpu::Buffer, wgpu::Buffer, u32) {
-    let mut vertices: Vec<Vertex> = vec![];
-    let mut indices: Vec<u32> = vec![];
-    for quad in [
-        sky_quad(0, Direction::UP,  Vector3::new(pos.x, pos.y - 0.5, pos.z)),
-        sky_quad(1, Direction::DOWN,  Vector3::new(pos.x, pos.y + 0.5, pos.z)),
-        sky_quad(2, Direction::NORTH,  Vector3::new(pos.x, pos.y, pos.z + 0.5)),
-        sky_quad(3, Direction::SOUTH,  Vector3::new(pos.x, pos.y, pos.z - 0.5)),
-        sky_quad(4, Direction::WEST,  Vector3::new(pos.x - 0.5, pos.y, pos.z)),
-        sky_quad(5, Direction::EAST,  Vector3::new(pos.x + 0.5, pos.y, pos.z)),
-    ].iter() {
-        vertices.extend(quad.vertices);
-        indices.extend(quad.indices);
+use cgmath::{InnerSpace, Vector3};
+use wgpu::{Device, Queue, util::DeviceExt};
+
+use crate::world_time::{self, WorldTime};
+
+use super::fog::Fog;
+
+const DAY_ZENITH: Vector3<f32> = Vector3::new(0.25, 0.5, 0.95);
+const DAY_HORIZON: Vector3<f32> = Vector3::new(0.65, 0.8, 1.0);
+const NIGHT_ZENITH: Vector3<f32> = Vector3::new(0.005, 0.007, 0.025);
+const NIGHT_HORIZON: Vector3<f32> = Vector3::new(0.03, 0.035, 0.07);
+const SUNSET_HORIZON: Vector3<f32> = Vector3::new(0.95, 0.45, 0.2);
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyUniform {
+    sun_direction: [f32; 4],
+    zenith_color: [f32; 4],
+    horizon_color: [f32; 4],
+    fog_color: [f32; 4],
+    sky_light: f32,
+    fog_start: f32,
+    fog_end: f32,
+    fog_density: f32,
+    fog_mode: u32,
+    /// How much of the sky is hidden behind the fog, 1.0 when the camera is inside a fluid.
+    sky_fog: f32,
+    _padding: [f32; 2],
+}
+
+impl SkyUniform {
+    pub fn new(time: &WorldTime, fog: &Fog) -> Self {
+        let sun_direction = time.get_sun_direction();
+        let daylight = world_time::smoothstep(-0.2, 0.2, sun_direction.y);
+        let sunset = 1.0 - world_time::smoothstep(0.0, 0.35, sun_direction.y.abs());
+
+        let zenith = lerp(NIGHT_ZENITH, DAY_ZENITH, daylight);
+        let horizon = lerp(lerp(NIGHT_HORIZON, DAY_HORIZON, daylight), SUNSET_HORIZON, sunset * 0.7);
+
+        // Fog fades into the horizon so the edge of the world blends into the sky
+        let fog_color = match fog.color {
+            Some(color) => Vector3::from(color),
+            None => horizon,
+        };
+
+        Self {
+            sun_direction: sun_direction.normalize().extend(0.0).into(),
+            zenith_color: zenith.extend(1.0).into(),
+            horizon_color: horizon.extend(1.0).into(),
+            fog_color: fog_color.extend(1.0).into(),
+            sky_light: time.get_sky_light(),
+            fog_start: fog.start,
+            fog_end: fog.end,
+            fog_density: fog.get_density(),
+            fog_mode: fog.mode.get_id(),
+            sky_fog: if fog.color.is_some() { 1.0 } else { 0.0 },
+            _padding: [0.0; 2],
+        }
     }
 
-    (
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
+    pub fn get_fog_color(&self) -> wgpu::Color {
+        wgpu::Color {
+            r: self.fog_color[0] as f64,
+            g: self.fog_color[1] as f64,
+            b: self.fog_color[2] as f64,
+            a: 1.0,
+        }
+    }
+}
+
+fn lerp(from: Vector3<f32>, to: Vector3<f32>, amount: f32) -> Vector3<f32> {
+    from + (to - from) * amount
+}
+
+/// A cube drawn around the camera before anything else, coloured by `sky.wgsl` from the time of day.
+pub struct Sky {
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub index_count: u32,
+    pub uniform: SkyUniform,
+    pub uniform_buffer: wgpu::Buffer,
+    pub bind_group_layout: wgpu::BindGroupLayout,
+    pub bind_group: wgpu::BindGroup,
+}
+
+impl Sky {
+    pub fn new(device: &Device, time: &WorldTime, fog: &Fog) -> Sky {
+        let vertices: [[f32; 3]; 8] = [
+            [-1.0, -1.0, -1.0],
+            [1.0, -1.0, -1.0],
+            [-1.0, 1.0, -1.0],
+            [1.0, 1.0, -1.0],
+            [-1.0, -1.0, 1.0],
+            [1.0, -1.0, 1.0],
+            [-1.0, 1.0, 1.0],
+            [1.0, 1.0, 1.0],
+        ];
+        let indices: [u32; 36] = [
+            0, 1, 2, 2, 1, 3, // North
+            4, 6, 5, 5, 6, 7, // South
+            0, 2, 4, 4, 2, 6, // East
+            1, 5, 3, 3, 5, 7, // West
+            2, 3, 6, 6, 3, 7, // Up
+            0, 4, 1, 1, 4, 5, // Down
+        ];
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sky Vertex Buffer"),
             contents: bytemuck::cast_slice(&vertices),
             usage: wgpu::BufferUsages::VERTEX,
-        }),
-        device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sky Index Buffer"),
             contents: bytemuck::cast_slice(&indices),
             usage: wgpu::BufferUsages::INDEX,
-        }),
-        indices.len() as u32,
-    )
-}
\ No newline at end of file
+        });
+
+        let uniform = SkyUniform::new(time, fog);
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Sky Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("sky_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("sky_bind_group"),
+        });
+
+        Sky {
+            vertex_buffer,
+            index_buffer,
+            index_count: indices.len() as u32,
+            uniform,
+            uniform_buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    pub fn update(&mut self, queue: &Queue, time: &WorldTime, fog: &Fog) {
+        self.uniform = SkyUniform::new(time, fog);
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[self.uniform]));
+    }
+
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[wgpu::VertexAttribute {
+                offset: 0,
+                shader_location: 0,
+                format: wgpu::VertexFormat::Float32x3,
+            }],
+        }
+    }
+}
@@ -0,0 +1,475 @@
+use std::ops::Range;
+
+use wgpu::{Device, Queue, util::DeviceExt};
+
+use super::{font, quad, texture::Texture};
+
+/// Quads the vertex buffer starts out with room for, it doubles whenever a frame needs more.
+const INITIAL_QUADS: u32 = 256;
+
+/// Linear RGBA, multiplied with the texture.
+pub type Color = [f32; 4];
+
+pub const WHITE: Color = [1.0, 1.0, 1.0, 1.0];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UiVertex {
+    /// In UI pixels from the top left corner of the window.
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: Color,
+}
+
+impl UiVertex {
+    pub fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<UiVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+}
+
+/// The textures quads can show.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UiTexture {
+    /// The font atlas, which also has the white pixel that plain rects use.
+    Font,
+    /// The block atlas the world is drawn with.
+    Blocks,
+}
+
+/// Consecutive quads showing the same texture, drawn with a single call.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Batch {
+    pub texture: UiTexture,
+    pub quads: Range<u32>,
+}
+
+/// A rectangle in UI pixels, with `x` and `y` at its top left corner.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect { x, y, width, height }
+    }
+}
+
+/// The quads of one frame of UI, drawn on top of the world by `UiRenderer`. Everything is laid out
+/// in UI pixels, which are `scale` physical pixels wide, so one pixel of the font is one UI pixel.
+/// Quads are drawn in the order they were added, later ones over earlier ones.
+pub struct Ui {
+    vertices: Vec<UiVertex>,
+    batches: Vec<Batch>,
+    width: f32,
+    height: f32,
+    scale: f32,
+}
+
+impl Ui {
+    pub fn new(size: winit::dpi::PhysicalSize<u32>, scale: f32) -> Ui {
+        let mut ui = Ui {
+            vertices: Vec::new(),
+            batches: Vec::new(),
+            width: 0.0,
+            height: 0.0,
+            scale,
+        };
+        ui.resize(size);
+        ui
+    }
+
+    pub fn resize(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+        self.width = size.width as f32 / self.scale;
+        self.height = size.height as f32 / self.scale;
+    }
+
+    /// Physical pixels per UI pixel.
+    pub fn get_scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Width and height of the window in UI pixels.
+    pub fn get_size(&self) -> (f32, f32) {
+        (self.width, self.height)
+    }
+
+    /// Drops last frame's quads, to be called before building the next frame.
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.batches.clear();
+    }
+
+    pub fn rect(&mut self, rect: Rect, color: Color) {
+        let (x, y) = font::get_white_pixel();
+        self.quad(rect, Rect::new(x as f32, y as f32, 0.0, 0.0), color);
+    }
+
+    /// Draws a line of text with its top left corner at `x` and `y`, returning its width.
+    pub fn text(&mut self, x: f32, y: f32, text: &str, color: Color) -> f32 {
+        let mut offset = 0.0;
+        for character in text.chars() {
+            let glyph = font::get_glyph(character);
+            if character != ' ' {
+                let size = font::GLYPH_SIZE as f32;
+                let area = Rect::new(glyph.x as f32, glyph.y as f32, glyph.width as f32, size);
+                self.quad(Rect::new(x + offset, y, glyph.width as f32, size), area, color);
+            }
+            offset += glyph.get_advance() as f32;
+        }
+        get_text_width(text)
+    }
+
+    /// Draws the part of `texture` under `uv`, which goes from 0 to 1 across the whole texture.
+    pub fn image(&mut self, rect: Rect, texture: UiTexture, uv: Rect, color: Color) {
+        self.push_quad(rect, texture, uv, color);
+    }
+
+    pub fn get_vertices(&self) -> &[UiVertex] {
+        &self.vertices
+    }
+
+    pub fn get_quad_count(&self) -> u32 {
+        self.vertices.len() as u32 / 4
+    }
+
+    pub fn get_batches(&self) -> &[Batch] {
+        &self.batches
+    }
+
+    /// Adds a quad covering `rect` that shows the part of the font atlas under `area`, in atlas pixels.
+    fn quad(&mut self, rect: Rect, area: Rect, color: Color) {
+        let (width, height) = (font::ATLAS_WIDTH as f32, font::ATLAS_HEIGHT as f32);
+        let uv = Rect::new(area.x / width, area.y / height, area.width / width, area.height / height);
+        self.push_quad(rect, UiTexture::Font, uv, color);
+    }
+
+    fn push_quad(&mut self, rect: Rect, texture: UiTexture, uv: Rect, color: Color) {
+        let index = self.get_quad_count();
+        match self.batches.last_mut() {
+            Some(batch) if batch.texture == texture => batch.quads.end = index + 1,
+            _ => self.batches.push(Batch { texture, quads: index..index + 1 }),
+        }
+        let (left, top) = (rect.x, rect.y);
+        let (right, bottom) = (rect.x + rect.width, rect.y + rect.height);
+        let (u_left, v_top) = (uv.x, uv.y);
+        let (u_right, v_bottom) = (uv.x + uv.width, uv.y + uv.height);
+        // In the order `quad::INDICES` expects
+        self.vertices.extend([
+            UiVertex { position: [left, top], uv: [u_left, v_top], color },
+            UiVertex { position: [right, top], uv: [u_right, v_top], color },
+            UiVertex { position: [left, bottom], uv: [u_left, v_bottom], color },
+            UiVertex { position: [right, bottom], uv: [u_right, v_bottom], color },
+        ]);
+    }
+}
+
+/// Width of a line of text in UI pixels.
+pub fn get_text_width(text: &str) -> f32 {
+    font::get_text_width(text) as f32
+}
+
+/// Draws a `Ui` in a pass of its own after the world, alpha blended over it without depth.
+pub struct UiRenderer {
+    pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    /// In quads.
+    capacity: u32,
+    /// Size of the window in UI pixels, which the vertex shader divides positions by.
+    screen_buffer: wgpu::Buffer,
+    screen_bind_group: wgpu::BindGroup,
+    #[allow(dead_code)]
+    font_texture: Texture,
+    font_bind_group: wgpu::BindGroup,
+    blocks_bind_group: wgpu::BindGroup,
+}
+
+impl UiRenderer {
+    /// `blocks` is the block atlas, for `UiTexture::Blocks`.
+    pub fn new(device: &Device, queue: &Queue, format: wgpu::TextureFormat, blocks: &Texture) -> UiRenderer {
+        let font_texture = Texture::from_image(device, queue, &image::DynamicImage::ImageRgba8(font::create_atlas()), Some("Font Atlas"));
+        let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("ui_texture_bind_group_layout"),
+        });
+        let font_bind_group = create_texture_bind_group(device, &texture_bind_group_layout, &font_texture, "font_bind_group");
+        let blocks_bind_group = create_texture_bind_group(device, &texture_bind_group_layout, blocks, "ui_blocks_bind_group");
+
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("UI Screen Buffer"),
+            size: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let screen_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("ui_screen_bind_group_layout"),
+        });
+        let screen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &screen_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+            label: Some("ui_screen_bind_group"),
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("UI Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../assets/shaders/ui.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("UI Pipeline Layout"),
+            bind_group_layouts: &[&texture_bind_group_layout, &screen_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("UI Pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[UiVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        let (vertex_buffer, index_buffer) = create_buffers(device, INITIAL_QUADS);
+        UiRenderer {
+            pipeline,
+            vertex_buffer,
+            index_buffer,
+            capacity: INITIAL_QUADS,
+            screen_buffer,
+            screen_bind_group,
+            font_texture,
+            font_bind_group,
+            blocks_bind_group,
+        }
+    }
+
+    /// Uploads the UI's quads and draws them over whatever is already in `view`.
+    pub fn draw(&mut self, device: &Device, queue: &Queue, encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView, ui: &Ui) {
+        let quad_count = ui.get_quad_count();
+        if quad_count == 0 {
+            return;
+        }
+        if quad_count > self.capacity {
+            self.capacity = quad_count.next_power_of_two();
+            (self.vertex_buffer, self.index_buffer) = create_buffers(device, self.capacity);
+        }
+        let (width, height) = ui.get_size();
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&[width, height, 0.0, 0.0]));
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(ui.get_vertices()));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("UI Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(1, &self.screen_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        for batch in ui.get_batches() {
+            let bind_group = match batch.texture {
+                UiTexture::Font => &self.font_bind_group,
+                UiTexture::Blocks => &self.blocks_bind_group,
+            };
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw_indexed(batch.quads.start * 6..batch.quads.end * 6, 0, 0..1);
+        }
+    }
+}
+
+fn create_texture_bind_group(device: &Device, layout: &wgpu::BindGroupLayout, texture: &Texture, label: &str) -> wgpu::BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            },
+        ],
+        label: Some(label),
+    })
+}
+
+fn create_buffers(device: &Device, capacity: u32) -> (wgpu::Buffer, wgpu::Buffer) {
+    let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("UI Vertex Buffer"),
+        size: (std::mem::size_of::<UiVertex>() * 4 * capacity as usize) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let indices: Vec<u32> = (0..capacity).flat_map(|index| quad::INDICES.map(|offset| index * 4 + offset)).collect();
+    let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("UI Index Buffer"),
+        contents: bytemuck::cast_slice(&indices),
+        usage: wgpu::BufferUsages::INDEX,
+    });
+    (vertex_buffer, index_buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::dpi::PhysicalSize;
+
+    use super::*;
+
+    #[test]
+    fn size_is_in_ui_pixels() {
+        let mut ui = Ui::new(PhysicalSize::new(1280, 720), 2.0);
+        assert_eq!(ui.get_size(), (640.0, 360.0));
+        ui.resize(PhysicalSize::new(800, 600));
+        assert_eq!(ui.get_size(), (400.0, 300.0));
+    }
+
+    #[test]
+    fn rects_are_one_quad_of_the_white_pixel() {
+        let mut ui = Ui::new(PhysicalSize::new(100, 100), 1.0);
+        let color = [1.0, 0.0, 0.0, 0.5];
+        ui.rect(Rect::new(10.0, 20.0, 30.0, 40.0), color);
+        assert_eq!(ui.get_quad_count(), 1);
+        let vertices = ui.get_vertices();
+        assert_eq!(vertices[0].position, [10.0, 20.0]);
+        assert_eq!(vertices[3].position, [40.0, 60.0]);
+        let (x, y) = font::get_white_pixel();
+        let white = [x as f32 / font::ATLAS_WIDTH as f32, y as f32 / font::ATLAS_HEIGHT as f32];
+        assert!(vertices.iter().all(|vertex| vertex.uv == white && vertex.color == color));
+
+        ui.clear();
+        assert_eq!(ui.get_quad_count(), 0);
+    }
+
+    #[test]
+    fn text_is_a_quad_per_visible_character() {
+        let mut ui = Ui::new(PhysicalSize::new(100, 100), 1.0);
+        let width = ui.text(5.0, 5.0, "Hi there", WHITE);
+        assert_eq!(width, get_text_width("Hi there"));
+        assert_eq!(ui.get_quad_count(), 7);
+
+        // Glyphs follow each other, spaced by their own widths
+        let vertices = ui.get_vertices();
+        let h = font::get_glyph('H');
+        assert_eq!(vertices[0].position, [5.0, 5.0]);
+        assert_eq!(vertices[3].position, [5.0 + h.width as f32, 5.0 + font::GLYPH_SIZE as f32]);
+        assert_eq!(vertices[4].position[0], 5.0 + h.get_advance() as f32);
+    }
+
+    #[test]
+    fn later_quads_come_after_earlier_ones() {
+        let mut ui = Ui::new(PhysicalSize::new(100, 100), 1.0);
+        ui.rect(Rect::new(0.0, 0.0, 1.0, 1.0), [0.0, 0.0, 0.0, 1.0]);
+        ui.text(0.0, 0.0, "x", WHITE);
+        assert_eq!(ui.get_vertices()[0].color, [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(ui.get_vertices()[4].color, WHITE);
+    }
+
+    #[test]
+    fn quads_are_batched_by_texture() {
+        let mut ui = Ui::new(PhysicalSize::new(100, 100), 1.0);
+        ui.rect(Rect::new(0.0, 0.0, 1.0, 1.0), WHITE);
+        ui.text(0.0, 0.0, "ab", WHITE);
+        let uv = Rect::new(0.25, 0.0, 0.5, 1.0);
+        ui.image(Rect::new(10.0, 10.0, 16.0, 16.0), UiTexture::Blocks, uv, WHITE);
+        ui.image(Rect::new(30.0, 10.0, 16.0, 16.0), UiTexture::Blocks, uv, WHITE);
+        ui.rect(Rect::new(0.0, 0.0, 1.0, 1.0), WHITE);
+        assert_eq!(ui.get_batches(), &[
+            Batch { texture: UiTexture::Font, quads: 0..3 },
+            Batch { texture: UiTexture::Blocks, quads: 3..5 },
+            Batch { texture: UiTexture::Font, quads: 5..6 },
+        ]);
+        let vertices = &ui.get_vertices()[12..16];
+        assert_eq!(vertices[0].uv, [0.25, 0.0]);
+        assert_eq!(vertices[3].uv, [0.75, 1.0]);
+
+        ui.clear();
+        assert!(ui.get_batches().is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::{HashSet, VecDeque};
+
+use cgmath::{Point3, Vector3};
+
+use crate::{chunk::{self, Chunk}, direction::Direction};
+
+const SIZE: usize = chunk::SIZE as usize;
+
+/// Which pairs of chunk faces can see each other through the non-opaque blocks inside a chunk.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub struct VisibilitySet(u64);
+
+impl VisibilitySet {
+    pub fn none() -> VisibilitySet {
+        VisibilitySet(0)
+    }
+
+    pub fn all() -> VisibilitySet {
+        VisibilitySet((1 << 36) - 1)
+    }
+
+    pub fn connect(&mut self, from: Direction, to: Direction) {
+        self.0 |= 1 << (from.get_id() * 6 + to.get_id());
+        self.0 |= 1 << (to.get_id() * 6 + from.get_id());
+    }
+
+    pub fn is_connected(&self, from: Direction, to: Direction) -> bool {
+        self.0 & (1 << (from.get_id() * 6 + to.get_id())) != 0
+    }
+}
+
+/// Flood fills every pocket of non-opaque blocks in the chunk and connects each pair of faces the pocket touches.
+pub fn compute(chunk: &Chunk, is_opaque: impl Fn(u32) -> bool) -> VisibilitySet {
+    // Most chunks above the ground are empty, which can't block anything
+    if chunk.blocks.get_palette().iter().all(|block| !is_opaque(*block)) {
+        return VisibilitySet::all();
+    }
+    let blocks: Vec<u32> = chunk.blocks.iter().collect();
+
+    let index = |x: usize, y: usize, z: usize| (x * SIZE + y) * SIZE + z;
+    let mut visited = vec![false; SIZE * SIZE * SIZE];
+    let mut set = VisibilitySet::none();
+    let mut stack = Vec::new();
+
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                if visited[index(x, y, z)] || is_opaque(blocks[index(x, y, z)]) {
+                    continue;
+                }
+
+                let mut faces: Vec<Direction> = Vec::new();
+                visited[index(x, y, z)] = true;
+                stack.push((x, y, z));
+                while let Some((x, y, z)) = stack.pop() {
+                    for direction in Direction::ALL {
+                        let offset = direction.get_vec();
+                        let (nx, ny, nz) = (x as i32 + offset.x as i32, y as i32 + offset.y as i32, z as i32 + offset.z as i32);
+                        if nx < 0 || ny < 0 || nz < 0 || nx >= SIZE as i32 || ny >= SIZE as i32 || nz >= SIZE as i32 {
+                            if !faces.contains(&direction) {
+                                faces.push(direction);
+                            }
+                            continue;
+                        }
+                        let (nx, ny, nz) = (nx as usize, ny as usize, nz as usize);
+                        if !visited[index(nx, ny, nz)] && !is_opaque(blocks[index(nx, ny, nz)]) {
+                            visited[index(nx, ny, nz)] = true;
+                            stack.push((nx, ny, nz));
+                        }
+                    }
+                }
+
+                for from in &faces {
+                    for to in &faces {
+                        set.connect(*from, *to);
+                    }
+                }
+            }
+        }
+    }
+    set
+}
+
+/// Walks outwards from the camera's chunk, only stepping into a neighbour when the face we
+/// entered the current chunk through can see the face we leave it through. Chunks are never
+/// entered in the direction opposite of one already travelled, so the search can't turn back
+/// towards the camera. `get_visibility` returns `None` for chunks that aren't loaded.
+///
+/// Returns `None` when the camera isn't inside a loaded chunk, in which case everything should be drawn.
+pub fn find_visible(start: Point3<i32>, get_visibility: impl Fn(Point3<i32>) -> Option<VisibilitySet>) -> Option<HashSet<Point3<i32>>> {
+    get_visibility(start)?;
+
+    let mut visible = HashSet::new();
+    let mut queue: VecDeque<(Point3<i32>, Option<Direction>, u8)> = VecDeque::new();
+    visible.insert(start);
+    queue.push_back((start, None, 0));
+
+    while let Some((position, entered_through, travelled)) = queue.pop_front() {
+        let visibility = match get_visibility(position) {
+            Some(visibility) => visibility,
+            None => continue,
+        };
+        for direction in Direction::ALL {
+            if travelled & (1 << direction.get_opposite().get_id()) != 0 {
+                continue;
+            }
+            if let Some(entered_through) = entered_through {
+                if !visibility.is_connected(entered_through, direction) {
+                    continue;
+                }
+            }
+            let offset = direction.get_vec();
+            let neighbour = position + Vector3::new(offset.x as i32, offset.y as i32, offset.z as i32);
+            if visible.contains(&neighbour) || get_visibility(neighbour).is_none() {
+                continue;
+            }
+            visible.insert(neighbour);
+            queue.push_back((neighbour, Some(direction.get_opposite()), travelled | (1 << direction.get_id())));
+        }
+    }
+    Some(visible)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn is_opaque(block: u32) -> bool {
+        block != 0
+    }
+
+    #[test]
+    fn empty_chunk_connects_every_face() {
+        let chunk = Chunk::new_empty(Point3::new(0, 0, 0));
+        assert_eq!(compute(&chunk, is_opaque), VisibilitySet::all());
+    }
+
+    #[test]
+    fn solid_chunk_connects_nothing() {
+        let chunk = Chunk::new_filled(Point3::new(0, 0, 0), 3);
+        assert_eq!(compute(&chunk, is_opaque), VisibilitySet::none());
+    }
+
+    #[test]
+    fn tunnel_connects_its_ends() {
+        let mut chunk = Chunk::new_filled(Point3::new(0, 0, 0), 3);
+        for x in 0..chunk::SIZE {
+            chunk.set_block(Point3::new(x, 8, 8), 0);
+        }
+        let set = compute(&chunk, is_opaque);
+        assert!(set.is_connected(Direction::EAST, Direction::WEST));
+        assert!(set.is_connected(Direction::WEST, Direction::EAST));
+        assert!(!set.is_connected(Direction::UP, Direction::DOWN));
+        assert!(!set.is_connected(Direction::EAST, Direction::UP));
+        assert!(!set.is_connected(Direction::NORTH, Direction::SOUTH));
+    }
+
+    #[test]
+    fn bent_tunnel_connects_its_ends_only() {
+        let mut chunk = Chunk::new_filled(Point3::new(0, 0, 0), 3);
+        for y in 4..chunk::SIZE {
+            chunk.set_block(Point3::new(4, y, 4), 0);
+        }
+        for z in 0..5 {
+            chunk.set_block(Point3::new(4, 4, z), 0);
+        }
+        let set = compute(&chunk, is_opaque);
+        assert!(set.is_connected(Direction::UP, Direction::NORTH));
+        assert!(!set.is_connected(Direction::UP, Direction::SOUTH));
+        assert!(!set.is_connected(Direction::NORTH, Direction::SOUTH));
+    }
+
+    #[test]
+    fn separate_pockets_stay_separate() {
+        let mut chunk = Chunk::new_empty(Point3::new(0, 0, 0));
+        for x in 0..chunk::SIZE {
+            for z in 0..chunk::SIZE {
+                chunk.set_block(Point3::new(x, 8, z), 3);
+            }
+        }
+        let set = compute(&chunk, is_opaque);
+        assert!(!set.is_connected(Direction::UP, Direction::DOWN));
+        assert!(set.is_connected(Direction::UP, Direction::NORTH));
+        assert!(set.is_connected(Direction::DOWN, Direction::NORTH));
+        assert!(set.is_connected(Direction::EAST, Direction::WEST));
+    }
+
+    #[test]
+    fn enclosed_pocket_connects_nothing() {
+        let mut chunk = Chunk::new_filled(Point3::new(0, 0, 0), 3);
+        chunk.set_block(Point3::new(8, 8, 8), 0);
+        chunk.set_block(Point3::new(8, 9, 8), 0);
+        assert_eq!(compute(&chunk, is_opaque), VisibilitySet::none());
+    }
+
+    fn visible_from(start: Point3<i32>, chunks: &HashMap<Point3<i32>, VisibilitySet>) -> Option<HashSet<Point3<i32>>> {
+        find_visible(start, |position| chunks.get(&position).copied())
+    }
+
+    #[test]
+    fn open_world_is_fully_visible() {
+        let mut chunks = HashMap::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    chunks.insert(Point3::new(x, y, z), VisibilitySet::all());
+                }
+            }
+        }
+        assert_eq!(visible_from(Point3::new(0, 0, 0), &chunks).unwrap().len(), 27);
+    }
+
+    #[test]
+    fn solid_chunk_hides_what_is_behind_it() {
+        // A row of chunks along x with a solid one in the middle
+        let mut chunks = HashMap::new();
+        chunks.insert(Point3::new(0, 0, 0), VisibilitySet::all());
+        chunks.insert(Point3::new(1, 0, 0), VisibilitySet::none());
+        chunks.insert(Point3::new(2, 0, 0), VisibilitySet::all());
+        let visible = visible_from(Point3::new(0, 0, 0), &chunks).unwrap();
+        assert!(visible.contains(&Point3::new(1, 0, 0)));
+        assert!(!visible.contains(&Point3::new(2, 0, 0)));
+    }
+
+    #[test]
+    fn tunnel_leads_to_chunks_behind_it() {
+        let mut tunnel = VisibilitySet::none();
+        tunnel.connect(Direction::EAST, Direction::WEST);
+        let mut chunks = HashMap::new();
+        chunks.insert(Point3::new(0, 0, 0), VisibilitySet::all());
+        chunks.insert(Point3::new(1, 0, 0), tunnel);
+        chunks.insert(Point3::new(2, 0, 0), VisibilitySet::all());
+        chunks.insert(Point3::new(1, 1, 0), VisibilitySet::all());
+        let visible = visible_from(Point3::new(0, 0, 0), &chunks).unwrap();
+        // Straight through the tunnel, but not out of its roof
+        assert!(visible.contains(&Point3::new(2, 0, 0)));
+        assert!(!visible.contains(&Point3::new(1, 1, 0)));
+    }
+
+    #[test]
+    fn search_does_not_turn_back() {
+        // The only way to (0, 0, 2) is around the solid chunk, which means heading back east
+        let mut chunks = HashMap::new();
+        for position in [Point3::new(0, 0, 0), Point3::new(1, 0, 0), Point3::new(1, 0, 1), Point3::new(1, 0, 2), Point3::new(0, 0, 2)] {
+            chunks.insert(position, VisibilitySet::all());
+        }
+        chunks.insert(Point3::new(0, 0, 1), VisibilitySet::none());
+        let visible = visible_from(Point3::new(0, 0, 0), &chunks).unwrap();
+        assert!(visible.contains(&Point3::new(1, 0, 2)));
+        assert!(!visible.contains(&Point3::new(0, 0, 2)));
+    }
+
+    #[test]
+    fn unloaded_camera_chunk_draws_everything() {
+        let chunks = HashMap::new();
+        assert!(visible_from(Point3::new(0, 0, 0), &chunks).is_none());
+    }
+}
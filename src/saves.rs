@@ -0,0 +1,181 @@
+use std::{fs, io, path::PathBuf};
+
+use cgmath::Point3;
+use log::warn;
+
+use crate::{level::{self, Level}, player, world_time};
+
+/// A world in the saves directory, by the name of its directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorldSummary {
+    pub name: String,
+    pub level: Level,
+}
+
+/// The directory every world is saved in, each in a directory of its own named after the world.
+pub struct Saves {
+    directory: PathBuf,
+}
+
+impl Saves {
+    pub fn new(directory: impl Into<PathBuf>) -> Saves {
+        Saves {
+            directory: directory.into(),
+        }
+    }
+
+    pub fn get_directory(&self, name: &str) -> PathBuf {
+        self.directory.join(name)
+    }
+
+    /// Every world with a level file, sorted by name. Worlds whose level can't be read are left
+    /// out with a warning rather than failing the whole list.
+    pub fn list(&self) -> io::Result<Vec<WorldSummary>> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error),
+        };
+        let mut worlds = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = match entry.file_name().into_string() {
+                Ok(name) if entry.file_type()?.is_dir() => name,
+                _ => continue,
+            };
+            match Level::load(&entry.path()) {
+                Ok(Some(level)) => worlds.push(WorldSummary { name, level }),
+                Ok(None) => {}
+                Err(error) => warn!("Skipping world {}: {}", name, error),
+            }
+        }
+        worlds.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(worlds)
+    }
+
+    /// Creates a world and saves its level right away, so the world is listed before it is first played.
+    pub fn create(&self, name: &str, seed: u32, generator: &str) -> io::Result<Level> {
+        validate_name(name)?;
+        if generator != level::GENERATOR {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown generator {}", generator)));
+        }
+        let directory = self.get_directory(name);
+        if directory.exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("A world named {} already exists", name)));
+        }
+        // Standing just below the starting chunks' center, like the very first world did
+        let spawn = Point3::new(0.0, 1.0 - player::EYE_HEIGHT, 2.0);
+        let level = Level::new(seed, world_time::NOON, spawn);
+        level.save(&directory)?;
+        Ok(level)
+    }
+
+    pub fn load(&self, name: &str) -> io::Result<Level> {
+        validate_name(name)?;
+        Level::load(&self.get_directory(name))?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("There is no world named {}", name)))
+    }
+
+    pub fn rename(&self, name: &str, new_name: &str) -> io::Result<()> {
+        validate_name(name)?;
+        validate_name(new_name)?;
+        let new_directory = self.get_directory(new_name);
+        if new_directory.exists() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("A world named {} already exists", new_name)));
+        }
+        fs::rename(self.get_directory(name), new_directory)
+    }
+
+    /// Deletes the world and every chunk in it for good.
+    pub fn delete(&self, name: &str) -> io::Result<()> {
+        validate_name(name)?;
+        fs::remove_dir_all(self.get_directory(name))
+    }
+}
+
+/// World names become directory names, so they can't reach outside of the saves directory or
+/// hold characters some file systems reject.
+fn validate_name(name: &str) -> io::Result<()> {
+    let invalid = |message: String| Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    if name.trim().is_empty() {
+        return invalid("World names can't be empty".to_string());
+    }
+    if name != name.trim() || name.starts_with('.') {
+        return invalid(format!("World names can't start or end with spaces or start with a dot: {:?}", name));
+    }
+    if let Some(character) = name.chars().find(|character| character.is_control() || "/\\:*?\"<>|".contains(*character)) {
+        return invalid(format!("World names can't contain {:?}", character));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TestDirectory;
+
+    use super::*;
+
+    fn names(saves: &Saves) -> Vec<String> {
+        saves.list().unwrap().into_iter().map(|world| world.name).collect()
+    }
+
+    #[test]
+    fn no_saves_directory_is_no_worlds() {
+        let directory = TestDirectory::new("saves_empty");
+        assert!(Saves::new(directory.path()).list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn created_worlds_are_listed_by_name() {
+        let directory = TestDirectory::new("saves_create");
+        let saves = Saves::new(directory.path());
+        saves.create("Zebra", 1, level::GENERATOR).unwrap();
+        let level = saves.create("My World", 42, level::GENERATOR).unwrap();
+        // Stray files and directories without a level aren't worlds
+        fs::create_dir_all(directory.path().join("empty")).unwrap();
+        fs::write(directory.path().join("notes.txt"), "").unwrap();
+
+        assert_eq!(names(&saves), vec!["My World", "Zebra"]);
+        assert_eq!(saves.load("My World").unwrap(), level);
+        assert_eq!(level.seed, 42);
+        assert_eq!(saves.load("empty").unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn create_refuses_existing_worlds_and_unknown_generators() {
+        let directory = TestDirectory::new("saves_refuse");
+        let saves = Saves::new(directory.path());
+        saves.create("world", 1, level::GENERATOR).unwrap();
+        assert_eq!(saves.create("world", 2, level::GENERATOR).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(saves.load("world").unwrap().seed, 1);
+        assert_eq!(saves.create("flat", 1, "flat").unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(names(&saves), vec!["world"]);
+    }
+
+    #[test]
+    fn rename_and_delete() {
+        let directory = TestDirectory::new("saves_rename");
+        let saves = Saves::new(directory.path());
+        saves.create("a", 1, level::GENERATOR).unwrap();
+        saves.create("b", 2, level::GENERATOR).unwrap();
+
+        assert_eq!(saves.rename("a", "b").unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        saves.rename("a", "c").unwrap();
+        assert_eq!(names(&saves), vec!["b", "c"]);
+        assert_eq!(saves.load("c").unwrap().seed, 1);
+
+        saves.delete("b").unwrap();
+        assert_eq!(names(&saves), vec!["c"]);
+        assert!(saves.delete("b").is_err());
+    }
+
+    #[test]
+    fn names_stay_inside_the_saves_directory() {
+        for name in ["", "  ", "..", ".hidden", "../escape", "a/b", "a\\b", " padded", "what?", "tab\t"] {
+            assert!(validate_name(name).is_err(), "{:?}", name);
+        }
+        for name in ["world", "My World", "world-2 (copy)", "Ünïcode"] {
+            assert!(validate_name(name).is_ok(), "{:?}", name);
+        }
+    }
+}
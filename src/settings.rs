@@ -0,0 +1,276 @@
+use std::{fmt::Display, fs, io::ErrorKind, ops::RangeInclusive, path::{Path, PathBuf}};
+
+use log::LevelFilter;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{input::KeyBindings, render::lod};
+
+/// File the settings are read from by default, next to where the game is started.
+pub const SETTINGS_PATH: &str = "settings.toml";
+
+pub const DEFAULT_RENDER_DISTANCE: i32 = 2;
+/// Allowed values, in chunks, degrees and multiples of the default speed. The settings screen
+/// steps through the same ones.
+pub const RENDER_DISTANCES: RangeInclusive<i32> = 1..=12;
+pub const FOVS: RangeInclusive<f32> = 30.0..=110.0;
+pub const MOUSE_SENSITIVITIES: RangeInclusive<f32> = 0.1..=5.0;
+/// In physical pixels.
+pub const WINDOW_SIZES: RangeInclusive<u32> = 64..=16384;
+pub const UI_SCALES: RangeInclusive<f32> = 0.5..=8.0;
+/// In chunks past the render distance.
+pub const LOD_DISTANCES: RangeInclusive<i32> = 1..=16;
+
+/// Everything the player can configure, read from a TOML file like
+///
+/// ```toml
+/// render_distance = 4
+/// fov = 80.0
+///
+/// [controls]
+/// jump = "Space"
+/// ```
+///
+/// where anything missing keeps its default. Command line arguments can override any of them, see
+/// `cli::Arguments`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    /// How far chunks are drawn at full detail, in chunks. Lower levels of detail go further.
+    pub render_distance: i32,
+    /// Outer edge of each lower level of detail past the render distance, in chunks and increasing.
+    /// Up to `lod::MAX_LEVEL` of them, none draws everything at full detail.
+    pub lod_distances: Vec<i32>,
+    /// Vertical field of view in degrees.
+    pub fov: f32,
+    /// Multiplies how far the camera turns for the same mouse movement.
+    pub mouse_sensitivity: f32,
+    pub vsync: bool,
+    /// Size of the window when it opens, in physical pixels.
+    pub window_width: u32,
+    pub window_height: u32,
+    /// Borderless fullscreen on the current monitor.
+    pub fullscreen: bool,
+    /// Physical pixels per UI pixel, see `Ui`.
+    pub ui_scale: f32,
+    /// The most detailed log messages shown, one of off, error, warn, info, debug or trace.
+    pub log_level: LevelFilter,
+    /// The `[controls]` table, which `KeyBindings` reads itself.
+    #[serde(skip)]
+    pub key_bindings: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            render_distance: DEFAULT_RENDER_DISTANCE,
+            lod_distances: lod::DEFAULT_DISTANCES.to_vec(),
+            fov: 70.0,
+            mouse_sensitivity: 1.0,
+            vsync: true,
+            window_width: 1280,
+            window_height: 720,
+            fullscreen: false,
+            ui_scale: 2.0,
+            log_level: LevelFilter::Info,
+            key_bindings: KeyBindings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// Reads a settings file, or the defaults if there is no file.
+    pub fn load(path: &Path) -> Result<Settings, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Settings::from_toml(&text).map_err(|error| format!("Invalid {}: {}", path.display(), error)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Settings::default()),
+            Err(error) => Err(format!("Could not read {}: {}", path.display(), error)),
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Settings, String> {
+        let mut table: toml::value::Table = toml::from_str(text).map_err(|error| error.to_string())?;
+        let controls = table.remove("controls");
+        let mut settings: Settings = toml::Value::Table(table).try_into().map_err(|error| error.to_string())?;
+        settings.key_bindings = KeyBindings::from_controls(controls.as_ref())?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// Checks every value is in its range, naming the first one that isn't.
+    pub fn validate(&self) -> Result<(), String> {
+        check_range("render_distance", self.render_distance, &RENDER_DISTANCES)?;
+        if self.lod_distances.len() > lod::MAX_LEVEL {
+            return Err(format!("lod_distances can have at most {} rings, not {}", lod::MAX_LEVEL, self.lod_distances.len()));
+        }
+        for (index, distance) in self.lod_distances.iter().enumerate() {
+            check_range("lod_distances", *distance, &LOD_DISTANCES)?;
+            if index > 0 && *distance <= self.lod_distances[index - 1] {
+                return Err(format!("lod_distances must increase, not {:?}", self.lod_distances));
+            }
+        }
+        check_range("fov", self.fov, &FOVS)?;
+        check_range("mouse_sensitivity", self.mouse_sensitivity, &MOUSE_SENSITIVITIES)?;
+        check_range("window_width", self.window_width, &WINDOW_SIZES)?;
+        check_range("window_height", self.window_height, &WINDOW_SIZES)?;
+        check_range("ui_scale", self.ui_scale, &UI_SCALES)
+    }
+
+    /// Replaces the whole file.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_toml()).map_err(|error| format!("Could not write {}: {}", path.display(), error))
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut table = match toml::Value::try_from(self) {
+            Ok(toml::Value::Table(table)) => table,
+            _ => unreachable!("settings are a table of plain values"),
+        };
+        table.insert("controls".to_string(), toml::Value::Table(self.key_bindings.to_toml()));
+        // Written as a value, which puts plain values before tables like TOML needs
+        toml::to_string(&toml::Value::Table(table)).unwrap()
+    }
+
+    pub fn get_present_mode(&self) -> wgpu::PresentMode {
+        if self.vsync { wgpu::PresentMode::AutoVsync } else { wgpu::PresentMode::AutoNoVsync }
+    }
+}
+
+/// The settings in effect next to the ones read from the settings file, which differ by whatever
+/// was overridden on the command line. Changes from the settings screen are made to both, but only
+/// the file's are saved, so overrides only last for the run they were given for.
+pub struct SettingsFile {
+    path: PathBuf,
+    saved: Settings,
+    current: Settings,
+}
+
+impl SettingsFile {
+    pub fn load(path: PathBuf) -> Result<SettingsFile, String> {
+        let saved = Settings::load(&path)?;
+        Ok(SettingsFile { path, current: saved.clone(), saved })
+    }
+
+    /// The settings in effect.
+    pub fn get(&self) -> &Settings {
+        &self.current
+    }
+
+    /// Changes the settings in effect without touching the file, checking they are still valid.
+    pub fn override_for_run(&mut self, change: impl FnOnce(&mut Settings)) -> Result<(), String> {
+        change(&mut self.current);
+        self.current.validate()
+    }
+
+    /// Changes the settings in effect and the ones to be saved the same way. The change should set
+    /// values rather than step them, so both end up with what the player saw.
+    pub fn change(&mut self, change: impl Fn(&mut Settings)) {
+        change(&mut self.current);
+        change(&mut self.saved);
+    }
+
+    /// Replaces the file with the settings read from it and the changes made since.
+    pub fn save(&self) -> Result<(), String> {
+        self.saved.save(&self.path)
+    }
+}
+
+fn check_range<T: PartialOrd + Display>(name: &str, value: T, range: &RangeInclusive<T>) -> Result<(), String> {
+    if range.contains(&value) {
+        Ok(())
+    } else {
+        Err(format!("{} must be from {} to {}, not {}", name, range.start(), range.end(), value))
+    }
+}
+
+/// Moves a setting by `step` without leaving `range`.
+pub fn step<T: PartialOrd + Copy + std::ops::Add<Output = T>>(value: T, step: T, range: &RangeInclusive<T>) -> T {
+    let value = value + step;
+    if value < *range.start() {
+        *range.start()
+    } else if value > *range.end() {
+        *range.end()
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::VirtualKeyCode;
+
+    use crate::{input::{Action, Binding}, test_util::TestDirectory};
+
+    use super::*;
+
+    #[test]
+    fn missing_values_keep_their_defaults() {
+        let settings = Settings::from_toml("fov = 90.0\nlog_level = \"debug\"\n\n[controls]\njump = \"J\"\n").unwrap();
+        assert_eq!(settings.fov, 90.0);
+        assert_eq!(settings.log_level, LevelFilter::Debug);
+        assert_eq!(settings.render_distance, DEFAULT_RENDER_DISTANCE);
+        assert_eq!(settings.key_bindings.get_actions(Binding::Key(VirtualKeyCode::J)), vec![Action::Jump]);
+        assert_eq!(Settings::from_toml("").unwrap(), Settings::default());
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let mut settings = Settings {
+            render_distance: 5,
+            lod_distances: vec![2, 8],
+            fov: 85.0,
+            mouse_sensitivity: 1.5,
+            vsync: false,
+            window_width: 800,
+            window_height: 600,
+            fullscreen: true,
+            ui_scale: 3.0,
+            log_level: LevelFilter::Warn,
+            key_bindings: KeyBindings::default(),
+        };
+        settings.key_bindings.rebind(Action::Jump, Binding::Key(VirtualKeyCode::J));
+        assert_eq!(Settings::from_toml(&settings.to_toml()).unwrap(), settings);
+    }
+
+    #[test]
+    fn errors_name_the_problem() {
+        assert_eq!(Settings::from_toml("render_distance = 20\n").unwrap_err(), "render_distance must be from 1 to 12, not 20");
+        assert_eq!(Settings::from_toml("ui_scale = 0.0\n").unwrap_err(), "ui_scale must be from 0.5 to 8, not 0");
+        assert_eq!(Settings::from_toml("lod_distances = [1, 0]\n").unwrap_err(), "lod_distances must be from 1 to 16, not 0");
+        assert_eq!(Settings::from_toml("lod_distances = [2, 2]\n").unwrap_err(), "lod_distances must increase, not [2, 2]");
+        assert_eq!(Settings::from_toml("lod_distances = [1, 2, 3, 4]\n").unwrap_err(), "lod_distances can have at most 3 rings, not 4");
+        assert!(Settings::from_toml("lod_distances = []\n").is_ok());
+        assert!(Settings::from_toml("fov = nan\n").unwrap_err().starts_with("fov must be"));
+        assert!(Settings::from_toml("fvo = 90.0\n").unwrap_err().contains("unknown field `fvo`"));
+        assert!(Settings::from_toml("vsync = \"sometimes\"\n").unwrap_err().contains("vsync"));
+        assert_eq!(Settings::from_toml("[controls]\nfly = \"F\"\n").unwrap_err(), "Unknown action: fly");
+    }
+
+    #[test]
+    fn steps_stay_in_range() {
+        assert_eq!(step(2, 1, &RENDER_DISTANCES), 3);
+        assert_eq!(step(1, -1, &RENDER_DISTANCES), 1);
+        assert_eq!(step(108.0, 5.0, &FOVS), 110.0);
+    }
+
+    #[test]
+    fn overrides_are_not_saved() {
+        let directory = TestDirectory::new("settings_overrides");
+        fs::create_dir_all(directory.path()).unwrap();
+        let path = directory.path().join("settings.toml");
+        fs::write(&path, "fov = 80.0\n").unwrap();
+
+        let mut settings = SettingsFile::load(path.clone()).unwrap();
+        settings.override_for_run(|settings| {
+            settings.fullscreen = true;
+            settings.render_distance = 12;
+        }).unwrap();
+        settings.change(|settings| settings.mouse_sensitivity = 2.0);
+        assert_eq!((settings.get().fov, settings.get().fullscreen, settings.get().render_distance), (80.0, true, 12));
+        assert_eq!(settings.get().mouse_sensitivity, 2.0);
+
+        settings.save().unwrap();
+        let saved = Settings::load(&path).unwrap();
+        assert_eq!(saved, Settings { fov: 80.0, mouse_sensitivity: 2.0, ..Settings::default() });
+        assert!(settings.override_for_run(|settings| settings.fov = 1.0).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{level, saves::{Saves, WorldSummary}};
+
+/// Lets the player pick a world to play in the terminal before the window opens, creating,
+/// renaming and deleting worlds along the way. Returns the name of the world to play, or `None`
+/// when the player quits or the input ends.
+pub fn run(saves: &Saves, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<Option<String>> {
+    loop {
+        let worlds = saves.list()?;
+        writeln!(output)?;
+        if worlds.is_empty() {
+            writeln!(output, "No worlds yet")?;
+        }
+        for (index, world) in worlds.iter().enumerate() {
+            writeln!(output, "{}. {} (seed {}, {})", index + 1, world.name, world.level.seed, world.level.generator)?;
+        }
+        let choice = match prompt(input, output, "Enter a world's number to play it, c to create, r to rename, d to delete or q to quit: ")? {
+            Some(choice) => choice,
+            None => return Ok(None),
+        };
+        match choice.as_str() {
+            "q" => return Ok(None),
+            "c" => {
+                let name = match prompt(input, output, "Name: ")? {
+                    Some(name) => name,
+                    None => return Ok(None),
+                };
+                let seed = match prompt(input, output, "Seed (empty for random): ")?.as_deref() {
+                    None => return Ok(None),
+                    Some("") => rand::random(),
+                    Some(seed) => match seed.parse() {
+                        Ok(seed) => seed,
+                        Err(_) => {
+                            writeln!(output, "Seeds are whole numbers from 0 to {}", u32::MAX)?;
+                            continue;
+                        }
+                    },
+                };
+                let generator = match prompt(input, output, &format!("Generator (empty for {}): ", level::GENERATOR))? {
+                    Some(generator) if generator.is_empty() => level::GENERATOR.to_string(),
+                    Some(generator) => generator,
+                    None => return Ok(None),
+                };
+                match saves.create(&name, seed, &generator) {
+                    Ok(_) => return Ok(Some(name)),
+                    Err(error) => writeln!(output, "{}", error)?,
+                }
+            }
+            "r" => {
+                if let Some(name) = choose(&worlds, input, output, "Number of the world to rename: ")? {
+                    let new_name = match prompt(input, output, "New name: ")? {
+                        Some(new_name) => new_name,
+                        None => return Ok(None),
+                    };
+                    if let Err(error) = saves.rename(&name, &new_name) {
+                        writeln!(output, "{}", error)?;
+                    }
+                }
+            }
+            "d" => {
+                if let Some(name) = choose(&worlds, input, output, "Number of the world to delete: ")? {
+                    let confirmation = prompt(input, output, &format!("Delete {} for good? (y/n): ", name))?;
+                    if confirmation.as_deref() == Some("y") {
+                        if let Err(error) = saves.delete(&name) {
+                            writeln!(output, "{}", error)?;
+                        }
+                    }
+                }
+            }
+            number => match get_world(&worlds, number) {
+                Some(name) => return Ok(Some(name)),
+                None => writeln!(output, "Unknown choice {}", number)?,
+            },
+        }
+    }
+}
+
+/// Name of the world with this number in the list, counting from 1.
+fn get_world(worlds: &[WorldSummary], number: &str) -> Option<String> {
+    let index = number.parse::<usize>().ok()?.checked_sub(1)?;
+    worlds.get(index).map(|world| world.name.clone())
+}
+
+/// Asks for the number of a world, returning its name. Returns `None` when there is no such world
+/// or the input has ended.
+fn choose(worlds: &[WorldSummary], input: &mut impl BufRead, output: &mut impl Write, text: &str) -> io::Result<Option<String>> {
+    let number = match prompt(input, output, text)? {
+        Some(number) => number,
+        None => return Ok(None),
+    };
+    let name = get_world(worlds, &number);
+    if name.is_none() {
+        writeln!(output, "There is no world {}", number)?;
+    }
+    Ok(name)
+}
+
+/// Asks for a line, returning it without the line break or surrounding spaces, or `None` once the
+/// input has ended.
+fn prompt(input: &mut impl BufRead, output: &mut impl Write, text: &str) -> io::Result<Option<String>> {
+    write!(output, "{}", text)?;
+    output.flush()?;
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TestDirectory;
+
+    use super::*;
+
+    fn run_with(saves: &Saves, input: &str) -> (Option<String>, String) {
+        let mut output = Vec::new();
+        let chosen = run(saves, &mut input.as_bytes(), &mut output).unwrap();
+        (chosen, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn creating_a_world_plays_it() {
+        let directory = TestDirectory::new("menu_create");
+        let saves = Saves::new(directory.path());
+        let (chosen, output) = run_with(&saves, "c\nMy World\n99\n\n");
+        assert!(output.contains("No worlds yet"));
+        assert_eq!(chosen.as_deref(), Some("My World"));
+        let level = saves.load("My World").unwrap();
+        assert_eq!(level.seed, 99);
+        assert_eq!(level.generator, level::GENERATOR);
+    }
+
+    #[test]
+    fn worlds_are_played_by_number() {
+        let directory = TestDirectory::new("menu_play");
+        let saves = Saves::new(directory.path());
+        saves.create("a", 1, level::GENERATOR).unwrap();
+        saves.create("b", 2, level::GENERATOR).unwrap();
+        let (chosen, output) = run_with(&saves, "3\n0\n2\n");
+        assert!(output.contains("2. b (seed 2, perlin)"));
+        assert!(output.contains("Unknown choice 3"));
+        assert_eq!(chosen.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn bad_input_goes_back_to_the_list() {
+        let directory = TestDirectory::new("menu_bad");
+        let saves = Saves::new(directory.path());
+        saves.create("a", 1, level::GENERATOR).unwrap();
+        let (chosen, output) = run_with(&saves, "c\na\n\n\nc\nb\nnot a seed\nc\nc\n1\nflat\nq\n");
+        assert_eq!(chosen, None);
+        assert!(output.contains("already exists"));
+        assert!(output.contains("Seeds are whole numbers"));
+        assert!(output.contains("Unknown generator flat"));
+        assert_eq!(saves.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rename_and_delete_worlds() {
+        let directory = TestDirectory::new("menu_rename");
+        let saves = Saves::new(directory.path());
+        saves.create("a", 1, level::GENERATOR).unwrap();
+        saves.create("b", 2, level::GENERATOR).unwrap();
+        // Renaming a to c, declining to delete b, then deleting it
+        let (chosen, _) = run_with(&saves, "r\n1\nc\nd\n1\nn\nd\n1\ny\n");
+        assert_eq!(chosen, None);
+        let worlds = saves.list().unwrap();
+        assert_eq!(worlds.len(), 1);
+        assert_eq!(worlds[0].name, "c");
+        assert_eq!(worlds[0].level.seed, 1);
+    }
+}
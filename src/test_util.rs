@@ -0,0 +1,23 @@
+use std::{fs, path::{Path, PathBuf}};
+
+/// A fresh directory for a test's files, removed again when dropped. Names have to be unique
+/// across the tests, since they all run in the same process.
+pub struct TestDirectory(PathBuf);
+
+impl TestDirectory {
+    pub fn new(name: &str) -> TestDirectory {
+        let path = std::env::temp_dir().join(format!("minecraft_clone_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        TestDirectory(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDirectory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+/// Number of game ticks simulated per second, regardless of the frame rate.
+pub const TICKS_PER_SECOND: u32 = 60;
+/// Ticks simulated in one frame at most, so a long stall doesn't make the game spend the next
+/// frames catching up.
+const MAX_TICKS_PER_FRAME: u32 = 10;
+
+/// Turns variable frame times into a whole number of fixed length ticks. The time left over
+/// between ticks is kept for the next frame and tells how far rendering is between two ticks.
+pub struct FixedTimestep {
+    tick_length: Duration,
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    pub fn new(ticks_per_second: u32) -> FixedTimestep {
+        FixedTimestep {
+            tick_length: Duration::from_secs(1) / ticks_per_second,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    /// Adds the time the last frame took and returns how many ticks to simulate for it.
+    pub fn advance(&mut self, frame_time: Duration) -> u32 {
+        self.accumulator += frame_time;
+        let mut ticks = 0;
+        while self.accumulator >= self.tick_length {
+            self.accumulator -= self.tick_length;
+            ticks += 1;
+            if ticks == MAX_TICKS_PER_FRAME {
+                self.accumulator = Duration::ZERO;
+                break;
+            }
+        }
+        ticks
+    }
+
+    /// How far the current frame is between the last tick and the next one, from 0 to 1.
+    pub fn get_alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.tick_length.as_secs_f32()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ticks simulated over a second of frames at the given frame rate.
+    fn ticks_in_a_second(fps: u32) -> u32 {
+        let mut timestep = FixedTimestep::new(TICKS_PER_SECOND);
+        (0..fps).map(|_| timestep.advance(Duration::from_secs(1) / fps)).sum()
+    }
+
+    #[test]
+    fn tick_rate_does_not_depend_on_frame_rate() {
+        for fps in [30, 60, 144, 240] {
+            let ticks = ticks_in_a_second(fps);
+            // Rounding each frame down to nanoseconds can lose the very last tick
+            assert!(ticks == TICKS_PER_SECOND || ticks == TICKS_PER_SECOND - 1, "{} ticks at {} fps", ticks, fps);
+        }
+    }
+
+    #[test]
+    fn leftover_time_carries_over() {
+        let mut timestep = FixedTimestep::new(10);
+        assert_eq!(timestep.advance(Duration::from_millis(150)), 1);
+        assert!((timestep.get_alpha() - 0.5).abs() < 0.001);
+        assert_eq!(timestep.advance(Duration::from_millis(60)), 1);
+        assert!((timestep.get_alpha() - 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn fast_frames_run_no_ticks() {
+        let mut timestep = FixedTimestep::new(20);
+        assert_eq!(timestep.advance(Duration::from_millis(10)), 0);
+        assert!((timestep.get_alpha() - 0.2).abs() < 0.001);
+        assert_eq!(timestep.advance(Duration::from_millis(40)), 1);
+    }
+
+    #[test]
+    fn long_stalls_are_dropped() {
+        let mut timestep = FixedTimestep::new(20);
+        assert_eq!(timestep.advance(Duration::from_secs(10)), MAX_TICKS_PER_FRAME);
+        assert_eq!(timestep.get_alpha(), 0.0);
+        assert_eq!(timestep.advance(Duration::from_millis(50)), 1);
+    }
+}
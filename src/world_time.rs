@@ -0,0 +1,187 @@
+use std::f32::consts::PI;
+
+use cgmath::Vector3;
+
+/// Number of game ticks in a full day and night.
+pub const DAY_LENGTH: u64 = 24000;
+
+pub const SUNRISE: u64 = 0;
+pub const NOON: u64 = 6000;
+pub const SUNSET: u64 = 12000;
+pub const MIDNIGHT: u64 = 18000;
+
+const MIN_SKY_LIGHT: f32 = 0.15;
+
+pub struct WorldTime {
+    time: u64,
+    frozen: bool,
+}
+
+impl WorldTime {
+    pub fn new(time: u64) -> WorldTime {
+        WorldTime {
+            time: time % DAY_LENGTH,
+            frozen: false,
+        }
+    }
+
+    /// Advances the clock by one game tick, unless the time is frozen.
+    pub fn tick(&mut self) {
+        if !self.frozen {
+            self.time = (self.time + 1) % DAY_LENGTH;
+        }
+    }
+
+    pub fn get_time(&self) -> u64 {
+        self.time
+    }
+
+    pub fn set_time(&mut self, time: u64) {
+        self.time = time % DAY_LENGTH;
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen
+    }
+
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    /// Angle of the sun around the world in radians, 0 at sunrise and PI at sunset.
+    pub fn get_celestial_angle(&self) -> f32 {
+        self.time as f32 / DAY_LENGTH as f32 * 2.0 * PI
+    }
+
+    /// Unit vector pointing towards the sun. The sun rises in the east and the moon is always opposite it.
+    pub fn get_sun_direction(&self) -> Vector3<f32> {
+        let angle = self.get_celestial_angle();
+        Vector3::new(-angle.cos(), angle.sin(), 0.0)
+    }
+
+    /// How strongly the sky lights the world, from `MIN_SKY_LIGHT` at night up to 1.0 during the day.
+    pub fn get_sky_light(&self) -> f32 {
+        let daylight = smoothstep(-0.2, 0.2, self.get_sun_direction().y);
+        MIN_SKY_LIGHT + (1.0 - MIN_SKY_LIGHT) * daylight
+    }
+
+    /// Runs a `time` command, e.g. `time set noon`, `time add 1000`, `time freeze` or `time unfreeze`.
+    pub fn execute(&mut self, command: &str) -> Result<String, String> {
+        let mut args = command.split_whitespace();
+        if args.next() != Some("time") {
+            return Err(format!("Unknown command: {}", command));
+        }
+        // Only set and add take a value, anything after it is a mistake
+        match (args.next(), args.next(), args.next()) {
+            (Some("set"), Some(value), None) => {
+                self.set_time(parse_time(value)?);
+                Ok(format!("Set the time to {}", self.time))
+            }
+            (Some("add"), Some(value), None) => {
+                let amount = value.parse::<u64>().map_err(|_| format!("Invalid time: {}", value))?;
+                self.set_time(self.time + amount % DAY_LENGTH);
+                Ok(format!("Set the time to {}", self.time))
+            }
+            (Some("query"), None, None) => match self.is_frozen() {
+                true => Ok(format!("The time is {} (frozen)", self.get_time())),
+                false => Ok(format!("The time is {}", self.get_time())),
+            },
+            (Some("freeze"), None, None) => {
+                self.set_frozen(true);
+                Ok(String::from("Froze the time"))
+            }
+            (Some("unfreeze"), None, None) => {
+                self.set_frozen(false);
+                Ok(String::from("Unfroze the time"))
+            }
+            _ => Err(String::from("Usage: time <set <ticks|sunrise|noon|sunset|midnight>|add <ticks>|query|freeze|unfreeze>")),
+        }
+    }
+}
+
+fn parse_time(value: &str) -> Result<u64, String> {
+    match value {
+        "sunrise" | "day" => Ok(SUNRISE),
+        "noon" => Ok(NOON),
+        "sunset" => Ok(SUNSET),
+        "midnight" | "night" => Ok(MIDNIGHT),
+        _ => value.parse::<u64>().map_err(|_| format!("Invalid time: {}", value)),
+    }
+}
+
+pub fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_wrap_around_the_day() {
+        let mut time = WorldTime::new(DAY_LENGTH - 1);
+        time.tick();
+        assert_eq!(time.get_time(), 0);
+        time.set_time(DAY_LENGTH + NOON);
+        assert_eq!(time.get_time(), NOON);
+    }
+
+    #[test]
+    fn frozen_time_stands_still() {
+        let mut time = WorldTime::new(NOON);
+        time.set_frozen(true);
+        time.tick();
+        assert_eq!(time.get_time(), NOON);
+        time.set_frozen(false);
+        time.tick();
+        assert_eq!(time.get_time(), NOON + 1);
+    }
+
+    #[test]
+    fn sun_is_overhead_at_noon_and_below_at_midnight() {
+        let noon = WorldTime::new(NOON);
+        assert!((noon.get_celestial_angle() - PI / 2.0).abs() < 1e-6);
+        assert!((noon.get_sun_direction().y - 1.0).abs() < 1e-6);
+        assert_eq!(noon.get_sky_light(), 1.0);
+
+        let midnight = WorldTime::new(MIDNIGHT);
+        assert!((midnight.get_celestial_angle() - PI * 1.5).abs() < 1e-6);
+        assert!((midnight.get_sun_direction().y + 1.0).abs() < 1e-6);
+        assert_eq!(midnight.get_sky_light(), MIN_SKY_LIGHT);
+
+        // Halfway between the two while the sun is on the horizon
+        let sunrise = WorldTime::new(SUNRISE).get_sky_light();
+        assert!((sunrise - (MIN_SKY_LIGHT + 1.0) / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn times_are_named_or_numbered() {
+        assert_eq!(parse_time("noon"), Ok(NOON));
+        assert_eq!(parse_time("night"), Ok(MIDNIGHT));
+        assert_eq!(parse_time("day"), Ok(SUNRISE));
+        assert_eq!(parse_time("1234"), Ok(1234));
+        assert_eq!(parse_time("-5"), Err("Invalid time: -5".to_string()));
+        assert_eq!(parse_time("teatime"), Err("Invalid time: teatime".to_string()));
+    }
+
+    #[test]
+    fn commands_change_the_time() {
+        let mut time = WorldTime::new(SUNRISE);
+        assert_eq!(time.execute("time set noon"), Ok("Set the time to 6000".to_string()));
+        assert_eq!(time.execute("time add 20000"), Ok("Set the time to 2000".to_string()));
+        assert_eq!(time.execute("time freeze"), Ok("Froze the time".to_string()));
+        assert!(time.is_frozen());
+        assert_eq!(time.execute("time query"), Ok("The time is 2000 (frozen)".to_string()));
+        assert_eq!(time.execute("time unfreeze"), Ok("Unfroze the time".to_string()));
+        assert_eq!(time.execute("  time   query "), Ok("The time is 2000".to_string()));
+
+        assert_eq!(time.execute("weather clear"), Err("Unknown command: weather clear".to_string()));
+        assert_eq!(time.execute("time set later"), Err("Invalid time: later".to_string()));
+        assert!(time.execute("time set").unwrap_err().starts_with("Usage"));
+        assert!(time.execute("time freeze now").unwrap_err().starts_with("Usage"));
+        assert!(time.execute("time set noon garbage").unwrap_err().starts_with("Usage"));
+        assert!(time.execute("time add 5 5").unwrap_err().starts_with("Usage"));
+        assert_eq!(time.get_time(), 2000);
+    }
+}